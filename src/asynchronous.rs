@@ -0,0 +1,320 @@
+//! Async wrappers for [`Database`], [`WriteTransaction`] and [`Range`]
+//!
+//! These wrappers are runtime-agnostic. Waiting for the write transaction slot does not occupy a
+//! thread: the waiting task is woken when the in-progress write transaction completes. Blocking
+//! work, such as committing a transaction or reading pages from disk, is run on a pool of
+//! long-lived background threads belonging to the [`AsyncDatabase`], so that it does not block the
+//! executor.
+
+use crate::{
+    AccessGuard, CommitError, Database, Key, Range, ReadTransaction, Result, TransactionError,
+    Value, WriteTransaction,
+};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock, mpsc};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+// Number of entries read from a range by each background read
+const RANGE_BATCH_SIZE: usize = 64;
+// Default number of background threads which run blocking work for an AsyncDatabase
+const DEFAULT_WORKER_THREADS: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Runs jobs on a fixed number of background threads, which are started when first needed, and
+// exit once the pool has been dropped and the queued jobs have finished
+struct WorkerPool {
+    threads: usize,
+    queue: OnceLock<Mutex<mpsc::Sender<Job>>>,
+}
+
+impl WorkerPool {
+    fn new(threads: usize) -> Self {
+        assert!(threads > 0, "at least one worker thread is required");
+        Self {
+            threads,
+            queue: OnceLock::new(),
+        }
+    }
+
+    // Queues a job to be run by one of the background threads
+    fn run(&self, job: Job) {
+        let queue = self.queue.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            for i in 0..self.threads {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("redb-async-{i}"))
+                    .spawn(move || {
+                        loop {
+                            // The lock is released before running the job, so that other threads
+                            // can take the next one
+                            let Ok(job) = receiver.lock().unwrap().recv() else {
+                                return;
+                            };
+                            job();
+                        }
+                    })
+                    .unwrap();
+            }
+            Mutex::new(sender)
+        });
+        queue.lock().unwrap().send(job).unwrap();
+    }
+}
+
+struct BlockingState<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+// Runs a closure on one of the background threads, and resolves to its result
+struct Blocking<T> {
+    pool: Arc<WorkerPool>,
+    task: Option<Box<dyn FnOnce() -> T + Send>>,
+    state: Arc<Mutex<BlockingState<T>>>,
+}
+
+impl<T: Send + 'static> Blocking<T> {
+    fn new(pool: Arc<WorkerPool>, task: impl FnOnce() -> T + Send + 'static) -> Self {
+        Self {
+            pool,
+            task: Some(Box::new(task)),
+            state: Arc::new(Mutex::new(BlockingState {
+                result: None,
+                waker: None,
+            })),
+        }
+    }
+}
+
+impl<T: Send + 'static> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(result) = state.result.take() {
+            return match result {
+                Ok(value) => Poll::Ready(value),
+                Err(payload) => panic::resume_unwind(payload),
+            };
+        }
+        state.waker = Some(cx.waker().clone());
+        drop(state);
+
+        // Start the task lazily, so that the future does nothing until it is polled
+        if let Some(task) = self.task.take() {
+            let shared = self.state.clone();
+            self.pool.run(Box::new(move || {
+                // Panics are caught, so that they are resumed in the task and do not stop the thread
+                let result = panic::catch_unwind(AssertUnwindSafe(task));
+                let mut state = shared.lock().unwrap();
+                state.result = Some(result);
+                if let Some(waker) = state.waker.take() {
+                    drop(state);
+                    waker.wake();
+                }
+            }));
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Async wrapper around a [`Database`]
+pub struct AsyncDatabase {
+    inner: Database,
+    pool: Arc<WorkerPool>,
+}
+
+impl AsyncDatabase {
+    /// Wraps the given [`Database`]
+    ///
+    /// Blocking work started through the wrapper, such as [`AsyncWriteTransaction::commit`], is
+    /// run on a pool of four background threads, which are started when first needed. Use
+    /// [`AsyncDatabase::with_worker_threads`] to choose the size of the pool.
+    pub fn new(database: Database) -> Self {
+        Self::with_worker_threads(database, DEFAULT_WORKER_THREADS)
+    }
+
+    /// Wraps the given [`Database`], running blocking work on a pool of `threads` background
+    /// threads
+    ///
+    /// The pool belongs to this wrapper, and to the transactions and ranges created through it.
+    /// Its threads exit once all of those have been dropped. Waiting for the write transaction
+    /// slot in [`AsyncDatabase::begin_write`] does not use a thread from the pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero
+    pub fn with_worker_threads(database: Database, threads: usize) -> Self {
+        Self {
+            inner: database,
+            pool: Arc::new(WorkerPool::new(threads)),
+        }
+    }
+
+    /// Returns the wrapped [`Database`]
+    pub fn database(&self) -> &Database {
+        &self.inner
+    }
+
+    /// Unwraps this [`AsyncDatabase`], returning the wrapped [`Database`]
+    pub fn into_inner(self) -> Database {
+        self.inner
+    }
+
+    /// Begins a write transaction
+    ///
+    /// Behaves like [`Database::begin_write`], except that if a write is in progress the returned
+    /// future waits for it to complete, without blocking the executor
+    pub fn begin_write(&self) -> BeginWrite<'_> {
+        BeginWrite { database: self }
+    }
+
+    /// Wraps the given [`Range`], so that its entries are read on this database's background
+    /// threads
+    ///
+    /// No entries are read until [`AsyncRange::next`] or [`AsyncRange::poll_next`] is called. Use
+    /// [`crate::ReadOnlyTable::range`] to create a [`Range`] which keeps its transaction alive
+    pub fn range<K: Key + Send + 'static, V: Value + Send + 'static>(
+        &self,
+        range: Range<'static, K, V>,
+    ) -> AsyncRange<K, V> {
+        AsyncRange {
+            pool: self.pool.clone(),
+            inner: Some(range),
+            buffered: VecDeque::new(),
+            pending: None,
+            exhausted: false,
+        }
+    }
+
+    /// Begins a read transaction
+    ///
+    /// Read transactions never wait for a write transaction, so this is equivalent to
+    /// [`Database::begin_read`]
+    pub fn begin_read(&self) -> Result<ReadTransaction, TransactionError> {
+        self.inner.begin_read()
+    }
+}
+
+/// Future returned by [`AsyncDatabase::begin_write`]
+pub struct BeginWrite<'db> {
+    database: &'db AsyncDatabase,
+}
+
+impl Future for BeginWrite<'_> {
+    type Output = Result<AsyncWriteTransaction, TransactionError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.database.inner.poll_begin_write(cx.waker()) {
+            Some(result) => Poll::Ready(result.map(|inner| AsyncWriteTransaction {
+                inner,
+                pool: self.database.pool.clone(),
+            })),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Async wrapper around a [`WriteTransaction`]
+///
+/// Tables are opened and modified through the wrapped [`WriteTransaction`], which this type
+/// dereferences to
+pub struct AsyncWriteTransaction {
+    inner: WriteTransaction,
+    pool: Arc<WorkerPool>,
+}
+
+impl AsyncWriteTransaction {
+    /// Commit the transaction
+    ///
+    /// The commit, including any fsync, is performed on a background thread. See
+    /// [`WriteTransaction::commit`]
+    pub fn commit(self) -> impl Future<Output = Result<(), CommitError>> {
+        let inner = self.inner;
+        Blocking::new(self.pool, move || inner.commit())
+    }
+
+    /// Abort the transaction
+    ///
+    /// See [`WriteTransaction::abort`]
+    pub fn abort(self) -> Result {
+        self.inner.abort()
+    }
+
+    /// Unwraps this [`AsyncWriteTransaction`], returning the wrapped [`WriteTransaction`]
+    pub fn into_inner(self) -> WriteTransaction {
+        self.inner
+    }
+}
+
+impl Deref for AsyncWriteTransaction {
+    type Target = WriteTransaction;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for AsyncWriteTransaction {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+type RangeEntry<K, V> = Result<(AccessGuard<'static, K>, AccessGuard<'static, V>)>;
+type RangeBatch<K, V> = (Range<'static, K, V>, VecDeque<RangeEntry<K, V>>);
+
+/// Async wrapper around a [`Range`]
+///
+/// Created with [`AsyncDatabase::range`]. Entries are read in batches on a background thread
+pub struct AsyncRange<K: Key + 'static, V: Value + 'static> {
+    pool: Arc<WorkerPool>,
+    inner: Option<Range<'static, K, V>>,
+    buffered: VecDeque<RangeEntry<K, V>>,
+    pending: Option<Blocking<RangeBatch<K, V>>>,
+    exhausted: bool,
+}
+
+impl<K: Key + Send + 'static, V: Value + Send + 'static> AsyncRange<K, V> {
+    /// Returns the next entry in the range, or `None` if the range is exhausted
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> impl Future<Output = Option<RangeEntry<K, V>>> + '_ {
+        std::future::poll_fn(|cx| self.poll_next(cx))
+    }
+
+    /// Polls for the next entry in the range
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<RangeEntry<K, V>>> {
+        loop {
+            if let Some(entry) = self.buffered.pop_front() {
+                return Poll::Ready(Some(entry));
+            }
+            if self.exhausted {
+                return Poll::Ready(None);
+            }
+            if self.pending.is_none() {
+                let mut range = self.inner.take().unwrap();
+                self.pending = Some(Blocking::new(self.pool.clone(), move || {
+                    let batch = range.by_ref().take(RANGE_BATCH_SIZE).collect();
+                    (range, batch)
+                }));
+            }
+            let (range, batch) = match Pin::new(self.pending.as_mut().unwrap()).poll(cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Poll::Pending,
+            };
+            self.pending = None;
+            self.exhausted = batch.len() < RANGE_BATCH_SIZE;
+            self.inner = Some(range);
+            self.buffered = batch;
+        }
+    }
+}
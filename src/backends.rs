@@ -1,2 +1,4 @@
-pub use crate::tree_store::InMemoryBackend;
 pub use crate::tree_store::file_backend::FileBackend;
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+pub use crate::tree_store::file_backend::IoUringBackend;
+pub use crate::tree_store::{InMemoryBackend, InMemoryImage};
@@ -0,0 +1,299 @@
+use crate::transaction_tracker::TransactionId;
+use crate::tree_store::{
+    Checksum, InternalTableDefinition, Page, PageNumber, PageSource, TableTree, TableType,
+    TransactionalMemory,
+};
+use crate::{
+    Builder, Database, DatabaseError, PageEncryption, ReadOnlyTable, ReadTransaction,
+    ReadableTable, Result, StorageError, TableDefinition,
+};
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::sync::Arc;
+
+// Incremental backups are stored as redb databases. Each one contains the definitions of all the
+// tables in its snapshot, and the pages of those tables which are not already contained in one of
+// the previous backups of its chain.
+//
+// Pages are keyed by their page number and checksum. Since a branch's checksum covers the
+// checksums of its children, a page that is already in the chain implies that its entire subtree
+// is also in the chain.
+const METADATA_TABLE: TableDefinition<&str, u64> = TableDefinition::new("redb_backup_metadata");
+const TABLES_TABLE: TableDefinition<&str, InternalTableDefinition> =
+    TableDefinition::new("redb_backup_tables");
+const PAGES_TABLE: TableDefinition<(u64, Checksum), &[u8]> =
+    TableDefinition::new("redb_backup_pages");
+
+const TRANSACTION_ID: &str = "transaction_id";
+const BASE_TRANSACTION_ID: &str = "base_transaction_id";
+const PAGE_SIZE: &str = "page_size";
+
+// Creates a new file at `path`, and passes it to `f`. The file is removed if `f` fails
+pub(crate) fn create_new_file(
+    path: &Path,
+    f: impl FnOnce(File) -> Result<(), DatabaseError>,
+) -> Result<(), DatabaseError> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(StorageError::from)?;
+    let result = f(file);
+    if result.is_err() {
+        // Don't leave a partial file behind
+        let _ = fs::remove_file(path);
+    }
+    result
+}
+
+fn page_key(page_number: PageNumber, checksum: Checksum) -> (u64, Checksum) {
+    (u64::from_le_bytes(page_number.to_le_bytes()), checksum)
+}
+
+fn invalid_chain(msg: String) -> StorageError {
+    StorageError::Corrupted(format!("Invalid backup chain: {msg}"))
+}
+
+struct OwnedPage {
+    page_number: PageNumber,
+    data: Vec<u8>,
+}
+
+impl Page for OwnedPage {
+    fn memory(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn get_page_number(&self) -> PageNumber {
+        self.page_number
+    }
+}
+
+struct BackupFile {
+    pages: ReadOnlyTable<(u64, Checksum), &'static [u8]>,
+    transaction: ReadTransaction,
+    transaction_id: u64,
+    base_transaction_id: Option<u64>,
+    page_size: u64,
+    // Must be dropped last
+    _database: Database,
+}
+
+impl BackupFile {
+    fn open(path: &Path, builder: &Builder) -> Result<Self, DatabaseError> {
+        let not_a_backup = || invalid_chain(format!("{} is not a backup", path.display()));
+
+        let database = builder.open(path)?;
+        let transaction = database.begin_read().map_err(|e| e.into_storage_error())?;
+        let metadata = transaction
+            .open_table(METADATA_TABLE)
+            .map_err(|_| not_a_backup())?;
+        let transaction_id = metadata
+            .get(TRANSACTION_ID)?
+            .ok_or_else(not_a_backup)?
+            .value();
+        let base_transaction_id = metadata.get(BASE_TRANSACTION_ID)?.map(|x| x.value());
+        let page_size = metadata.get(PAGE_SIZE)?.ok_or_else(not_a_backup)?.value();
+        let pages = transaction
+            .open_table(PAGES_TABLE)
+            .map_err(|_| not_a_backup())?;
+
+        Ok(Self {
+            pages,
+            transaction,
+            transaction_id,
+            base_transaction_id,
+            page_size,
+            _database: database,
+        })
+    }
+
+    fn contains(&self, page_number: PageNumber, checksum: Checksum) -> Result<bool> {
+        Ok(self.pages.get(page_key(page_number, checksum))?.is_some())
+    }
+}
+
+struct BackupChain {
+    files: Vec<BackupFile>,
+}
+
+impl BackupChain {
+    // `builder` is used to open each backup
+    fn open(paths: &[impl AsRef<Path>], builder: &Builder) -> Result<Self, DatabaseError> {
+        let mut files: Vec<BackupFile> = vec![];
+        for path in paths {
+            let path = path.as_ref();
+            let file = BackupFile::open(path, builder)?;
+            match (files.last(), file.base_transaction_id) {
+                (None, None) => {}
+                (None, Some(_)) => {
+                    return Err(invalid_chain(format!(
+                        "{} is an incremental backup, but the chain must start with a full backup",
+                        path.display()
+                    ))
+                    .into());
+                }
+                (Some(_), None) => {
+                    return Err(invalid_chain(format!(
+                        "{} is a full backup, but only the first backup in the chain may be full",
+                        path.display()
+                    ))
+                    .into());
+                }
+                (Some(previous), Some(base)) => {
+                    if base != previous.transaction_id {
+                        return Err(invalid_chain(format!(
+                            "{} is based on transaction {base}, but the previous backup is of transaction {}",
+                            path.display(),
+                            previous.transaction_id
+                        ))
+                        .into());
+                    }
+                    if file.page_size != previous.page_size {
+                        return Err(invalid_chain(format!(
+                            "{} has page size {}, but the previous backup has page size {}",
+                            path.display(),
+                            file.page_size,
+                            previous.page_size
+                        ))
+                        .into());
+                    }
+                }
+            }
+            files.push(file);
+        }
+
+        Ok(Self { files })
+    }
+
+    fn contains(&self, page_number: PageNumber, checksum: Checksum) -> Result<bool> {
+        for file in &self.files {
+            if file.contains(page_number, checksum)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl PageSource for BackupChain {
+    type Page = OwnedPage;
+
+    fn get_page(&self, page_number: PageNumber, checksum: Checksum) -> Result<OwnedPage> {
+        for file in self.files.iter().rev() {
+            if let Some(data) = file.pages.get(page_key(page_number, checksum))? {
+                return Ok(OwnedPage {
+                    page_number,
+                    data: data.value().to_vec(),
+                });
+            }
+        }
+        Err(invalid_chain(format!("page {page_number:?} is missing")))
+    }
+}
+
+// Writes the snapshot of `tables` as an incremental backup on top of the backups in `previous`
+pub(crate) fn write_incremental_backup(
+    mem: &TransactionalMemory,
+    tables: &TableTree,
+    transaction_id: TransactionId,
+    previous: &[impl AsRef<Path>],
+    file: File,
+) -> Result<(), DatabaseError> {
+    // Backups are encrypted in the same way as the database
+    let mut builder = Database::builder();
+    builder.set_shared_encryption(mem.encryption());
+    let chain = BackupChain::open(previous, &builder)?;
+    let page_size = mem.get_page_size() as u64;
+    if let Some(last) = chain.files.last() {
+        if last.page_size != page_size {
+            return Err(invalid_chain(format!(
+                "database has page size {page_size}, but the previous backup has page size {}",
+                last.page_size
+            ))
+            .into());
+        }
+        if last.transaction_id > transaction_id.raw_id() {
+            return Err(invalid_chain(format!(
+                "the previous backup is of transaction {}, which is newer than this snapshot",
+                last.transaction_id
+            ))
+            .into());
+        }
+    }
+
+    let backup = builder.create_file(file)?;
+    let txn = backup.begin_write().map_err(|e| e.into_storage_error())?;
+    {
+        let mut pages = txn
+            .open_table(PAGES_TABLE)
+            .map_err(|e| e.into_storage_error_or_corrupted("Internal error"))?;
+        let mut definitions = txn
+            .open_table(TABLES_TABLE)
+            .map_err(|e| e.into_storage_error_or_corrupted("Internal error"))?;
+        for table_type in [TableType::Normal, TableType::Multimap] {
+            for name in tables.list_tables(table_type)? {
+                let definition = tables
+                    .get_table_untyped(&name, table_type)
+                    .map_err(|e| e.into_storage_error_or_corrupted("Internal corruption"))?
+                    .unwrap();
+                definition.visit_pages_with_checksums(mem, |page, checksum| {
+                    let page_number = page.get_page_number();
+                    if chain.contains(page_number, checksum)? {
+                        // This page, and therefore all of its descendants, are already backed up
+                        return Ok(false);
+                    }
+                    pages.insert(page_key(page_number, checksum), page.memory())?;
+                    Ok(true)
+                })?;
+                definitions.insert(name.as_str(), &definition)?;
+            }
+        }
+
+        let mut metadata = txn
+            .open_table(METADATA_TABLE)
+            .map_err(|e| e.into_storage_error_or_corrupted("Internal error"))?;
+        metadata.insert(TRANSACTION_ID, transaction_id.raw_id())?;
+        metadata.insert(PAGE_SIZE, page_size)?;
+        if let Some(last) = chain.files.last() {
+            metadata.insert(BASE_TRANSACTION_ID, last.transaction_id)?;
+        }
+    }
+    txn.commit().map_err(|e| e.into_storage_error())?;
+
+    Ok(())
+}
+
+// Restores the snapshot of the last backup in `chain` as a new database
+pub(crate) fn restore_backup_chain(
+    chain: &[impl AsRef<Path>],
+    file: File,
+    encryption: Option<Arc<dyn PageEncryption>>,
+) -> Result<(), DatabaseError> {
+    let mut builder = Database::builder();
+    builder.set_shared_encryption(encryption.clone());
+    let chain = BackupChain::open(chain, &builder)?;
+    let Some(last) = chain.files.last() else {
+        return Err(invalid_chain("the chain is empty".to_string()).into());
+    };
+
+    let page_size = usize::try_from(last.page_size)
+        .map_err(|_| invalid_chain(format!("invalid page size {}", last.page_size)))?;
+    let mut destination = Database::create_copy_destination(file, page_size, encryption)?;
+    let txn = destination
+        .begin_write()
+        .map_err(|e| e.into_storage_error())?;
+    let definitions = last
+        .transaction
+        .open_table(TABLES_TABLE)
+        .map_err(|e| e.into_storage_error_or_corrupted("Invalid backup"))?;
+    for entry in definitions.iter()? {
+        let (name, definition) = entry?;
+        txn.copy_table_from(name.value(), definition.value(), &chain)
+            .map_err(|e| e.into_storage_error_or_corrupted("Invalid backup"))?;
+    }
+    txn.commit().map_err(|e| e.into_storage_error())?;
+
+    destination.compact_copy()
+}
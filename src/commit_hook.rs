@@ -0,0 +1,197 @@
+use crate::tree_store::InternalTableDefinition;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+pub(crate) type CommitCallback = dyn Fn(&CommitEvent) + Send + Sync;
+
+pub(crate) struct CommitHook {
+    pub(crate) callback: Arc<CommitCallback>,
+    pub(crate) capture_key_changes: bool,
+}
+
+/// The net effect of a committed transaction on a key
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ChangeKind {
+    /// The key did not exist before the transaction
+    Inserted,
+    /// The key existed before the transaction, and its value may have been changed
+    Updated,
+    /// The key was removed by the transaction
+    Removed,
+}
+
+/// A key which was changed by a committed transaction
+#[derive(Clone, Debug)]
+pub struct KeyChange {
+    table: String,
+    key: Vec<u8>,
+    kind: ChangeKind,
+}
+
+impl KeyChange {
+    /// Name of the table containing the key
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// The serialized key. Use [`crate::Value::from_bytes`] to deserialize it
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// How the key was changed
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+}
+
+/// Describes a successfully committed write transaction
+///
+/// Passed to the hook set with [`crate::Builder::set_commit_hook`]
+#[derive(Clone, Debug)]
+pub struct CommitEvent {
+    transaction_id: u64,
+    tables: Vec<String>,
+    key_changes: Option<Vec<KeyChange>>,
+}
+
+impl CommitEvent {
+    /// Id of the committed transaction
+    pub fn transaction_id(&self) -> u64 {
+        self.transaction_id
+    }
+
+    /// Names of the tables, including multimap tables, which were created, modified, renamed or
+    /// deleted by the transaction
+    pub fn tables(&self) -> &[String] {
+        &self.tables
+    }
+
+    /// Keys which were inserted, updated or removed by the transaction, ordered by table name and
+    /// then by serialized key
+    ///
+    /// Only available if enabled with [`crate::Builder::set_commit_hook_key_changes`]. Keys in
+    /// multimap tables, and keys removed by deleting a table, are not reported. Returns `None` if
+    /// key changes are disabled, or the transaction restored a savepoint
+    pub fn key_changes(&self) -> Option<&[KeyChange]> {
+        self.key_changes.as_deref()
+    }
+}
+
+// State of a key at the start of the transaction, and now
+#[derive(Clone)]
+struct KeyState {
+    existed: bool,
+    exists: bool,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct KeyChangeLog {
+    tables: HashMap<String, BTreeMap<Vec<u8>, KeyState>>,
+    invalidated: bool,
+}
+
+impl KeyChangeLog {
+    pub(crate) fn record(&mut self, table: &str, key: &[u8], existed: bool, exists: bool) {
+        if !self.tables.contains_key(table) {
+            self.tables.insert(table.to_string(), BTreeMap::new());
+        }
+        let keys = self.tables.get_mut(table).unwrap();
+        if let Some(state) = keys.get_mut(key) {
+            state.exists = exists;
+        } else {
+            keys.insert(key.to_vec(), KeyState { existed, exists });
+        }
+    }
+
+    pub(crate) fn rename_table(&mut self, name: &str, new_name: &str) {
+        if let Some(keys) = self.tables.remove(name) {
+            self.tables.insert(new_name.to_string(), keys);
+        }
+    }
+
+    pub(crate) fn delete_table(&mut self, name: &str) {
+        self.tables.remove(name);
+    }
+
+    // Called when the changes can no longer be tracked, such as when a savepoint is restored
+    pub(crate) fn invalidate(&mut self) {
+        self.invalidated = true;
+        self.tables.clear();
+    }
+
+    fn into_changes(self) -> Option<Vec<KeyChange>> {
+        if self.invalidated {
+            return None;
+        }
+        let mut tables: Vec<_> = self.tables.into_iter().collect();
+        tables.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut result = vec![];
+        for (table, keys) in tables {
+            for (key, state) in keys {
+                let kind = match (state.existed, state.exists) {
+                    (false, false) => continue,
+                    (false, true) => ChangeKind::Inserted,
+                    (true, true) => ChangeKind::Updated,
+                    (true, false) => ChangeKind::Removed,
+                };
+                result.push(KeyChange {
+                    table: table.clone(),
+                    key,
+                    kind,
+                });
+            }
+        }
+        Some(result)
+    }
+}
+
+// `before` and `after` must be sorted by name
+pub(crate) fn commit_event(
+    transaction_id: u64,
+    before: &[(String, InternalTableDefinition)],
+    after: &[(String, InternalTableDefinition)],
+    key_changes: Option<KeyChangeLog>,
+) -> CommitEvent {
+    let mut tables = vec![];
+    let mut before = before.iter().peekable();
+    let mut after = after.iter().peekable();
+    loop {
+        match (before.peek(), after.peek()) {
+            (None, None) => break,
+            (Some((name, _)), None) => {
+                tables.push(name.clone());
+                before.next();
+            }
+            (None, Some((name, _))) => {
+                tables.push(name.clone());
+                after.next();
+            }
+            (Some((old_name, old)), Some((new_name, new))) => match old_name.cmp(new_name) {
+                Ordering::Less => {
+                    tables.push(old_name.clone());
+                    before.next();
+                }
+                Ordering::Greater => {
+                    tables.push(new_name.clone());
+                    after.next();
+                }
+                Ordering::Equal => {
+                    if old != new {
+                        tables.push(new_name.clone());
+                    }
+                    before.next();
+                    after.next();
+                }
+            },
+        }
+    }
+
+    CommitEvent {
+        transaction_id,
+        tables,
+        key_changes: key_changes.and_then(KeyChangeLog::into_changes),
+    }
+}
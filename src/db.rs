@@ -14,8 +14,12 @@ use std::fs::{File, OpenOptions};
 use std::marker::PhantomData;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::task::Waker;
+use std::time::Duration;
 use std::{io, thread};
 
+use crate::backup::{create_new_file, restore_backup_chain};
+use crate::commit_hook::{CommitCallback, CommitEvent, CommitHook};
 use crate::error::TransactionError;
 use crate::sealed::Sealed;
 use crate::transactions::{
@@ -38,6 +42,16 @@ pub trait StorageBackend: 'static + Debug + Send + Sync {
     /// If `len` + `offset` exceeds the length of the storage an appropriate `Error` should be returned or a panic may occur.
     fn read(&self, offset: u64, len: usize) -> std::result::Result<Vec<u8>, io::Error>;
 
+    /// Reads the specified array of bytes from the storage into `buffer`, which has the length of
+    /// the array.
+    ///
+    /// redb reads pages with this method, so implementing it avoids allocating a `Vec` for every
+    /// page. The default implementation calls [`StorageBackend::read`].
+    fn read_into(&self, offset: u64, buffer: &mut [u8]) -> std::result::Result<(), io::Error> {
+        buffer.copy_from_slice(&self.read(offset, buffer.len())?);
+        Ok(())
+    }
+
     /// Sets the length of the storage.
     ///
     /// When extending the storage the new positions should be zero initialized.
@@ -53,6 +67,18 @@ pub trait StorageBackend: 'static + Debug + Send + Sync {
     /// Writes the specified array to the storage.
     fn write(&self, offset: u64, data: &[u8]) -> std::result::Result<(), io::Error>;
 
+    /// Writes each array to the storage, at the offset paired with it.
+    ///
+    /// The arrays do not overlap, and may be written in any order. redb writes all of the pages
+    /// which were buffered by a transaction with a single call to this method when it commits.
+    /// The default implementation calls [`StorageBackend::write`] for each of them.
+    fn write_vectored(&self, writes: &[(u64, &[u8])]) -> std::result::Result<(), io::Error> {
+        for (offset, data) in writes {
+            self.write(*offset, data)?;
+        }
+        Ok(())
+    }
+
     /// Release any resources held by the backend
     ///
     /// Note: redb will not access the backend after calling this method and will call it exactly
@@ -62,6 +88,33 @@ pub trait StorageBackend: 'static + Debug + Send + Sync {
     }
 }
 
+/// Encrypts the pages of a database before they are written to its [`StorageBackend`]
+///
+/// Each page is encrypted individually, and its ciphertext must be the same length as its
+/// plaintext, so a length-preserving cipher that is tweaked by the page's offset, such as AES-XTS
+/// with the offset as the tweak, should be used. The first page of the file, which contains the
+/// database header, is not encrypted. Pages written to the write-ahead log are also encrypted.
+///
+/// The implementation owns the key. A checksum of an empty page encrypted at offset 0, which is
+/// never used for data, is stored in the header when the database is created, and opening the
+/// database with a different key returns [`DatabaseError::EncryptionKeyMismatch`].
+///
+/// Detecting tampering is out of scope. Since the ciphertext is the same length as the page, it
+/// cannot carry an authentication tag, and pages are not verified when they are read, so a page
+/// that has been tampered with decrypts to arbitrary contents, which may be returned as data or
+/// cause an error or a panic. The checksums that the parent of each page stores are only verified
+/// by [`Database::check_integrity`] and by a repair. An implementation which must reject tampered
+/// pages has to authenticate them itself, in [`PageEncryption::decrypt`].
+pub trait PageEncryption: 'static + Debug + Send + Sync {
+    /// Encrypts, in place, the page that will be written at `offset`
+    fn encrypt(&self, offset: u64, page: &mut [u8]) -> std::result::Result<(), io::Error>;
+
+    /// Decrypts, in place, the page that was read from `offset`
+    ///
+    /// An error may be returned if the page is known to have been tampered with
+    fn decrypt(&self, offset: u64, page: &mut [u8]) -> std::result::Result<(), io::Error>;
+}
+
 pub trait TableHandle: Sealed {
     // Returns the name of the table
     fn name(&self) -> &str;
@@ -337,6 +390,7 @@ impl Drop for TransactionGuard {
 pub struct Database {
     mem: Arc<TransactionalMemory>,
     transaction_tracker: Arc<TransactionTracker>,
+    commit_hook: Option<Arc<CommitHook>>,
 }
 
 impl Database {
@@ -397,6 +451,9 @@ impl Database {
     /// Returns `Ok(true)` if the database passed integrity checks; `Ok(false)` if it failed but was repaired,
     /// and `Err(Corrupted)` if the check failed and the file could not be repaired
     pub fn check_integrity(&mut self) -> Result<bool, DatabaseError> {
+        // Reloading discards any commits which are only in the write-ahead log
+        self.mem.checkpoint()?;
+        self.transaction_tracker.clear_pending_non_durable_commits();
         let allocator_hash = self.mem.allocator_hash();
         let mut was_clean = Arc::get_mut(&mut self.mem)
             .unwrap()
@@ -440,7 +497,7 @@ impl Database {
         // Use 2-phase commit to avoid any possible security issues. Plus this compaction is going to be so slow that it doesn't matter.
         // Once https://github.com/cberner/redb/issues/829 is fixed, we should upgrade this to use quick-repair -- that way the user
         // can cancel the compaction without requiring a full repair afterwards
-        let mut txn = self.begin_write().map_err(|e| e.into_storage_error())?;
+        let mut txn = self.begin_internal_write()?;
         if txn.list_persistent_savepoints()?.next().is_some() {
             return Err(CompactionError::PersistentSavepointExists);
         }
@@ -450,12 +507,12 @@ impl Database {
         txn.set_two_phase_commit(true);
         txn.commit().map_err(|e| e.into_storage_error())?;
         // Repeat, just in case executing list_persistent_savepoints() created a new table
-        let mut txn = self.begin_write().map_err(|e| e.into_storage_error())?;
+        let mut txn = self.begin_internal_write()?;
         txn.set_two_phase_commit(true);
         txn.commit().map_err(|e| e.into_storage_error())?;
         // There can't be any outstanding transactions because we have a `&mut self`, so all pending free pages
         // should have been cleared out by the above commit()
-        let txn = self.begin_write().map_err(|e| e.into_storage_error())?;
+        let txn = self.begin_internal_write()?;
         assert!(!txn.pending_free_pages()?);
         txn.abort()?;
 
@@ -464,7 +521,7 @@ impl Database {
         loop {
             let mut progress = false;
 
-            let mut txn = self.begin_write().map_err(|e| e.into_storage_error())?;
+            let mut txn = self.begin_internal_write()?;
             if txn.compact_pages()? {
                 progress = true;
                 txn.commit().map_err(|e| e.into_storage_error())?;
@@ -473,16 +530,16 @@ impl Database {
             }
 
             // Double commit to free up the relocated pages for reuse
-            let mut txn = self.begin_write().map_err(|e| e.into_storage_error())?;
+            let mut txn = self.begin_internal_write()?;
             txn.set_two_phase_commit(true);
             txn.commit().map_err(|e| e.into_storage_error())?;
             // Triple commit to free up the relocated pages for reuse
             // TODO: this really shouldn't be necessary, but the data freed tree is a system table
             // and so free'ing up its pages causes more deletes from the system tree
-            let mut txn = self.begin_write().map_err(|e| e.into_storage_error())?;
+            let mut txn = self.begin_internal_write()?;
             txn.set_two_phase_commit(true);
             txn.commit().map_err(|e| e.into_storage_error())?;
-            let txn = self.begin_write().map_err(|e| e.into_storage_error())?;
+            let txn = self.begin_internal_write()?;
             assert!(!txn.pending_free_pages()?);
             txn.abort()?;
 
@@ -671,6 +728,10 @@ impl Database {
         read_cache_size_bytes: usize,
         write_cache_size_bytes: usize,
         repair_callback: &(dyn Fn(&mut RepairSession) + 'static),
+        commit_hook: Option<Arc<CommitHook>>,
+        write_ahead_log: Option<Box<dyn StorageBackend>>,
+        encryption: Option<Arc<dyn PageEncryption>>,
+        memory_map: Option<File>,
     ) -> Result<Self, DatabaseError> {
         #[cfg(feature = "logging")]
         let file_path = format!("{:?}", &file);
@@ -683,6 +744,9 @@ impl Database {
             region_size,
             read_cache_size_bytes,
             write_cache_size_bytes,
+            write_ahead_log,
+            encryption,
+            memory_map,
         )?;
         let mut mem = Arc::new(mem);
         // If the last transaction used 2-phase commit and updated the allocator state table, then
@@ -710,6 +774,7 @@ impl Database {
         let db = Database {
             mem,
             transaction_tracker: Arc::new(TransactionTracker::new(next_transaction_id)),
+            commit_hook,
         };
 
         // Restore the tracker state for any persistent savepoints
@@ -792,6 +857,89 @@ impl Database {
         ))
     }
 
+    /// Writes a compacted copy of the database to a new file
+    ///
+    /// The copy contains all data committed before this method was called. The database may
+    /// continue to be read and written while the copy is made. See [`ReadTransaction::copy_to`]
+    pub fn backup_to(&self, path: impl AsRef<Path>) -> Result<(), DatabaseError> {
+        self.begin_read()
+            .map_err(|e| e.into_storage_error())?
+            .copy_to(path)
+    }
+
+    /// Writes an incremental backup of the database to a new file
+    ///
+    /// The backup contains all data committed before this method was called. See
+    /// [`ReadTransaction::incremental_backup_to`]
+    pub fn incremental_backup_to(
+        &self,
+        path: impl AsRef<Path>,
+        previous: &[impl AsRef<Path>],
+    ) -> Result<(), DatabaseError> {
+        self.begin_read()
+            .map_err(|e| e.into_storage_error())?
+            .incremental_backup_to(path, previous)
+    }
+
+    /// Restores a chain of backups to a new database file
+    ///
+    /// `chain` must start with a full backup, followed by the incremental backups that were taken
+    /// after it, in order. The restored database contains the snapshot of the last backup in the
+    /// chain. Returns an error if `path` already exists
+    ///
+    /// Use [`Builder::restore_backup_chain`] to restore encrypted backups
+    pub fn restore_backup_chain(
+        chain: &[impl AsRef<Path>],
+        path: impl AsRef<Path>,
+    ) -> Result<(), DatabaseError> {
+        Builder::new().restore_backup_chain(chain, path)
+    }
+
+    // Creates a new, empty, database to copy another database into
+    pub(crate) fn create_copy_destination(
+        file: File,
+        page_size: usize,
+        encryption: Option<Arc<dyn PageEncryption>>,
+    ) -> Result<Database, DatabaseError> {
+        let builder = Builder::new();
+        Database::new(
+            Box::new(FileBackend::new(file)?),
+            true,
+            page_size,
+            None,
+            builder.read_cache_size_bytes,
+            builder.write_cache_size_bytes,
+            &builder.repair_callback,
+            None,
+            None,
+            encryption,
+            None,
+        )
+    }
+
+    // Compacts a database that was created by create_copy_destination()
+    pub(crate) fn compact_copy(&mut self) -> Result<(), DatabaseError> {
+        match self.compact() {
+            Ok(_) => Ok(()),
+            Err(CompactionError::Storage(err)) => Err(err.into()),
+            // The copy is private to its creator, so it has no other transactions or savepoints
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Writes the transactions in the write-ahead log to the database file, and empties the log
+    ///
+    /// Blocks until any write transaction in progress completes. Does nothing if the database does
+    /// not have a write-ahead log. See [`Builder::set_write_ahead_log`]
+    pub fn checkpoint(&self) -> Result<(), TransactionError> {
+        let txn = self.begin_write()?;
+        self.mem.checkpoint()?;
+        self.transaction_tracker.clear_pending_non_durable_commits();
+        txn.abort()?;
+
+        Ok(())
+    }
+
     /// Convenience method for [`Builder::new`]
     pub fn builder() -> Builder {
         Builder::new()
@@ -809,8 +957,99 @@ impl Database {
             self.transaction_tracker.start_write_transaction(),
             self.transaction_tracker.clone(),
         );
-        WriteTransaction::new(guard, self.transaction_tracker.clone(), self.mem.clone())
-            .map_err(|e| e.into())
+        WriteTransaction::new(
+            guard,
+            self.transaction_tracker.clone(),
+            self.mem.clone(),
+            self.commit_hook.clone(),
+        )
+        .map_err(|e| e.into())
+    }
+
+    /// Begins a write transaction, without blocking
+    ///
+    /// Behaves like [`Self::begin_write`], except that if a write is in progress this function
+    /// returns [`TransactionError::WriteTransactionInProgress`] immediately
+    pub fn try_begin_write(&self) -> Result<WriteTransaction, TransactionError> {
+        // Fail early if there has been an I/O error -- nothing can be committed in that case
+        self.mem.check_io_errors()?;
+        let Some(id) = self.transaction_tracker.try_start_write_transaction() else {
+            return Err(TransactionError::WriteTransactionInProgress);
+        };
+        let guard = TransactionGuard::new_write(id, self.transaction_tracker.clone());
+        WriteTransaction::new(
+            guard,
+            self.transaction_tracker.clone(),
+            self.mem.clone(),
+            self.commit_hook.clone(),
+        )
+        .map_err(|e| e.into())
+    }
+
+    /// Begins a write transaction, waiting at most `timeout` for an in-progress write to complete
+    ///
+    /// Behaves like [`Self::begin_write`], except that if the in-progress write has not completed
+    /// before `timeout` elapses, [`TransactionError::WriteTransactionInProgress`] is returned
+    pub fn begin_write_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<WriteTransaction, TransactionError> {
+        // Fail early if there has been an I/O error -- nothing can be committed in that case
+        self.mem.check_io_errors()?;
+        let Some(id) = self
+            .transaction_tracker
+            .start_write_transaction_timeout(timeout)
+        else {
+            return Err(TransactionError::WriteTransactionInProgress);
+        };
+        let guard = TransactionGuard::new_write(id, self.transaction_tracker.clone());
+        WriteTransaction::new(
+            guard,
+            self.transaction_tracker.clone(),
+            self.mem.clone(),
+            self.commit_hook.clone(),
+        )
+        .map_err(|e| e.into())
+    }
+
+    // Non-blocking variant of begin_write() for async callers. Returns None, and registers the waker,
+    // if a write is in progress
+    pub(crate) fn poll_begin_write(
+        &self,
+        waker: &Waker,
+    ) -> Option<Result<WriteTransaction, TransactionError>> {
+        // Fail early if there has been an I/O error -- nothing can be committed in that case
+        if let Err(err) = self.mem.check_io_errors() {
+            return Some(Err(err.into()));
+        }
+        let id = self
+            .transaction_tracker
+            .poll_start_write_transaction(waker)?;
+        let guard = TransactionGuard::new_write(id, self.transaction_tracker.clone());
+        Some(
+            WriteTransaction::new(
+                guard,
+                self.transaction_tracker.clone(),
+                self.mem.clone(),
+                self.commit_hook.clone(),
+            )
+            .map_err(|e| e.into()),
+        )
+    }
+
+    // Begins a write transaction for database maintenance, which does not invoke the commit hook
+    fn begin_internal_write(&self) -> Result<WriteTransaction, StorageError> {
+        self.mem.check_io_errors()?;
+        let guard = TransactionGuard::new_write(
+            self.transaction_tracker.start_write_transaction(),
+            self.transaction_tracker.clone(),
+        );
+        WriteTransaction::new(
+            guard,
+            self.transaction_tracker.clone(),
+            self.mem.clone(),
+            None,
+        )
     }
 
     /// Begins a read transaction
@@ -827,6 +1066,24 @@ impl Database {
         ReadTransaction::new(self.get_memory(), guard)
     }
 
+    /// Waits until a transaction newer than `transaction_id` has been committed, or `timeout`
+    /// elapses
+    ///
+    /// Returns `true` if a newer transaction was committed, in which case it is visible to read
+    /// transactions begun after this method returns. `transaction_id` is typically that of the
+    /// last read transaction, as returned by [`ReadTransaction::transaction_id`]
+    pub fn wait_for_commit_after(
+        &self,
+        transaction_id: u64,
+        timeout: Duration,
+    ) -> Result<bool, StorageError> {
+        self.transaction_tracker.wait_for_commit_after(
+            &self.mem,
+            TransactionId::new(transaction_id),
+            timeout,
+        )
+    }
+
     fn ensure_allocator_state_table(&self) -> Result<(), Error> {
         // If the allocator state table is already up to date, we're done
         if Self::get_allocator_state_table(&self.mem)?.is_some() {
@@ -836,7 +1093,7 @@ impl Database {
         // Make a new quick-repair commit to update the allocator state table
         #[cfg(feature = "logging")]
         debug!("Writing allocator state table");
-        let mut tx = self.begin_write()?;
+        let mut tx = self.begin_internal_write()?;
         tx.set_quick_repair(true);
         tx.commit()?;
 
@@ -893,6 +1150,12 @@ pub struct Builder {
     read_cache_size_bytes: usize,
     write_cache_size_bytes: usize,
     repair_callback: Box<dyn Fn(&mut RepairSession)>,
+    commit_hook: Option<Arc<CommitCallback>>,
+    capture_key_changes: bool,
+    write_ahead_log: bool,
+    encryption: Option<Arc<dyn PageEncryption>>,
+    #[cfg(target_os = "linux")]
+    memory_mapped_reads: bool,
 }
 
 impl Builder {
@@ -914,6 +1177,12 @@ impl Builder {
             // TODO: Default should probably take into account the total system memory
             write_cache_size_bytes: 0,
             repair_callback: Box::new(|_| {}),
+            commit_hook: None,
+            capture_key_changes: false,
+            write_ahead_log: false,
+            encryption: None,
+            #[cfg(target_os = "linux")]
+            memory_mapped_reads: false,
         };
 
         result.set_cache_size(1024 * 1024 * 1024);
@@ -935,6 +1204,149 @@ impl Builder {
         self
     }
 
+    /// Set a hook which will be invoked after each write transaction is successfully committed
+    ///
+    /// The hook is called on the thread that committed the transaction, after the transaction's
+    /// changes are visible to new read transactions and the write lock has been released. Commits
+    /// made internally, such as by [`Database::compact`], do not invoke the hook.
+    pub fn set_commit_hook(
+        &mut self,
+        hook: impl Fn(&CommitEvent) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.commit_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Set whether the [`CommitEvent`]s passed to the commit hook report the keys which were
+    /// inserted, updated and removed
+    ///
+    /// ## Defaults
+    ///
+    /// Defaults to `false`, since recording the keys adds overhead to every write
+    pub fn set_commit_hook_key_changes(&mut self, enabled: bool) -> &mut Self {
+        self.capture_key_changes = enabled;
+        self
+    }
+
+    fn commit_hook(&self) -> Option<Arc<CommitHook>> {
+        self.commit_hook.as_ref().map(|callback| {
+            Arc::new(CommitHook {
+                callback: callback.clone(),
+                capture_key_changes: self.capture_key_changes,
+            })
+        })
+    }
+
+    /// Set whether commits are written to a write-ahead log
+    ///
+    /// When enabled, a commit appends the pages it wrote to a log, and only the log is synced,
+    /// rather than the database file. Since the log is written sequentially, this is faster for
+    /// workloads with many small transactions. The database file is synced and the log emptied by
+    /// a checkpoint, which happens when the log grows past 64MiB, when a transaction with 2-phase
+    /// commit or quick-repair is committed, when the database is closed, and when
+    /// [`Database::checkpoint`] is called.
+    ///
+    /// When the log grows past 64MiB, the commit which caused it to do so performs the checkpoint
+    /// on the committing thread before it returns, but after releasing the write lock, so other
+    /// write transactions may begin and commit while the database file is synced. If one of them
+    /// appends to the log during the checkpoint, the log is not emptied, and the next commit
+    /// checkpoints it again.
+    ///
+    /// The log is stored next to the database file, with `-wal` appended to its name. This only
+    /// applies to [`Builder::create`] and [`Builder::open`]; use
+    /// [`Builder::create_with_write_ahead_log`] to provide the log's backend directly. If the
+    /// database is not closed cleanly, it must be reopened with its log, or the transactions which
+    /// were not checkpointed are lost.
+    ///
+    /// ## Defaults
+    ///
+    /// Defaults to `false`
+    pub fn set_write_ahead_log(&mut self, enabled: bool) -> &mut Self {
+        self.write_ahead_log = enabled;
+        self
+    }
+
+    fn open_write_ahead_log(
+        &self,
+        path: &Path,
+    ) -> Result<Option<Box<dyn StorageBackend>>, DatabaseError> {
+        if !self.write_ahead_log {
+            return Ok(None);
+        }
+        let mut log_path = path.as_os_str().to_owned();
+        log_path.push("-wal");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(log_path)?;
+
+        Ok(Some(Box::new(FileBackend::new(file)?)))
+    }
+
+    /// Set the encryption applied to the pages of the database
+    ///
+    /// A new database is encrypted if this is set when it is created, and an existing database
+    /// must be opened with the same encryption that it was created with. Backups and copies of
+    /// the database, such as those written by [`Database::backup_to`], are encrypted in the same
+    /// way. See [`PageEncryption`]
+    ///
+    /// Encrypted databases are stored with file format version 4, so that versions of redb which
+    /// do not support encryption refuse to open them.
+    ///
+    /// ## Defaults
+    ///
+    /// Defaults to no encryption
+    pub fn set_encryption(&mut self, encryption: impl PageEncryption) -> &mut Self {
+        self.encryption = Some(Arc::new(encryption));
+        self
+    }
+
+    pub(crate) fn set_shared_encryption(
+        &mut self,
+        encryption: Option<Arc<dyn PageEncryption>>,
+    ) -> &mut Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Enable reading committed pages through a memory map of the database file
+    ///
+    /// Values returned by [`crate::ReadOnlyTable::get`] then borrow directly from the mapping,
+    /// instead of being copied into the cache. Other reads, and all reads by write transactions,
+    /// still go through the cache. Pages referenced by a value remain allocated until the value
+    /// is dropped, even if its transaction has been dropped, and the database file stays locked
+    /// until then, so it cannot be reopened in the meantime.
+    ///
+    /// Ignored if the database is encrypted, or not opened from a file. The database file must not
+    /// be truncated by another process while it is open, or reading it will crash this process
+    ///
+    /// ## Defaults
+    ///
+    /// Defaults to `false`
+    #[cfg(target_os = "linux")]
+    pub fn set_memory_mapped_reads(&mut self, enabled: bool) -> &mut Self {
+        self.memory_mapped_reads = enabled;
+        self
+    }
+
+    // Returns the backend for a database file, and the handle to memory map, if enabled
+    fn file_backend(
+        &self,
+        file: File,
+    ) -> Result<(Box<dyn StorageBackend>, Option<File>), DatabaseError> {
+        #[cfg(target_os = "linux")]
+        if self.memory_mapped_reads && self.encryption.is_none() {
+            let memory_map = file.try_clone()?;
+            let mut backend = FileBackend::new(file)?;
+            // The memory map holds the lock until all of the mapped pages have been dropped
+            backend.keep_lock_on_close();
+            return Ok((Box::new(backend), Some(memory_map)));
+        }
+        Ok((Box::new(FileBackend::new(file)?), None))
+    }
+
     /// Set the internal page size of the database
     ///
     /// Valid values are powers of two, greater than or equal to 512
@@ -974,31 +1386,44 @@ impl Builder {
             .write(true)
             .create(true)
             .truncate(false)
-            .open(path)?;
+            .open(path.as_ref())?;
+        let (backend, memory_map) = self.file_backend(file)?;
 
         Database::new(
-            Box::new(FileBackend::new(file)?),
+            backend,
             true,
             self.page_size,
             self.region_size,
             self.read_cache_size_bytes,
             self.write_cache_size_bytes,
             &self.repair_callback,
+            self.commit_hook(),
+            self.open_write_ahead_log(path.as_ref())?,
+            self.encryption.clone(),
+            memory_map,
         )
     }
 
     /// Opens an existing redb database.
     pub fn open(&self, path: impl AsRef<Path>) -> Result<Database, DatabaseError> {
-        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref())?;
+        let (backend, memory_map) = self.file_backend(file)?;
 
         Database::new(
-            Box::new(FileBackend::new(file)?),
+            backend,
             false,
             self.page_size,
             None,
             self.read_cache_size_bytes,
             self.write_cache_size_bytes,
             &self.repair_callback,
+            self.commit_hook(),
+            self.open_write_ahead_log(path.as_ref())?,
+            self.encryption.clone(),
+            memory_map,
         )
     }
 
@@ -1006,18 +1431,27 @@ impl Builder {
     ///
     /// The file must be empty or contain a valid database.
     pub fn create_file(&self, file: File) -> Result<Database, DatabaseError> {
+        let (backend, memory_map) = self.file_backend(file)?;
         Database::new(
-            Box::new(FileBackend::new(file)?),
+            backend,
             true,
             self.page_size,
             self.region_size,
             self.read_cache_size_bytes,
             self.write_cache_size_bytes,
             &self.repair_callback,
+            self.commit_hook(),
+            None,
+            self.encryption.clone(),
+            memory_map,
         )
     }
 
     /// Open an existing or create a new database with the given backend.
+    ///
+    /// The backend may already contain a database, such as one loaded with
+    /// [`crate::backends::InMemoryBackend::from_bytes`]. It is opened without repair if it was
+    /// closed cleanly.
     pub fn create_with_backend(
         &self,
         backend: impl StorageBackend,
@@ -1030,6 +1464,47 @@ impl Builder {
             self.read_cache_size_bytes,
             self.write_cache_size_bytes,
             &self.repair_callback,
+            self.commit_hook(),
+            None,
+            self.encryption.clone(),
+            None,
+        )
+    }
+
+    /// Restores a chain of backups, encrypted with this builder's encryption, to a new database file
+    ///
+    /// The restored database is encrypted in the same way. See [`Database::restore_backup_chain`]
+    pub fn restore_backup_chain(
+        &self,
+        chain: &[impl AsRef<Path>],
+        path: impl AsRef<Path>,
+    ) -> Result<(), DatabaseError> {
+        create_new_file(path.as_ref(), |file| {
+            restore_backup_chain(chain, file, self.encryption.clone())
+        })
+    }
+
+    /// Open an existing or create a new database with the given backend, and a write-ahead log
+    /// stored in `log`
+    ///
+    /// See [`Builder::set_write_ahead_log`]
+    pub fn create_with_write_ahead_log(
+        &self,
+        backend: impl StorageBackend,
+        log: impl StorageBackend,
+    ) -> Result<Database, DatabaseError> {
+        Database::new(
+            Box::new(backend),
+            true,
+            self.page_size,
+            self.region_size,
+            self.read_cache_size_bytes,
+            self.write_cache_size_bytes,
+            &self.repair_callback,
+            self.commit_hook(),
+            Some(Box::new(log)),
+            self.encryption.clone(),
+            None,
         )
     }
 }
@@ -1050,12 +1525,13 @@ mod test {
     use std::fs::File;
     use std::io::{ErrorKind, Read, Seek, SeekFrom};
     use std::sync::Arc;
-    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
     #[derive(Debug)]
     struct FailingBackend {
         inner: FileBackend,
         countdown: Arc<AtomicU64>,
+        fail_sync: Arc<AtomicBool>,
     }
 
     impl FailingBackend {
@@ -1063,6 +1539,7 @@ mod test {
             Self {
                 inner: backend,
                 countdown: Arc::new(AtomicU64::new(countdown)),
+                fail_sync: Arc::new(AtomicBool::new(false)),
             }
         }
 
@@ -1105,6 +1582,9 @@ mod test {
 
         fn sync_data(&self, eventual: bool) -> Result<(), std::io::Error> {
             self.check_countdown()?;
+            if self.fail_sync.load(Ordering::SeqCst) {
+                return Err(std::io::Error::from(ErrorKind::Other));
+            }
             self.inner.sync_data(eventual)
         }
 
@@ -1200,6 +1680,48 @@ mod test {
         assert_ne!(god_byte[0] & 2, 0);
     }
 
+    #[test]
+    fn group_sync_io_error() {
+        let tmpfile = crate::create_tempfile();
+        let (file, path) = tmpfile.into_parts();
+
+        let backend = FailingBackend::new(FileBackend::new(file).unwrap(), u64::MAX);
+        let fail_sync = backend.fail_sync.clone();
+        let db = Database::builder()
+            .set_cache_size(0)
+            .create_with_backend(backend)
+            .unwrap();
+
+        let table_def: TableDefinition<u64, u64> = TableDefinition::new("x");
+
+        let mut tx = db.begin_write().unwrap();
+        tx.set_group_commit(true);
+        {
+            let mut table = tx.open_table(table_def).unwrap();
+            table.insert(0, 0).unwrap();
+        }
+        // Cause an error in the sync, after the transaction has been committed non-durably
+        fail_sync.store(true, Ordering::SeqCst);
+        let result = tx.commit().err().unwrap();
+        assert!(matches!(result, CommitError::Storage(StorageError::Io(_))));
+        assert!(db.get_memory().storage_failure());
+        let result = db.begin_write().err().unwrap();
+        assert!(matches!(
+            result,
+            TransactionError::Storage(StorageError::PreviousIo)
+        ));
+        // Simulate a transient error
+        fail_sync.store(false, Ordering::SeqCst);
+        drop(db);
+
+        // Check that recovery flag is set, even though the error has "cleared"
+        let mut file = File::open(&path).unwrap();
+        file.seek(SeekFrom::Start(9)).unwrap();
+        let mut god_byte = vec![0u8];
+        assert_eq!(file.read(&mut god_byte).unwrap(), 1);
+        assert_ne!(god_byte[0] & 2, 0);
+    }
+
     #[test]
     fn small_pages() {
         let tmpfile = crate::create_tempfile();
@@ -211,6 +211,9 @@ pub enum DatabaseError {
     RepairAborted,
     /// The database file is in an old file format and must be manually upgraded
     UpgradeRequired(u8),
+    /// The database is encrypted with a different key than the one provided, is encrypted and no
+    /// key was provided, or is not encrypted and a key was provided
+    EncryptionKeyMismatch,
     /// Error from underlying storage
     Storage(StorageError),
 }
@@ -221,6 +224,7 @@ impl From<DatabaseError> for Error {
             DatabaseError::DatabaseAlreadyOpen => Error::DatabaseAlreadyOpen,
             DatabaseError::RepairAborted => Error::RepairAborted,
             DatabaseError::UpgradeRequired(x) => Error::UpgradeRequired(x),
+            DatabaseError::EncryptionKeyMismatch => Error::EncryptionKeyMismatch,
             DatabaseError::Storage(storage) => storage.into(),
         }
     }
@@ -250,6 +254,9 @@ impl Display for DatabaseError {
             DatabaseError::RepairAborted => {
                 write!(f, "Database repair aborted.")
             }
+            DatabaseError::EncryptionKeyMismatch => {
+                write!(f, "Encryption key does not match the database.")
+            }
             DatabaseError::DatabaseAlreadyOpen => {
                 write!(f, "Database already open. Cannot acquire lock.")
             }
@@ -397,6 +404,8 @@ pub enum TransactionError {
     Storage(StorageError),
     /// The transaction is still referenced by a table or other object
     ReadTransactionStillInUse(Box<ReadTransaction>),
+    /// Another write transaction is in progress
+    WriteTransactionInProgress,
 }
 
 impl TransactionError {
@@ -415,6 +424,7 @@ impl From<TransactionError> for Error {
             TransactionError::ReadTransactionStillInUse(txn) => {
                 Error::ReadTransactionStillInUse(txn)
             }
+            TransactionError::WriteTransactionInProgress => Error::WriteTransactionInProgress,
         }
     }
 }
@@ -432,6 +442,9 @@ impl Display for TransactionError {
             TransactionError::ReadTransactionStillInUse(_) => {
                 write!(f, "Transaction still in use")
             }
+            TransactionError::WriteTransactionInProgress => {
+                write!(f, "Another write transaction is in progress")
+            }
         }
     }
 }
@@ -534,6 +547,11 @@ pub enum Error {
     LockPoisoned(&'static panic::Location<'static>),
     /// The transaction is still referenced by a table or other object
     ReadTransactionStillInUse(Box<ReadTransaction>),
+    /// Another write transaction is in progress
+    WriteTransactionInProgress,
+    /// The database is encrypted with a different key than the one provided, is encrypted and no
+    /// key was provided, or is not encrypted and a key was provided
+    EncryptionKeyMismatch,
 }
 
 impl<T> From<PoisonError<T>> for Error {
@@ -624,6 +642,9 @@ impl Display for Error {
             Error::RepairAborted => {
                 write!(f, "Database repair aborted.")
             }
+            Error::EncryptionKeyMismatch => {
+                write!(f, "Encryption key does not match the database.")
+            }
             Error::PersistentSavepointModified => {
                 write!(
                     f,
@@ -654,6 +675,9 @@ impl Display for Error {
             Error::ReadTransactionStillInUse(_) => {
                 write!(f, "Transaction still in use")
             }
+            Error::WriteTransactionInProgress => {
+                write!(f, "Another write transaction is in progress")
+            }
         }
     }
 }
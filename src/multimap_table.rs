@@ -5,8 +5,9 @@ use crate::table::{ReadableTableMetadata, TableStats};
 use crate::tree_store::{
     AllPageNumbersBtreeIter, BRANCH, BranchAccessor, BranchMutator, Btree, BtreeHeader, BtreeMut,
     BtreeRangeIter, BtreeStats, Checksum, DEFERRED, LEAF, LeafAccessor, LeafMutator,
-    MAX_PAIR_LENGTH, MAX_VALUE_LENGTH, Page, PageHint, PageNumber, PagePath, PageTrackerPolicy,
-    RawBtree, RawLeafBuilder, TransactionalMemory, UntypedBtree, UntypedBtreeMut, btree_stats,
+    MAX_PAIR_LENGTH, MAX_VALUE_LENGTH, Page, PageHint, PageImpl, PageNumber, PagePath, PageSource,
+    PageTrackerPolicy, RawBtree, RawLeafBuilder, SubtreeCount, TransactionalMemory, UntypedBtree,
+    UntypedBtreeMut, btree_stats, copy_btree, copy_page, subtree_count, visit_pages_with_checksums,
 };
 use crate::types::{Key, TypeName, Value};
 use crate::{AccessGuard, MultimapTableHandle, Result, StorageError, WriteTransaction};
@@ -275,6 +276,137 @@ pub(crate) fn relocate_subtrees(
     Ok((new_page_number, DEFERRED))
 }
 
+// Copies the tree, including any Dynamic collection subtrees, rooted at page_number in `source`
+// to newly allocated pages in `destination`, and returns the root of the copy and its count.
+// The checksums of the copy are deferred, and must be finalized by the caller
+pub(crate) fn copy_subtrees(
+    page_number: PageNumber,
+    checksum: Checksum,
+    key_size: Option<usize>,
+    value_size: Option<usize>,
+    source: &impl PageSource,
+    destination: &TransactionalMemory,
+    allocated_pages: &mut PageTrackerPolicy,
+) -> Result<(PageNumber, SubtreeCount)> {
+    let old_page = source.get_page(page_number, checksum)?;
+    let mut new_page = copy_page(&old_page, key_size, destination, allocated_pages)?;
+
+    match old_page.memory()[0] {
+        LEAF => {
+            let accessor = LeafAccessor::new(
+                old_page.memory(),
+                key_size,
+                UntypedDynamicCollection::fixed_width_with(value_size),
+            );
+            // TODO: maybe there's a better abstraction, so that we don't need to call into this low-level method?
+            let mut mutator = LeafMutator::new(
+                &mut new_page,
+                key_size,
+                UntypedDynamicCollection::fixed_width_with(value_size),
+            );
+            for i in 0..accessor.num_pairs() {
+                let entry = accessor.entry(i).unwrap();
+                let collection = UntypedDynamicCollection::from_bytes(entry.value());
+                if matches!(collection.collection_type(), SubtreeV2) {
+                    let sub_root = collection.as_subtree();
+                    let (new_sub_root, _) = copy_btree(
+                        sub_root.root,
+                        sub_root.checksum,
+                        value_size,
+                        source,
+                        destination,
+                        allocated_pages,
+                    )?;
+                    let new_collection = UntypedDynamicCollection::make_subtree_data(
+                        BtreeHeader::new(new_sub_root, DEFERRED, sub_root.length),
+                    );
+                    mutator.insert(i, true, entry.key(), &new_collection);
+                }
+            }
+        }
+        BRANCH => {
+            let accessor = BranchAccessor::new(&old_page, key_size);
+            let mut mutator = BranchMutator::new(&mut new_page);
+            for i in 0..accessor.count_children() {
+                let (new_child, new_count) = copy_subtrees(
+                    accessor.child_page(i).unwrap(),
+                    accessor.child_checksum(i).unwrap(),
+                    key_size,
+                    value_size,
+                    source,
+                    destination,
+                    allocated_pages,
+                )?;
+                mutator.write_child_page(i, new_child, DEFERRED);
+                mutator.write_child_count(i, new_count);
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    Ok((new_page.get_page_number(), subtree_count(&new_page)))
+}
+
+// Visits the pages of the tree, including any Dynamic collection subtrees, rooted at page_number,
+// along with the checksum of each page.
+// The children of a page are skipped if the visitor returns false for it
+pub(crate) fn visit_subtree_pages_with_checksums<F>(
+    page_number: PageNumber,
+    checksum: Checksum,
+    key_size: Option<usize>,
+    value_size: Option<usize>,
+    mem: &TransactionalMemory,
+    visitor: &mut F,
+) -> Result
+where
+    F: FnMut(&PageImpl, Checksum) -> Result<bool>,
+{
+    let page = mem.get_page(page_number)?;
+    if !visitor(&page, checksum)? {
+        return Ok(());
+    }
+
+    match page.memory()[0] {
+        LEAF => {
+            let accessor = LeafAccessor::new(
+                page.memory(),
+                key_size,
+                UntypedDynamicCollection::fixed_width_with(value_size),
+            );
+            for i in 0..accessor.num_pairs() {
+                let entry = accessor.entry(i).unwrap();
+                let collection = UntypedDynamicCollection::from_bytes(entry.value());
+                if matches!(collection.collection_type(), SubtreeV2) {
+                    let sub_root = collection.as_subtree();
+                    visit_pages_with_checksums(
+                        sub_root.root,
+                        sub_root.checksum,
+                        value_size,
+                        mem,
+                        visitor,
+                    )?;
+                }
+            }
+        }
+        BRANCH => {
+            let accessor = BranchAccessor::new(&page, key_size);
+            for i in 0..accessor.count_children() {
+                visit_subtree_pages_with_checksums(
+                    accessor.child_page(i).unwrap(),
+                    accessor.child_checksum(i).unwrap(),
+                    key_size,
+                    value_size,
+                    mem,
+                    visitor,
+                )?;
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
 // Finalize all the checksums in the tree, including any Dynamic collection subtrees
 // Returns the root checksum
 pub(crate) fn finalize_tree_and_subtree_checksums(
@@ -1346,6 +1478,54 @@ impl<'txn, K: Key + 'static, V: Key + 'static> MultimapTable<'txn, K, V> {
 
         Ok(iter)
     }
+
+    /// Removes all keys in the specified range, along with all of their values
+    ///
+    /// Subtrees of the table which lie entirely within the range are freed without decoding their
+    /// keys, unless a key's values are stored in a separate tree, which must also be freed. Every
+    /// leaf page in the range is still read, so the time taken grows linearly with the size of the
+    /// range.
+    ///
+    /// Returns the number of values removed
+    pub fn remove_range<'a, KR>(&mut self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        let mut removed_values = 0;
+        let mut subtrees = vec![];
+        let mut on_removed = |_: &[u8], value: &[u8]| {
+            let collection = DynamicCollection::<V>::new(value);
+            removed_values += collection.get_num_values();
+            if matches!(
+                collection.collection_type(),
+                DynamicCollectionType::SubtreeV2
+            ) {
+                subtrees.push(collection.as_subtree().root);
+            }
+            Ok(())
+        };
+        self.tree.remove_range(&range, Some(&mut on_removed))?;
+
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut allocated_pages = self.allocated_pages.lock().unwrap();
+        for root in subtrees {
+            let all_pages = AllPageNumbersBtreeIter::new(
+                root,
+                V::fixed_width(),
+                <() as Value>::fixed_width(),
+                self.mem.clone(),
+            )?;
+            for page in all_pages {
+                let page = page?;
+                if !self.mem.free_if_uncommitted(page, &mut allocated_pages) {
+                    freed_pages.push(page);
+                }
+            }
+        }
+        self.num_values -= removed_values;
+
+        Ok(removed_values)
+    }
 }
 
 impl<K: Key + 'static, V: Key + 'static> ReadableTableMetadata for MultimapTable<'_, K, V> {
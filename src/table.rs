@@ -1,9 +1,9 @@
 use crate::db::TransactionGuard;
 use crate::sealed::Sealed;
 use crate::tree_store::{
-    AccessGuardMutInPlace, Btree, BtreeExtractIf, BtreeHeader, BtreeMut, BtreeRangeIter,
-    MAX_PAIR_LENGTH, MAX_VALUE_LENGTH, PageHint, PageNumber, PageTrackerPolicy, RawBtree,
-    TransactionalMemory,
+    AccessGuardMutInPlace, Btree, BtreeCursor, BtreeExtractIf, BtreeHeader, BtreeMut,
+    BtreeRangeIter, EntryGuard, MAX_PAIR_LENGTH, MAX_VALUE_LENGTH, Page, PageHint, PageNumber,
+    PageTrackerPolicy, RawBtree, TransactionalMemory,
 };
 use crate::types::{Key, MutInPlaceValue, Value};
 use crate::{AccessGuard, AccessGuardMut, StorageError, WriteTransaction};
@@ -11,7 +11,7 @@ use crate::{Result, TableHandle};
 use std::borrow::Borrow;
 use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
-use std::ops::RangeBounds;
+use std::ops::{Bound, RangeBounds};
 use std::sync::{Arc, Mutex};
 
 /// Informational storage stats about a table
@@ -98,12 +98,29 @@ impl<'txn, K: Key + 'static, V: Value + 'static> Table<'txn, K, V> {
         self.tree.print_debug(include_values)
     }
 
+    // Takes the fields separately, so that it can be called while `tree` is borrowed
+    fn record_key_change(
+        transaction: &WriteTransaction,
+        name: &str,
+        key: &K::SelfType<'_>,
+        existed: bool,
+        exists: bool,
+    ) {
+        if transaction.capturing_key_changes() {
+            transaction.record_key_change(name, K::as_bytes(key).as_ref(), existed, exists);
+        }
+    }
+
     /// Returns an accessor, which allows mutation, to the value corresponding to the given key
     pub fn get_mut<'k>(
         &mut self,
         key: impl Borrow<K::SelfType<'k>>,
     ) -> Result<Option<AccessGuardMut<V>>> {
-        self.tree.get_mut(key.borrow())
+        let result = self.tree.get_mut(key.borrow())?;
+        if result.is_some() {
+            Self::record_key_change(self.transaction, &self.name, key.borrow(), true, true);
+        }
+        Ok(result)
     }
 
     /// Removes and returns the first key-value pair in the table
@@ -165,9 +182,13 @@ impl<'txn, K: Key + 'static, V: Value + 'static> Table<'txn, K, V> {
     where
         KR: Borrow<K::SelfType<'a>> + 'a,
     {
+        let recorder = self
+            .transaction
+            .capturing_key_changes()
+            .then_some((self.transaction, self.name.as_str()));
         self.tree
             .extract_from_if(&range, predicate)
-            .map(ExtractIf::new)
+            .map(|inner| ExtractIf::new(inner).record_key_changes(recorder))
     }
 
     /// Applies `predicate` to all key-value pairs. All entries for which
@@ -177,7 +198,7 @@ impl<'txn, K: Key + 'static, V: Value + 'static> Table<'txn, K, V> {
         &mut self,
         predicate: F,
     ) -> Result {
-        self.tree.retain_in::<K::SelfType<'_>, F>(predicate, ..)
+        self.retain_in::<K::SelfType<'_>, F>(.., predicate)
     }
 
     /// Applies `predicate` to all key-value pairs in the range `start..end`. All entries for which
@@ -191,7 +212,27 @@ impl<'txn, K: Key + 'static, V: Value + 'static> Table<'txn, K, V> {
     where
         KR: Borrow<K::SelfType<'a>> + 'a,
     {
-        self.tree.retain_in(predicate, range)
+        if !self.transaction.capturing_key_changes() {
+            return self.tree.retain_in(predicate, range);
+        }
+        let mut predicate = predicate;
+        let mut removed = vec![];
+        self.tree.retain_in(
+            |key, value| {
+                let key_bytes = K::as_bytes(&key).as_ref().to_vec();
+                let retain = predicate(key, value);
+                if !retain {
+                    removed.push(key_bytes);
+                }
+                retain
+            },
+            range,
+        )?;
+        for key in removed {
+            self.transaction
+                .record_key_change(&self.name, &key, true, false);
+        }
+        Ok(())
     }
 
     /// Insert mapping of the given key to the given value
@@ -204,18 +245,143 @@ impl<'txn, K: Key + 'static, V: Value + 'static> Table<'txn, K, V> {
         key: impl Borrow<K::SelfType<'k>>,
         value: impl Borrow<V::SelfType<'v>>,
     ) -> Result<Option<AccessGuard<V>>> {
-        let value_len = V::as_bytes(value.borrow()).as_ref().len();
+        Self::check_pair_length(key.borrow(), value.borrow())?;
+        let old = self.tree.insert(key.borrow(), value.borrow())?;
+        Self::record_key_change(
+            self.transaction,
+            &self.name,
+            key.borrow(),
+            old.is_some(),
+            true,
+        );
+        Ok(old)
+    }
+
+    /// Replaces the value of the given key with the result of `f`
+    ///
+    /// `f` is called with the current value, or `None` if the key is not present. If it returns a
+    /// value, that value is inserted, and if it returns `None` the key is removed. The lookup and
+    /// the insertion are performed in a single traversal of the table.
+    ///
+    /// Returns the old value, if the key was present in the table, otherwise None is returned
+    pub fn update<'k, 'v, F, VR>(
+        &mut self,
+        key: impl Borrow<K::SelfType<'k>>,
+        f: F,
+    ) -> Result<Option<AccessGuard<V>>>
+    where
+        F: for<'f> FnOnce(Option<V::SelfType<'f>>) -> Option<VR>,
+        VR: Borrow<V::SelfType<'v>>,
+    {
+        let key = key.borrow();
+        let key_len = K::as_bytes(key).as_ref().len();
+        if key_len > MAX_VALUE_LENGTH {
+            return Err(StorageError::ValueTooLarge(key_len));
+        }
+        let mut f = Some(f);
+        let mut compute = |old: Option<&[u8]>| {
+            let Some(value) = (f.take().unwrap())(old.map(V::from_bytes)) else {
+                return Ok(None);
+            };
+            Self::check_pair_length(key, value.borrow())?;
+            Ok(Some(V::as_bytes(value.borrow()).as_ref().to_vec()))
+        };
+        let (old, exists) = self.tree.update(key, &mut compute)?;
+        if old.is_some() || exists {
+            Self::record_key_change(self.transaction, &self.name, key, old.is_some(), exists);
+        }
+        Ok(old)
+    }
+
+    /// Insert mapping of the given key to the given value, if the key is not already present
+    ///
+    /// Returns `true` if the value was inserted
+    pub fn insert_if_absent<'k, 'v>(
+        &mut self,
+        key: impl Borrow<K::SelfType<'k>>,
+        value: impl Borrow<V::SelfType<'v>>,
+    ) -> Result<bool> {
+        Self::check_pair_length(key.borrow(), value.borrow())?;
+        let value = V::as_bytes(value.borrow());
+        let mut compute = |old: Option<&[u8]>| Ok(old.is_none().then(|| value.as_ref().to_vec()));
+        let inserted = self.tree.insert_computed(key.borrow(), &mut compute)?;
+        if inserted {
+            Self::record_key_change(self.transaction, &self.name, key.borrow(), false, true);
+        }
+        Ok(inserted)
+    }
+
+    /// Replaces the value of the given key with `new`, if its current value is `expected`
+    ///
+    /// Values are compared by their serialized bytes. Returns `true` if the value was replaced, and
+    /// `false` if the key is not present or has a different value
+    pub fn compare_and_swap<'k, 'v>(
+        &mut self,
+        key: impl Borrow<K::SelfType<'k>>,
+        expected: impl Borrow<V::SelfType<'v>>,
+        new: impl Borrow<V::SelfType<'v>>,
+    ) -> Result<bool> {
+        Self::check_pair_length(key.borrow(), new.borrow())?;
+        let expected = V::as_bytes(expected.borrow());
+        let new = V::as_bytes(new.borrow());
+        let mut compute = |old: Option<&[u8]>| {
+            Ok((old == Some(expected.as_ref())).then(|| new.as_ref().to_vec()))
+        };
+        let swapped = self.tree.insert_computed(key.borrow(), &mut compute)?;
+        if swapped {
+            Self::record_key_change(self.transaction, &self.name, key.borrow(), true, true);
+        }
+        Ok(swapped)
+    }
+
+    fn check_pair_length(key: &K::SelfType<'_>, value: &V::SelfType<'_>) -> Result {
+        let value_len = V::as_bytes(value).as_ref().len();
         if value_len > MAX_VALUE_LENGTH {
             return Err(StorageError::ValueTooLarge(value_len));
         }
-        let key_len = K::as_bytes(key.borrow()).as_ref().len();
+        let key_len = K::as_bytes(key).as_ref().len();
         if key_len > MAX_VALUE_LENGTH {
             return Err(StorageError::ValueTooLarge(key_len));
         }
         if value_len + key_len > MAX_PAIR_LENGTH {
             return Err(StorageError::ValueTooLarge(value_len + key_len));
         }
-        self.tree.insert(key.borrow(), value.borrow())
+        Ok(())
+    }
+
+    // If the table is empty, the tree is built directly from the entries for as long as they are
+    // sorted. Any remaining entries are inserted individually
+    pub(crate) fn bulk_load<'k, 'v, KI, VI>(
+        &mut self,
+        entries: impl IntoIterator<Item = (KI, VI)>,
+    ) -> Result<u64>
+    where
+        KI: Borrow<K::SelfType<'k>>,
+        VI: Borrow<V::SelfType<'v>>,
+    {
+        let mut entries = entries.into_iter();
+        let mut loaded = 0;
+        if self.tree.get_root().is_none() {
+            let transaction = self.transaction;
+            let name = self.name.as_str();
+            let mut checked = entries.by_ref().map(|(key, value)| {
+                Self::check_pair_length(key.borrow(), value.borrow())?;
+                Self::record_key_change(transaction, name, key.borrow(), false, true);
+                Ok((key, value))
+            });
+            let (count, unsorted) = self.tree.bulk_load(&mut checked)?;
+            loaded += count;
+            if let Some((key, value)) = unsorted {
+                self.insert(key, value)?;
+                loaded += 1;
+            }
+        }
+        for (key, value) in entries {
+            self.insert(key, value)?;
+            loaded += 1;
+        }
+
+        Ok(loaded)
     }
 
     /// Removes the given key
@@ -225,7 +391,63 @@ impl<'txn, K: Key + 'static, V: Value + 'static> Table<'txn, K, V> {
         &mut self,
         key: impl Borrow<K::SelfType<'a>>,
     ) -> Result<Option<AccessGuard<V>>> {
-        self.tree.remove(key.borrow())
+        let old = self.tree.remove(key.borrow())?;
+        if old.is_some() {
+            Self::record_key_change(self.transaction, &self.name, key.borrow(), true, false);
+        }
+        Ok(old)
+    }
+
+    /// Removes all entries in the specified range
+    ///
+    /// Subtrees of the table which lie entirely within the range are freed without reading their
+    /// leaf pages, unless key changes are being captured for a commit hook, so this is much faster
+    /// than removing the keys individually. Databases created by older versions of redb do not
+    /// store the number of entries in each subtree, and their leaf pages are read to count them.
+    ///
+    /// Returns the number of entries removed
+    pub fn remove_range<'a, KR>(&mut self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        if !self.transaction.capturing_key_changes() {
+            return self.tree.remove_range(&range, None);
+        }
+        let transaction = self.transaction;
+        let name = self.name.as_str();
+        let mut record = |key: &[u8], _: &[u8]| {
+            transaction.record_key_change(name, key, true, false);
+            Ok(())
+        };
+        self.tree.remove_range(&range, Some(&mut record))
+    }
+
+    /// Removes all entries from the table
+    ///
+    /// Only the branch pages of the table are read, to find the pages to free. Its leaf pages are
+    /// also read if key changes are being captured for a commit hook, or if the database was
+    /// created by an older version of redb, which does not store the number of entries in each
+    /// subtree
+    pub fn clear(&mut self) -> Result {
+        self.remove_range::<K::SelfType<'_>>(..)?;
+        Ok(())
+    }
+
+    /// Returns a cursor over the table, which is initially unpositioned
+    pub fn cursor(&self) -> Cursor<K, V> {
+        Cursor::new(
+            self.tree.cursor(),
+            self.tree.get_root().map(|x| x.root),
+            self.transaction.transaction_guard(),
+        )
+    }
+
+    /// Returns a cursor which can modify the table at its position
+    ///
+    /// The cursor is initially unpositioned
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, 'txn, K, V> {
+        let inner = self.tree.cursor();
+        CursorMut { table: self, inner }
     }
 }
 
@@ -250,6 +472,10 @@ impl<K: Key + 'static, V: MutInPlaceValue + 'static> Table<'_, K, V> {
         if value_length as usize + key_len > MAX_PAIR_LENGTH {
             return Err(StorageError::ValueTooLarge(value_length as usize + key_len));
         }
+        if self.transaction.capturing_key_changes() {
+            let existed = self.tree.get(key.borrow())?.is_some();
+            Self::record_key_change(self.transaction, &self.name, key.borrow(), existed, true);
+        }
         self.tree.insert_reserve(key.borrow(), value_length)
     }
 }
@@ -294,6 +520,22 @@ impl<K: Key + 'static, V: Value + 'static> ReadableTable<K, V> for Table<'_, K,
     fn last(&self) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
         self.tree.last()
     }
+
+    fn range_len<'a, KR>(&self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        self.tree.range_len(&range)
+    }
+
+    fn nth(&self, index: u64) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        self.tree.nth(index)
+    }
+
+    fn rank<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<u64> {
+        let range = (Bound::Unbounded, Bound::Excluded(key.borrow()));
+        self.tree.range_len::<_, &K::SelfType<'a>>(&range)
+    }
 }
 
 impl<K: Key, V: Value> Sealed for Table<'_, K, V> {}
@@ -420,6 +662,53 @@ pub trait ReadableTable<K: Key + 'static, V: Value + 'static>: ReadableTableMeta
     fn iter(&self) -> Result<Range<K, V>> {
         self.range::<K::SelfType<'_>>(..)
     }
+
+    /// Returns the number of entries in the range
+    ///
+    /// [`Table`] and [`ReadOnlyTable`] store the number of entries below each page, so only the
+    /// pages at the boundaries of the range are read, and the time taken grows logarithmically with
+    /// the size of the table. Databases created by older versions of redb do not store these
+    /// counts, and the pages within the range are read instead. The default implementation
+    /// iterates over the range.
+    fn range_len<'a, KR>(&self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        let mut len = 0;
+        for entry in self.range(range)? {
+            entry?;
+            len += 1;
+        }
+        Ok(len)
+    }
+
+    /// Returns the key-value pair at position `index` in the table, counting from zero
+    ///
+    /// Entries before `index` are skipped using the stored counts, as in
+    /// [`ReadableTable::range_len`]. The default implementation iterates over the table.
+    fn nth(&self, index: u64) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        let Ok(index) = usize::try_from(index) else {
+            return Ok(None);
+        };
+        self.iter()?.nth(index).transpose()
+    }
+
+    /// Returns the number of keys in the table which are less than `key`
+    ///
+    /// This is the position `key` has, or would have, in the table. It is counted as in
+    /// [`ReadableTable::range_len`]. The default implementation iterates over the table.
+    fn rank<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<u64> {
+        let key = K::as_bytes(key.borrow());
+        let mut rank = 0;
+        for entry in self.iter()? {
+            let (entry_key, _) = entry?;
+            if K::compare(K::as_bytes(&entry_key.value()).as_ref(), key.as_ref()).is_ge() {
+                break;
+            }
+            rank += 1;
+        }
+        Ok(rank)
+    }
 }
 
 /// A read-only untyped table
@@ -507,6 +796,17 @@ impl<K: Key + 'static, V: Value + 'static> ReadOnlyTable<K, V> {
             .range(&range)
             .map(|x| Range::new(x, self.transaction_guard.clone()))
     }
+
+    /// Returns a cursor over the table, which is initially unpositioned
+    ///
+    /// The cursor is reference counted and keeps the transaction alive until it is dropped.
+    pub fn cursor(&self) -> Cursor<'static, K, V> {
+        Cursor::new(
+            self.tree.cursor(),
+            self.tree.get_root().map(|x| x.root),
+            self.transaction_guard.clone(),
+        )
+    }
 }
 
 impl<K: Key + 'static, V: Value + 'static> ReadableTableMetadata for ReadOnlyTable<K, V> {
@@ -549,6 +849,22 @@ impl<K: Key + 'static, V: Value + 'static> ReadableTable<K, V> for ReadOnlyTable
     fn last(&self) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
         self.tree.last()
     }
+
+    fn range_len<'a, KR>(&self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        KR: Borrow<K::SelfType<'a>> + 'a,
+    {
+        self.tree.range_len(&range)
+    }
+
+    fn nth(&self, index: u64) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+        self.tree.nth(index)
+    }
+
+    fn rank<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<u64> {
+        let range = (Bound::Unbounded, Bound::Excluded(key.borrow()));
+        self.tree.range_len::<_, &K::SelfType<'a>>(&range)
+    }
 }
 
 impl<K: Key, V: Value> Sealed for ReadOnlyTable<K, V> {}
@@ -566,6 +882,8 @@ pub struct ExtractIf<
     F: for<'f> FnMut(K::SelfType<'f>, V::SelfType<'f>) -> bool,
 > {
     inner: BtreeExtractIf<'a, K, V, F>,
+    // The transaction and table to record the extracted keys in, for the commit hook
+    recorder: Option<(&'a WriteTransaction, &'a str)>,
 }
 
 impl<
@@ -576,7 +894,24 @@ impl<
 > ExtractIf<'a, K, V, F>
 {
     pub(crate) fn new(inner: BtreeExtractIf<'a, K, V, F>) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            recorder: None,
+        }
+    }
+
+    pub(crate) fn record_key_changes(
+        mut self,
+        recorder: Option<(&'a WriteTransaction, &'a str)>,
+    ) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    fn record_key_change(&self, key: &[u8]) {
+        if let Some((transaction, table)) = self.recorder {
+            transaction.record_key_change(table, key, true, false);
+        }
     }
 }
 
@@ -593,6 +928,7 @@ impl<
         let entry = self.inner.next()?;
         Some(entry.map(|entry| {
             let (page, key_range, value_range) = entry.into_raw();
+            self.record_key_change(&page.memory()[key_range.clone()]);
             let key = AccessGuard::with_page(page.clone(), key_range);
             let value = AccessGuard::with_page(page, value_range);
             (key, value)
@@ -610,6 +946,7 @@ impl<
         let entry = self.inner.next_back()?;
         Some(entry.map(|entry| {
             let (page, key_range, value_range) = entry.into_raw();
+            self.record_key_change(&page.memory()[key_range.clone()]);
             let key = AccessGuard::with_page(page.clone(), key_range);
             let value = AccessGuard::with_page(page, value_range);
             (key, value)
@@ -662,3 +999,170 @@ impl<K: Key + 'static, V: Value + 'static> DoubleEndedIterator for Range<'_, K,
         })
     }
 }
+
+fn entry_guards<'a, K: Key + 'static, V: Value + 'static>(
+    entry: EntryGuard<K, V>,
+) -> (AccessGuard<'a, K>, AccessGuard<'a, V>) {
+    let (page, key_range, value_range) = entry.into_raw();
+    let key = AccessGuard::with_page(page.clone(), key_range);
+    let value = AccessGuard::with_page(page, value_range);
+    (key, value)
+}
+
+/// A cursor over a table, which can be moved forwards and backwards from any position
+///
+/// A cursor is either unpositioned, or positioned at a key. Moving the cursor returns the entry it
+/// moved to. If there is no entry in the requested direction, `None` is returned and the cursor
+/// does not move. An unpositioned cursor moves to the first entry with [`Cursor::next`], and to the
+/// last entry with [`Cursor::prev`].
+///
+/// Moving repeatedly in the same direction is as efficient as iterating over a [`Range`]. Changing
+/// direction requires a lookup from the current position.
+pub struct Cursor<'a, K: Key + 'static, V: Value + 'static> {
+    inner: BtreeCursor<K, V>,
+    root: Option<PageNumber>,
+    _transaction_guard: Arc<TransactionGuard>,
+    // This lifetime is here so that `&` can be held on `Table` preventing concurrent mutation
+    _lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a, K: Key + 'static, V: Value + 'static> Cursor<'a, K, V> {
+    fn new(
+        inner: BtreeCursor<K, V>,
+        root: Option<PageNumber>,
+        guard: Arc<TransactionGuard>,
+    ) -> Self {
+        Self {
+            inner,
+            root,
+            _transaction_guard: guard,
+            _lifetime: Default::default(),
+        }
+    }
+
+    /// Moves to the first entry whose key is greater than or equal to `key`, and returns it
+    ///
+    /// If there is no such entry, `None` is returned and the cursor is positioned after the last
+    /// entry, so that [`Cursor::prev`] returns the last entry
+    pub fn seek<'k>(
+        &mut self,
+        key: impl Borrow<K::SelfType<'k>>,
+    ) -> Result<Option<(AccessGuard<'a, K>, AccessGuard<'a, V>)>> {
+        let key = K::as_bytes(key.borrow());
+        Ok(self.inner.seek(self.root, key.as_ref())?.map(entry_guards))
+    }
+
+    /// Moves to the first entry in the table, and returns it
+    pub fn seek_first(&mut self) -> Result<Option<(AccessGuard<'a, K>, AccessGuard<'a, V>)>> {
+        Ok(self.inner.seek_end(self.root, false)?.map(entry_guards))
+    }
+
+    /// Moves to the last entry in the table, and returns it
+    pub fn seek_last(&mut self) -> Result<Option<(AccessGuard<'a, K>, AccessGuard<'a, V>)>> {
+        Ok(self.inner.seek_end(self.root, true)?.map(entry_guards))
+    }
+
+    /// Moves to the next entry, and returns it
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<(AccessGuard<'a, K>, AccessGuard<'a, V>)>> {
+        Ok(self.inner.step(self.root, false)?.map(entry_guards))
+    }
+
+    /// Moves to the previous entry, and returns it
+    pub fn prev(&mut self) -> Result<Option<(AccessGuard<'a, K>, AccessGuard<'a, V>)>> {
+        Ok(self.inner.step(self.root, true)?.map(entry_guards))
+    }
+
+    /// Returns the entry at the current position, if any
+    pub fn current(&self) -> Option<(AccessGuard<'a, K>, AccessGuard<'a, V>)> {
+        self.inner.current().map(entry_guards)
+    }
+}
+
+/// A cursor over a [`Table`], which can also modify the entry at its position
+///
+/// It moves in the same way as a [`Cursor`]. Modifying the table requires a lookup from the
+/// current position the next time the cursor is moved.
+pub struct CursorMut<'c, 'txn, K: Key + 'static, V: Value + 'static> {
+    table: &'c mut Table<'txn, K, V>,
+    inner: BtreeCursor<K, V>,
+}
+
+impl<K: Key + 'static, V: Value + 'static> CursorMut<'_, '_, K, V> {
+    fn root(&self) -> Option<PageNumber> {
+        self.table.tree.get_root().map(|x| x.root)
+    }
+
+    /// Moves to the first entry whose key is greater than or equal to `key`, and returns it
+    ///
+    /// If there is no such entry, `None` is returned and the cursor is positioned after the last
+    /// entry, so that [`CursorMut::prev`] returns the last entry
+    pub fn seek<'k>(
+        &mut self,
+        key: impl Borrow<K::SelfType<'k>>,
+    ) -> Result<Option<(AccessGuard<'_, K>, AccessGuard<'_, V>)>> {
+        let key = K::as_bytes(key.borrow());
+        let root = self.root();
+        Ok(self.inner.seek(root, key.as_ref())?.map(entry_guards))
+    }
+
+    /// Moves to the first entry in the table, and returns it
+    pub fn seek_first(&mut self) -> Result<Option<(AccessGuard<'_, K>, AccessGuard<'_, V>)>> {
+        let root = self.root();
+        Ok(self.inner.seek_end(root, false)?.map(entry_guards))
+    }
+
+    /// Moves to the last entry in the table, and returns it
+    pub fn seek_last(&mut self) -> Result<Option<(AccessGuard<'_, K>, AccessGuard<'_, V>)>> {
+        let root = self.root();
+        Ok(self.inner.seek_end(root, true)?.map(entry_guards))
+    }
+
+    /// Moves to the next entry, and returns it
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<(AccessGuard<'_, K>, AccessGuard<'_, V>)>> {
+        let root = self.root();
+        Ok(self.inner.step(root, false)?.map(entry_guards))
+    }
+
+    /// Moves to the previous entry, and returns it
+    pub fn prev(&mut self) -> Result<Option<(AccessGuard<'_, K>, AccessGuard<'_, V>)>> {
+        let root = self.root();
+        Ok(self.inner.step(root, true)?.map(entry_guards))
+    }
+
+    /// Returns the entry at the current position, if any
+    pub fn current(&self) -> Option<(AccessGuard<'_, K>, AccessGuard<'_, V>)> {
+        self.inner.current().map(entry_guards)
+    }
+
+    /// Removes the entry at the current position
+    ///
+    /// The cursor keeps its position, so that [`CursorMut::next`] and [`CursorMut::prev`] return
+    /// the entries which followed and preceded the removed one. Returns `false` if there is no
+    /// entry at the current position
+    pub fn delete_current(&mut self) -> Result<bool> {
+        if self.inner.current().is_none() {
+            return Ok(false);
+        }
+        let key = self.inner.position().unwrap().to_vec();
+        self.inner.release();
+        self.table.remove(K::from_bytes(&key))?;
+        Ok(true)
+    }
+
+    /// Replaces the value of the entry at the current position
+    ///
+    /// Returns `false` if there is no entry at the current position
+    pub fn replace_current<'v>(&mut self, value: impl Borrow<V::SelfType<'v>>) -> Result<bool> {
+        if self.inner.current().is_none() {
+            return Ok(false);
+        }
+        let key = self.inner.position().unwrap().to_vec();
+        self.inner.release();
+        self.table.insert(K::from_bytes(&key), value)?;
+        let root = self.root();
+        self.inner.seek(root, &key)?;
+        Ok(true)
+    }
+}
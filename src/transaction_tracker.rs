@@ -4,8 +4,11 @@ use crate::{Key, Result, Savepoint, TypeName, Value};
 use log::debug;
 use std::cmp::Ordering;
 use std::collections::btree_map::BTreeMap;
+use std::mem;
 use std::mem::size_of;
 use std::sync::{Condvar, Mutex};
+use std::task::Waker;
+use std::time::{Duration, Instant};
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
 pub(crate) struct TransactionId(u64);
@@ -91,11 +94,14 @@ struct State {
     // We need to make sure that the freed-table does not get processed for these, since they are not durable yet
     // Therefore, we hold a read transaction on their parent
     pending_non_durable_commits: Vec<TransactionId>,
+    // Async tasks waiting for the live write transaction to complete
+    write_transaction_wakers: Vec<Waker>,
 }
 
 pub(crate) struct TransactionTracker {
     state: Mutex<State>,
     live_write_transaction_available: Condvar,
+    commit_available: Condvar,
 }
 
 impl TransactionTracker {
@@ -108,8 +114,10 @@ impl TransactionTracker {
                 live_write_transaction: None,
                 valid_savepoints: Default::default(),
                 pending_non_durable_commits: Default::default(),
+                write_transaction_wakers: Default::default(),
             }),
             live_write_transaction_available: Condvar::new(),
+            commit_available: Condvar::new(),
         }
     }
 
@@ -118,6 +126,61 @@ impl TransactionTracker {
         while state.live_write_transaction.is_some() {
             state = self.live_write_transaction_available.wait(state).unwrap();
         }
+        Self::allocate_write_transaction(&mut state)
+    }
+
+    // Returns None, without blocking, if a write transaction is already in progress
+    pub(crate) fn try_start_write_transaction(&self) -> Option<TransactionId> {
+        let mut state = self.state.lock().unwrap();
+        if state.live_write_transaction.is_some() {
+            return None;
+        }
+        Some(Self::allocate_write_transaction(&mut state))
+    }
+
+    // Returns None if the in-progress write transaction did not complete before the timeout
+    pub(crate) fn start_write_transaction_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Option<TransactionId> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut state = self.state.lock().unwrap();
+        while state.live_write_transaction.is_some() {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                // The deadline is too far in the future to represent, so wait indefinitely
+                None => Duration::MAX,
+            };
+            if remaining.is_zero() {
+                return None;
+            }
+            state = self
+                .live_write_transaction_available
+                .wait_timeout(state, remaining)
+                .unwrap()
+                .0;
+        }
+        Some(Self::allocate_write_transaction(&mut state))
+    }
+
+    // Returns None, and registers the waker to be woken when the in-progress write transaction
+    // completes, if a write transaction is already in progress
+    pub(crate) fn poll_start_write_transaction(&self, waker: &Waker) -> Option<TransactionId> {
+        let mut state = self.state.lock().unwrap();
+        if state.live_write_transaction.is_some() {
+            if !state
+                .write_transaction_wakers
+                .iter()
+                .any(|x| x.will_wake(waker))
+            {
+                state.write_transaction_wakers.push(waker.clone());
+            }
+            return None;
+        }
+        Some(Self::allocate_write_transaction(&mut state))
+    }
+
+    fn allocate_write_transaction(state: &mut State) -> TransactionId {
         assert!(state.live_write_transaction.is_none());
         let transaction_id = state.next_transaction_id.increment();
         #[cfg(feature = "logging")]
@@ -131,12 +194,62 @@ impl TransactionTracker {
         let mut state = self.state.lock().unwrap();
         assert_eq!(state.live_write_transaction.unwrap(), id);
         state.live_write_transaction = None;
+        let wakers: Vec<Waker> = state.write_transaction_wakers.drain(..).collect();
+        drop(state);
         self.live_write_transaction_available.notify_one();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    // Wakes the threads waiting in wait_for_commit_after(). Must be called after each commit
+    pub(crate) fn notify_commit(&self) {
+        // Acquire the lock, so that a thread which has checked for a new commit, but not yet
+        // started waiting, can't miss the notification
+        drop(self.state.lock().unwrap());
+        self.commit_available.notify_all();
+    }
+
+    // Returns false if no transaction newer than `id` was committed before the timeout
+    pub(crate) fn wait_for_commit_after(
+        &self,
+        mem: &TransactionalMemory,
+        id: TransactionId,
+        timeout: Duration,
+    ) -> Result<bool> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut state = self.state.lock()?;
+        while mem.get_last_committed_transaction_id()? <= id {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                // The deadline is too far in the future to represent, so wait indefinitely
+                None => Duration::MAX,
+            };
+            if remaining.is_zero() {
+                return Ok(false);
+            }
+            state = self.commit_available.wait_timeout(state, remaining)?.0;
+        }
+        Ok(true)
     }
 
     pub(crate) fn clear_pending_non_durable_commits(&self) {
         let mut state = self.state.lock().unwrap();
         let ids: Vec<TransactionId> = state.pending_non_durable_commits.drain(..).collect();
+        Self::release_non_durable_commits(&mut state, ids);
+    }
+
+    // Clear the pending non-durable commits up to and including `last`, which have been made durable
+    pub(crate) fn clear_pending_non_durable_commits_through(&self, last: TransactionId) {
+        let mut state = self.state.lock().unwrap();
+        let (ids, pending) = mem::take(&mut state.pending_non_durable_commits)
+            .into_iter()
+            .partition(|id| *id <= last);
+        state.pending_non_durable_commits = pending;
+        Self::release_non_durable_commits(&mut state, ids);
+    }
+
+    fn release_non_durable_commits(state: &mut State, ids: Vec<TransactionId>) {
         for id in ids {
             if let Some(parent) = id.parent() {
                 let ref_count = state.live_read_transactions.get_mut(&parent).unwrap();
@@ -1,3 +1,5 @@
+use crate::backup::{create_new_file, write_incremental_backup};
+use crate::commit_hook::{CommitEvent, CommitHook, KeyChangeLog, commit_event};
 use crate::db::TransactionGuard;
 use crate::error::CommitError;
 use crate::multimap_table::ReadOnlyUntypedMultimapTable;
@@ -6,16 +8,16 @@ use crate::table::ReadOnlyUntypedTable;
 use crate::transaction_tracker::{SavepointId, TransactionId, TransactionTracker};
 use crate::tree_store::{
     Btree, BtreeHeader, BtreeMut, InternalTableDefinition, MAX_PAIR_LENGTH, MAX_VALUE_LENGTH, Page,
-    PageHint, PageListMut, PageNumber, PageTrackerPolicy, SerializedSavepoint, TableTree,
-    TableTreeMut, TableType, TransactionalMemory,
+    PageHint, PageListMut, PageNumber, PageSource, PageTrackerPolicy, SerializedSavepoint,
+    TableTree, TableTreeMut, TableType, TransactionalMemory,
 };
 use crate::types::{Key, Value};
 use crate::{
-    AccessGuard, AccessGuardMutInPlace, ExtractIf, MultimapTable, MultimapTableDefinition,
-    MultimapTableHandle, MutInPlaceValue, Range, ReadOnlyMultimapTable, ReadOnlyTable, Result,
-    Savepoint, SavepointError, SetDurabilityError, StorageError, Table, TableDefinition,
-    TableError, TableHandle, TransactionError, TypeName, UntypedMultimapTableHandle,
-    UntypedTableHandle,
+    AccessGuard, AccessGuardMutInPlace, Database, DatabaseError, ExtractIf, MultimapTable,
+    MultimapTableDefinition, MultimapTableHandle, MutInPlaceValue, Range, ReadOnlyMultimapTable,
+    ReadOnlyTable, Result, Savepoint, SavepointError, SetDurabilityError, StorageError, Table,
+    TableDefinition, TableError, TableHandle, TransactionError, TypeName,
+    UntypedMultimapTableHandle, UntypedTableHandle,
 };
 #[cfg(feature = "logging")]
 use log::{debug, warn};
@@ -23,9 +25,11 @@ use std::borrow::Borrow;
 use std::cmp::min;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
+use std::fs::File;
 use std::marker::PhantomData;
 use std::mem::size_of;
-use std::ops::RangeBounds;
+use std::ops::{Deref, RangeBounds};
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::{panic, thread};
@@ -732,6 +736,21 @@ impl TableNamespace<'_> {
         self.inner_delete(name, TableType::Normal)
     }
 
+    fn insert_copied_table(
+        &mut self,
+        transaction: &WriteTransaction,
+        name: &str,
+        definition: InternalTableDefinition,
+        source: &impl PageSource,
+    ) -> Result<(), TableError> {
+        self.set_dirty(transaction);
+        let root = {
+            let mut allocated_pages = self.allocated_pages.lock().unwrap();
+            definition.copy_tree(source, &transaction.mem, &mut allocated_pages)?
+        };
+        self.table_tree.insert_copied_table(name, definition, root)
+    }
+
     #[track_caller]
     fn delete_multimap_table(
         &mut self,
@@ -759,6 +778,7 @@ impl TableNamespace<'_> {
 /// A read/write transaction
 ///
 /// Only a single [`WriteTransaction`] may exist at a time
+#[allow(clippy::struct_excessive_bools)]
 pub struct WriteTransaction {
     transaction_tracker: Arc<TransactionTracker>,
     mem: Arc<TransactionalMemory>,
@@ -771,9 +791,19 @@ pub struct WriteTransaction {
     durability: InternalDurability,
     two_phase_commit: bool,
     quick_repair: bool,
+    group_commit: bool,
+    // Set by a logged commit once the write-ahead log should be checkpointed
+    checkpoint_log: bool,
     // Persistent savepoints created during this transaction
     created_persistent_savepoints: Mutex<HashSet<SavepointId>>,
     deleted_persistent_savepoints: Mutex<Vec<(SavepointId, TransactionId)>>,
+    commit_hook: Option<Arc<CommitHook>>,
+    // Root of the table tree when the transaction began. Used to find the tables changed by it
+    original_root: Option<BtreeHeader>,
+    // The table definitions when the transaction began, if they have been read. They must be read
+    // before restoring a savepoint, since that may free the pages of the original table tree
+    original_tables: Option<Vec<(String, InternalTableDefinition)>>,
+    key_changes: Option<Mutex<KeyChangeLog>>,
 }
 
 impl WriteTransaction {
@@ -781,6 +811,7 @@ impl WriteTransaction {
         guard: TransactionGuard,
         transaction_tracker: Arc<TransactionTracker>,
         mem: Arc<TransactionalMemory>,
+        commit_hook: Option<Arc<CommitHook>>,
     ) -> Result<Self> {
         let transaction_id = guard.id();
         let guard = Arc::new(guard);
@@ -803,11 +834,45 @@ impl WriteTransaction {
             durability: InternalDurability::Immediate,
             two_phase_commit: false,
             quick_repair: false,
+            group_commit: false,
+            checkpoint_log: false,
             created_persistent_savepoints: Mutex::new(Default::default()),
             deleted_persistent_savepoints: Mutex::new(vec![]),
+            key_changes: commit_hook
+                .as_ref()
+                .filter(|hook| hook.capture_key_changes)
+                .map(|_| Mutex::new(KeyChangeLog::default())),
+            commit_hook,
+            original_root: root_page,
+            original_tables: None,
         })
     }
 
+    pub(crate) fn capturing_key_changes(&self) -> bool {
+        self.key_changes.is_some()
+    }
+
+    // Records a change to `key` for the commit hook. `existed` is whether the key existed before
+    // the change, and `exists` whether it exists after it
+    pub(crate) fn record_key_change(&self, table: &str, key: &[u8], existed: bool, exists: bool) {
+        if let Some(changes) = &self.key_changes {
+            changes.lock().unwrap().record(table, key, existed, exists);
+        }
+    }
+
+    fn original_tables(&self) -> Result<Vec<(String, InternalTableDefinition)>> {
+        if let Some(tables) = &self.original_tables {
+            return Ok(tables.clone());
+        }
+        TableTree::new(
+            self.original_root,
+            PageHint::None,
+            self.transaction_guard.clone(),
+            self.mem.clone(),
+        )?
+        .list_definitions()
+    }
+
     pub(crate) fn pending_free_pages(&self) -> Result<bool> {
         let mut system_tables = self.system_tables.lock().unwrap();
         if system_tables
@@ -1105,6 +1170,12 @@ impl WriteTransaction {
         // Restoring a savepoint that reverted a file format or checksum type change could corrupt
         // the database
         assert_eq!(self.mem.get_version(), savepoint.get_version());
+        if self.commit_hook.is_some() && self.original_tables.is_none() {
+            self.original_tables = Some(self.original_tables()?);
+        }
+        if let Some(changes) = &self.key_changes {
+            changes.lock().unwrap().invalidate();
+        }
         self.dirty.store(true, Ordering::Release);
 
         // Restoring a savepoint needs to accomplish the following:
@@ -1169,6 +1240,125 @@ impl WriteTransaction {
         Ok(())
     }
 
+    /// Begins a nested transaction, whose changes can be rolled back without affecting the rest of
+    /// this transaction
+    ///
+    /// Tables are opened and modified through the returned [`NestedTransaction`], which
+    /// dereferences to this transaction. Its changes become part of this transaction when it is
+    /// committed, and are discarded if it is aborted or dropped. Savepoints cannot be created
+    /// while a nested transaction is in progress. Once it has ended, they can be created again if
+    /// this transaction has not been modified
+    pub fn begin_nested(&mut self) -> Result<NestedTransaction<'_>> {
+        let checkpoint = self.checkpoint()?;
+        Ok(NestedTransaction {
+            transaction: self,
+            checkpoint: Some(checkpoint),
+        })
+    }
+
+    fn checkpoint(&mut self) -> Result<Checkpoint> {
+        // Pages allocated before the checkpoint must not be tracked as allocated after a
+        // savepoint, so prevent savepoints from being created until the checkpoint is released
+        let dirty = self.dirty.swap(true, Ordering::AcqRel);
+        let (data_root, data_freed_pages) = self.tables.get_mut()?.table_tree.checkpoint()?;
+        let (system_root, system_freed_pages) =
+            self.system_tables.get_mut()?.table_tree.checkpoint()?;
+
+        Ok(Checkpoint {
+            dirty,
+            data_root,
+            data_freed_pages,
+            system_root,
+            system_freed_pages,
+            deleted_persistent_savepoints: self.deleted_persistent_savepoints.get_mut()?.len(),
+            key_changes: match &mut self.key_changes {
+                Some(changes) => Some(changes.get_mut()?.clone()),
+                None => None,
+            },
+            frozen_pages: self.mem.freeze_uncommitted(),
+        })
+    }
+
+    fn release_checkpoint(&mut self, checkpoint: Checkpoint) {
+        let unmodified = !checkpoint.dirty
+            && !self.mem.any_uncommitted()
+            && self
+                .tables
+                .get_mut()
+                .unwrap()
+                .freed_pages
+                .lock()
+                .unwrap()
+                .len()
+                == checkpoint.data_freed_pages
+            && self
+                .system_tables
+                .get_mut()
+                .unwrap()
+                .freed_pages
+                .lock()
+                .unwrap()
+                .len()
+                == checkpoint.system_freed_pages
+            && self.deleted_persistent_savepoints.get_mut().unwrap().len()
+                == checkpoint.deleted_persistent_savepoints;
+        self.mem.unfreeze_uncommitted(checkpoint.frozen_pages);
+        // Pages which were allocated before the checkpoint, and freed after it, were not freed
+        // immediately since they were frozen
+        self.tables
+            .get_mut()
+            .unwrap()
+            .table_tree
+            .free_uncommitted_since(checkpoint.data_freed_pages);
+        self.system_tables
+            .get_mut()
+            .unwrap()
+            .table_tree
+            .free_uncommitted_since(checkpoint.system_freed_pages);
+        if unmodified {
+            self.mark_clean();
+        }
+    }
+
+    // Returns a transaction, which has not been modified, to the state it was in when it began
+    fn mark_clean(&mut self) {
+        self.dirty.store(false, Ordering::Release);
+        *self
+            .tables
+            .get_mut()
+            .unwrap()
+            .allocated_pages
+            .lock()
+            .unwrap() = PageTrackerPolicy::new_tracking();
+    }
+
+    fn rollback_to_checkpoint(&mut self, checkpoint: Checkpoint) {
+        let tables = self.tables.get_mut().unwrap();
+        assert!(tables.open_tables.is_empty());
+        tables
+            .table_tree
+            .rollback_to(checkpoint.data_root, checkpoint.data_freed_pages);
+        self.system_tables
+            .get_mut()
+            .unwrap()
+            .table_tree
+            .rollback_to(checkpoint.system_root, checkpoint.system_freed_pages);
+        self.mem.rollback_to_frozen(
+            checkpoint.frozen_pages,
+            &mut tables.allocated_pages.lock().unwrap(),
+        );
+        self.deleted_persistent_savepoints
+            .get_mut()
+            .unwrap()
+            .truncate(checkpoint.deleted_persistent_savepoints);
+        if let Some(changes) = &mut self.key_changes {
+            *changes.get_mut().unwrap() = checkpoint.key_changes.unwrap();
+        }
+        if !checkpoint.dirty {
+            self.mark_clean();
+        }
+    }
+
     /// Set the desired durability level for writes made in this transaction
     /// Defaults to [`Durability::Immediate`]
     ///
@@ -1255,6 +1445,26 @@ impl WriteTransaction {
         self.quick_repair = enabled;
     }
 
+    /// Enable or disable group commit (defaults to disabled)
+    ///
+    /// By default, a commit with [`Durability::Immediate`] holds the write lock until its `fsync`
+    /// completes, so the commit rate is limited by the latency of `fsync`.
+    ///
+    /// Alternatively, you can enable group commit. In this mode, the transaction is committed
+    /// without a durability guarantee, the write lock is released, and then [`Self::commit`] waits
+    /// for the transaction to become durable. Transactions which commit while another is waiting
+    /// for `fsync` are made durable together by a single `fsync`. [`Self::commit`] still only
+    /// returns once the transaction is durable, but other transactions may read its writes
+    /// before then, and those writes will be lost if the process crashes before the `fsync`
+    /// completes.
+    ///
+    /// Group commit has no effect if 2-phase commit or quick-repair are enabled, if the durability
+    /// is not [`Durability::Immediate`], or if the transaction creates or deletes a persistent
+    /// savepoint.
+    pub fn set_group_commit(&mut self, enabled: bool) {
+        self.group_commit = enabled;
+    }
+
     /// Open the given table
     ///
     /// The table will be created if it does not exist
@@ -1266,6 +1476,41 @@ impl WriteTransaction {
         self.tables.lock().unwrap().open_table(self, definition)
     }
 
+    /// Insert all of the given key-value pairs into the table
+    ///
+    /// The table will be created if it does not exist. If the table is empty and the entries are
+    /// sorted by key, without duplicates, the table is built directly from full pages rather than
+    /// by inserting each entry, which is much faster and writes far less data. Otherwise, entries
+    /// are inserted as if by [`Table::insert`], starting from the first entry which is out of
+    /// order, or from the beginning if the table was not empty.
+    ///
+    /// Returns the number of entries inserted
+    #[track_caller]
+    pub fn bulk_load<'k, 'v, K: Key + 'static, V: Value + 'static, KI, VI>(
+        &self,
+        definition: TableDefinition<K, V>,
+        entries: impl IntoIterator<Item = (KI, VI)>,
+    ) -> Result<u64, TableError>
+    where
+        KI: Borrow<K::SelfType<'k>>,
+        VI: Borrow<V::SelfType<'v>>,
+    {
+        let mut table = self.open_table(definition)?;
+        Ok(table.bulk_load(entries)?)
+    }
+
+    /// Removes all entries from the given table, as if by [`Table::clear`]
+    ///
+    /// The table will be created if it does not exist
+    #[track_caller]
+    pub fn truncate_table<K: Key + 'static, V: Value + 'static>(
+        &self,
+        definition: TableDefinition<K, V>,
+    ) -> Result<(), TableError> {
+        let mut table = self.open_table(definition)?;
+        Ok(table.clear()?)
+    }
+
     /// Open the given table
     ///
     /// The table will be created if it does not exist
@@ -1301,7 +1546,11 @@ impl WriteTransaction {
         self.tables
             .lock()
             .unwrap()
-            .rename_table(self, &name, new_name.name())
+            .rename_table(self, &name, new_name.name())?;
+        if let Some(changes) = &self.key_changes {
+            changes.lock().unwrap().rename_table(&name, new_name.name());
+        }
+        Ok(())
     }
 
     /// Rename the given multimap table
@@ -1326,7 +1575,24 @@ impl WriteTransaction {
         let name = definition.name().to_string();
         // Drop the definition so that callers can pass in a `Table` or `MultimapTable` to delete, without getting a TableAlreadyOpen error
         drop(definition);
-        self.tables.lock().unwrap().delete_table(self, &name)
+        let existed = self.tables.lock().unwrap().delete_table(self, &name)?;
+        if let Some(changes) = &self.key_changes {
+            changes.lock().unwrap().delete_table(&name);
+        }
+        Ok(existed)
+    }
+
+    // Copies a table, which may belong to a different database, into this transaction
+    pub(crate) fn copy_table_from(
+        &self,
+        name: &str,
+        definition: InternalTableDefinition,
+        source: &impl PageSource,
+    ) -> Result<(), TableError> {
+        self.tables
+            .lock()
+            .unwrap()
+            .insert_copied_table(self, name, definition, source)
     }
 
     /// Delete the given table
@@ -1374,31 +1640,112 @@ impl WriteTransaction {
     pub fn commit(mut self) -> Result<(), CommitError> {
         // Set completed flag first, so that we don't go through the abort() path on drop, if this fails
         self.completed = true;
-        self.commit_inner()
+        let event = self.commit_inner()?;
+        let hook = self.commit_hook.take();
+        let group_sync = self.group_commit.then(|| {
+            (
+                self.mem.clone(),
+                self.transaction_tracker.clone(),
+                self.transaction_id,
+            )
+        });
+        let checkpoint = self
+            .checkpoint_log
+            .then(|| (self.mem.clone(), self.transaction_tracker.clone()));
+        // Release the write lock first, so that the hook may begin another write transaction, and
+        // so that other transactions can join a group commit, or commit during a checkpoint
+        drop(self);
+        if let Some((mem, transaction_tracker, transaction_id)) = group_sync {
+            if let Some(synced) = mem.group_sync(transaction_id)? {
+                transaction_tracker.clear_pending_non_durable_commits_through(synced);
+            }
+        }
+        if let Some((mem, transaction_tracker)) = checkpoint {
+            if let Some(synced) = mem.checkpoint_logged_commits()? {
+                transaction_tracker.clear_pending_non_durable_commits_through(synced);
+            }
+        }
+        if let Some(event) = event {
+            (hook.unwrap().callback)(&event);
+        }
+        Ok(())
     }
 
-    fn commit_inner(&mut self) -> Result<(), CommitError> {
+    fn commit_inner(&mut self) -> Result<Option<CommitEvent>, CommitError> {
         // Quick-repair requires 2-phase commit
         if self.quick_repair {
             self.two_phase_commit = true;
         }
+        if self.two_phase_commit
+            || self.mem.has_write_ahead_log()
+            || !matches!(self.durability, InternalDurability::Immediate)
+            || !self
+                .created_persistent_savepoints
+                .lock()
+                .unwrap()
+                .is_empty()
+            || !self
+                .deleted_persistent_savepoints
+                .lock()
+                .unwrap()
+                .is_empty()
+        {
+            self.group_commit = false;
+        }
 
         let (user_root, allocated_pages, data_freed) =
             self.tables.lock().unwrap().table_tree.flush_and_close()?;
 
+        let event = if self.commit_hook.is_some() {
+            let tables = TableTree::new(
+                user_root,
+                PageHint::None,
+                self.transaction_guard.clone(),
+                self.mem.clone(),
+            )?
+            .list_definitions()?;
+            Some(commit_event(
+                self.transaction_id.raw_id(),
+                &self.original_tables()?,
+                &tables,
+                self.key_changes
+                    .take()
+                    .map(|changes| changes.into_inner().unwrap()),
+            ))
+        } else {
+            None
+        };
+
         self.store_data_freed_pages(data_freed)?;
         self.store_allocated_pages(allocated_pages.into_iter().collect())?;
 
         #[cfg(feature = "logging")]
         debug!(
-            "Committing transaction id={:?} with durability={:?} two_phase={} quick_repair={}",
-            self.transaction_id, self.durability, self.two_phase_commit, self.quick_repair
+            "Committing transaction id={:?} with durability={:?} two_phase={} quick_repair={} group_commit={}",
+            self.transaction_id,
+            self.durability,
+            self.two_phase_commit,
+            self.quick_repair,
+            self.group_commit
         );
         match self.durability {
+            // With a write-ahead log, only 2-phase commits sync the database file. They must not be
+            // eventual, since the log is emptied once they complete
+            InternalDurability::None if self.mem.has_write_ahead_log() => {
+                self.logged_commit(user_root)?;
+            }
+            _ if self.mem.has_write_ahead_log() && self.two_phase_commit => {
+                self.durable_commit(user_root, false)?;
+            }
+            _ if self.mem.has_write_ahead_log() => self.logged_commit(user_root)?,
             InternalDurability::None => self.non_durable_commit(user_root)?,
             InternalDurability::Eventual => self.durable_commit(user_root, true)?,
+            InternalDurability::Immediate if self.group_commit => {
+                self.group_commit(user_root)?;
+            }
             InternalDurability::Immediate => self.durable_commit(user_root, false)?,
         }
+        self.transaction_tracker.notify_commit();
 
         for (savepoint, transaction) in self.deleted_persistent_savepoints.lock().unwrap().iter() {
             self.transaction_tracker
@@ -1430,7 +1777,7 @@ impl WriteTransaction {
             self.transaction_id
         );
 
-        Ok(())
+        Ok(event)
     }
 
     fn store_data_freed_pages(&self, mut freed_pages: Vec<PageNumber>) -> Result {
@@ -1628,33 +1975,75 @@ impl WriteTransaction {
 
     // Commit without a durability guarantee
     pub(crate) fn non_durable_commit(&mut self, user_root: Option<BtreeHeader>) -> Result {
-        let system_root = {
-            let mut system_tables = self.system_tables.lock().unwrap();
-            let system_freed_pages = system_tables.system_freed_pages();
-            system_tables.table_tree.flush_table_root_updates()?;
-            // Store all freed pages for a future commit(), since we can't free pages during a
-            // non-durable commit (it's non-durable, so could be rolled back anytime in the future)
-            self.store_system_freed_pages(
-                &mut system_tables.table_tree,
-                system_freed_pages,
-                &mut 0,
-            )?;
-
-            system_tables
-                .table_tree
-                .flush_table_root_updates()?
-                .finalize_dirty_checksums()?
-        };
+        let system_root = self.flush_system_tables_non_durable()?;
 
+        // Register this as a non-durable transaction to ensure that the freed pages we just pushed
+        // are only processed after this has been persisted. This must happen before the commit
+        // becomes visible, since a group sync may make it durable as soon as it is
+        self.transaction_tracker
+            .register_non_durable_commit(self.transaction_id);
         self.mem
             .non_durable_commit(user_root, system_root, self.transaction_id)?;
-        // Register this as a non-durable transaction to ensure that the freed pages we just pushed
-        // are only processed after this has been persisted
+        Ok(())
+    }
+
+    fn flush_system_tables_non_durable(&mut self) -> Result<Option<BtreeHeader>> {
+        let mut system_tables = self.system_tables.lock().unwrap();
+        let system_freed_pages = system_tables.system_freed_pages();
+        system_tables.table_tree.flush_table_root_updates()?;
+        // Store all freed pages for a future commit(), since we can't free pages during a
+        // non-durable commit (it's non-durable, so could be rolled back anytime in the future)
+        self.store_system_freed_pages(&mut system_tables.table_tree, system_freed_pages, &mut 0)?;
+
+        system_tables
+            .table_tree
+            .flush_table_root_updates()?
+            .finalize_dirty_checksums()
+    }
+
+    // Commit by appending the written pages to the write-ahead log. The database file is only
+    // synced when the log is checkpointed, which happens in commit() after the write lock is
+    // released
+    fn logged_commit(&mut self, user_root: Option<BtreeHeader>) -> Result {
+        // As with a group commit, freed pages can be processed, because a logged commit that has
+        // not been synced is registered as pending, and holds a read on its parent
+        let free_until_transaction = self
+            .transaction_tracker
+            .oldest_live_read_transaction()
+            .map_or(self.transaction_id, |x| x.next());
+        self.process_freed_pages(free_until_transaction)?;
+
+        let system_root = self.flush_system_tables_non_durable()?;
+        let sync = !matches!(self.durability, InternalDurability::None);
+        let eventual = matches!(self.durability, InternalDurability::Eventual);
         self.transaction_tracker
             .register_non_durable_commit(self.transaction_id);
+        self.checkpoint_log =
+            self.mem
+                .logged_commit(user_root, system_root, self.transaction_id, sync, eventual)?;
+        if sync {
+            // The log is synced sequentially, so every earlier logged commit is now durable too
+            self.transaction_tracker.clear_pending_non_durable_commits();
+        }
+
         Ok(())
     }
 
+    // Commit without a durability guarantee, so that the commit can be made durable by a group
+    // sync, after the write lock is released
+    fn group_commit(&mut self, user_root: Option<BtreeHeader>) -> Result {
+        // Unlike a plain non-durable commit, freed pages can be processed. Any pending non-durable
+        // commit holds a read on its parent, so only pages freed by transactions that are already
+        // durable, and which are not referenced by the durable state on disk, are released
+        let free_until_transaction = self
+            .transaction_tracker
+            .oldest_live_read_transaction()
+            .map_or(self.transaction_id, |x| x.next());
+        self.process_freed_pages(free_until_transaction)?;
+
+        self.non_durable_commit(user_root)
+    }
+
     // Relocate pages to lower number regions/pages
     // Returns true if a page(s) was moved
     pub(crate) fn compact_pages(&mut self) -> Result<bool> {
@@ -1867,6 +2256,67 @@ impl Drop for WriteTransaction {
     }
 }
 
+// State of a write transaction when a nested transaction began
+struct Checkpoint {
+    // Whether the transaction had been modified
+    dirty: bool,
+    data_root: Option<BtreeHeader>,
+    data_freed_pages: usize,
+    system_root: Option<BtreeHeader>,
+    system_freed_pages: usize,
+    deleted_persistent_savepoints: usize,
+    key_changes: Option<KeyChangeLog>,
+    // Pages allocated by the transaction before the checkpoint
+    frozen_pages: HashSet<PageNumber>,
+}
+
+/// A nested transaction within a [`WriteTransaction`]
+///
+/// Created with [`WriteTransaction::begin_nested`]. Dereferences to the enclosing transaction, so
+/// tables are opened through it in the same way. If it is dropped without being committed, its
+/// changes are rolled back
+pub struct NestedTransaction<'a> {
+    transaction: &'a mut WriteTransaction,
+    checkpoint: Option<Checkpoint>,
+}
+
+impl NestedTransaction<'_> {
+    /// Begins a transaction nested within this one
+    ///
+    /// See [`WriteTransaction::begin_nested`]
+    pub fn begin_nested(&mut self) -> Result<NestedTransaction<'_>> {
+        self.transaction.begin_nested()
+    }
+
+    /// Makes the changes of this nested transaction part of the enclosing transaction
+    pub fn commit(mut self) {
+        let checkpoint = self.checkpoint.take().unwrap();
+        self.transaction.release_checkpoint(checkpoint);
+    }
+
+    /// Rolls back all changes made by this nested transaction
+    pub fn abort(mut self) {
+        let checkpoint = self.checkpoint.take().unwrap();
+        self.transaction.rollback_to_checkpoint(checkpoint);
+    }
+}
+
+impl Deref for NestedTransaction<'_> {
+    type Target = WriteTransaction;
+
+    fn deref(&self) -> &WriteTransaction {
+        self.transaction
+    }
+}
+
+impl Drop for NestedTransaction<'_> {
+    fn drop(&mut self) {
+        if let Some(checkpoint) = self.checkpoint.take() {
+            self.transaction.rollback_to_checkpoint(checkpoint);
+        }
+    }
+}
+
 /// A read-only transaction
 ///
 /// Read-only transactions may exist concurrently with writes
@@ -1889,6 +2339,13 @@ impl ReadTransaction {
         })
     }
 
+    /// Returns the id of the transaction whose snapshot this transaction reads
+    ///
+    /// See [`Database::wait_for_commit_after`]
+    pub fn transaction_id(&self) -> u64 {
+        self.tree.transaction_guard().id().raw_id()
+    }
+
     /// Open the given table
     pub fn open_table<K: Key + 'static, V: Value + 'static>(
         &self,
@@ -2005,6 +2462,64 @@ impl ReadTransaction {
             .map(|x| x.into_iter().map(UntypedMultimapTableHandle::new))
     }
 
+    /// Writes a compacted copy of the database, as of this transaction's snapshot, to a new file
+    ///
+    /// The database may continue to be read and written while the copy is made. Persistent
+    /// savepoints are not included in the copy. Returns an error if `path` already exists
+    pub fn copy_to(&self, path: impl AsRef<Path>) -> Result<(), DatabaseError> {
+        create_new_file(path.as_ref(), |file| self.copy_to_file(file))
+    }
+
+    fn copy_to_file(&self, file: File) -> Result<(), DatabaseError> {
+        let mut destination = Database::create_copy_destination(
+            file,
+            self.mem.get_page_size(),
+            self.mem.encryption(),
+        )?;
+        let txn = destination
+            .begin_write()
+            .map_err(|e| e.into_storage_error())?;
+        for table_type in [TableType::Normal, TableType::Multimap] {
+            for name in self.tree.list_tables(table_type)? {
+                let definition = self
+                    .tree
+                    .get_table_untyped(&name, table_type)
+                    .map_err(|e| e.into_storage_error_or_corrupted("Internal corruption"))?
+                    .unwrap();
+                txn.copy_table_from(&name, definition, self.mem.as_ref())
+                    .map_err(|e| e.into_storage_error_or_corrupted("Internal corruption"))?;
+            }
+        }
+        txn.commit().map_err(|e| e.into_storage_error())?;
+
+        destination.compact_copy()
+    }
+
+    /// Writes an incremental backup of this transaction's snapshot to a new file
+    ///
+    /// `previous` is the chain of backups that this backup builds on, in the order they were
+    /// written: a full backup, followed by the incremental backups that were taken after it. The
+    /// new backup only contains the pages that were written since the last backup in `previous`.
+    /// If `previous` is empty, a full backup is written.
+    ///
+    /// Backups are restored with [`Database::restore_backup_chain`]. Persistent savepoints are
+    /// not included in backups. Returns an error if `path` already exists
+    pub fn incremental_backup_to(
+        &self,
+        path: impl AsRef<Path>,
+        previous: &[impl AsRef<Path>],
+    ) -> Result<(), DatabaseError> {
+        create_new_file(path.as_ref(), |file| {
+            write_incremental_backup(
+                &self.mem,
+                &self.tree,
+                self.tree.transaction_guard().id(),
+                previous,
+                file,
+            )
+        })
+    }
+
     /// Close the transaction
     ///
     /// Transactions are automatically closed when they and all objects referencing them have been dropped,
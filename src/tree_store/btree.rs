@@ -1,14 +1,18 @@
 use crate::db::TransactionGuard;
 use crate::tree_store::btree_base::{
     AccessGuardMut, BRANCH, BranchAccessor, BranchMutator, BtreeHeader, Checksum, DEFERRED, LEAF,
-    LeafAccessor, branch_checksum, leaf_checksum,
+    LeafAccessor, SubtreeCount, branch_checksum, copy_page, leaf_checksum, range_covers,
+    range_intersects, subtree_count,
 };
+use crate::tree_store::btree_builder::BtreeBuilder;
 use crate::tree_store::btree_iters::BtreeExtractIf;
-use crate::tree_store::btree_mutator::MutateHelper;
+use crate::tree_store::btree_mutator::{
+    ComputeValue, InsertValue, MutateHelper, RangeRemovalCallback,
+};
 use crate::tree_store::page_store::{Page, PageImpl, PageMut, TransactionalMemory};
 use crate::tree_store::{
-    AccessGuardMutInPlace, AllPageNumbersBtreeIter, BtreeRangeIter, PageHint, PageNumber,
-    PageTrackerPolicy,
+    AccessGuardMutInPlace, AllPageNumbersBtreeIter, BtreeCursor, BtreeRangeIter, PageHint,
+    PageNumber, PageTrackerPolicy,
 };
 use crate::types::{Key, MutInPlaceValue, Value};
 use crate::{AccessGuard, Result};
@@ -18,7 +22,7 @@ use std::borrow::Borrow;
 use std::cmp::max;
 use std::collections::HashMap;
 use std::marker::PhantomData;
-use std::ops::RangeBounds;
+use std::ops::{Bound, RangeBounds};
 use std::sync::{Arc, Mutex};
 
 pub(crate) struct BtreeStats {
@@ -433,6 +437,62 @@ impl<K: Key + 'static, V: Value + 'static> BtreeMut<'_, K, V> {
         Ok(old_value)
     }
 
+    // Calls `f` with the existing value of `key`, if any, and inserts the value it returns. If `f`
+    // returns `None`, the key is removed instead. The lookup and insertion are performed in a single
+    // traversal of the tree.
+    //
+    // Returns the old value, and whether the key is now present
+    pub(crate) fn update(
+        &mut self,
+        key: &K::SelfType<'_>,
+        f: &mut ComputeValue,
+    ) -> Result<(Option<AccessGuard<V>>, bool)> {
+        #[cfg(feature = "logging")]
+        trace!("Btree(root={:?}): Updating {:?}", &self.root, key);
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut operation: MutateHelper<'_, '_, K, V> = MutateHelper::new(
+            &mut self.root,
+            self.mem.clone(),
+            freed_pages.as_mut(),
+            self.allocated_pages.clone(),
+        );
+        let mut found = false;
+        let mut compute = |old: Option<&[u8]>| {
+            found = old.is_some();
+            f(old)
+        };
+        if let Some((old_value, _)) =
+            operation.insert_with(key, &mut InsertValue::Computed(&mut compute))?
+        {
+            return Ok((old_value, true));
+        }
+        if found {
+            Ok((operation.delete(key)?, false))
+        } else {
+            Ok((None, false))
+        }
+    }
+
+    // Like update(), except that the tree is left unmodified if `f` returns `None`.
+    //
+    // Returns `true` if a value was inserted
+    pub(crate) fn insert_computed(
+        &mut self,
+        key: &K::SelfType<'_>,
+        f: &mut ComputeValue,
+    ) -> Result<bool> {
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut operation: MutateHelper<'_, '_, K, V> = MutateHelper::new(
+            &mut self.root,
+            self.mem.clone(),
+            freed_pages.as_mut(),
+            self.allocated_pages.clone(),
+        );
+        Ok(operation
+            .insert_with(key, &mut InsertValue::Computed(f))?
+            .is_some())
+    }
+
     // Insert without allocating or freeing any pages. This requires that you've previously
     // inserted the same key, with a value of at least the same serialized length, earlier
     // in the same transaction. If those preconditions aren't satisfied, insert_inplace()
@@ -608,6 +668,24 @@ impl<K: Key + 'static, V: Value + 'static> BtreeMut<'_, K, V> {
         self.read_tree()?.range(range)
     }
 
+    pub(crate) fn range_len<'a0, T: RangeBounds<KR>, KR: Borrow<K::SelfType<'a0>>>(
+        &self,
+        range: &'_ T,
+    ) -> Result<u64> {
+        self.read_tree()?.range_len(range)
+    }
+
+    pub(crate) fn nth(
+        &self,
+        index: u64,
+    ) -> Result<Option<(AccessGuard<'static, K>, AccessGuard<'static, V>)>> {
+        self.read_tree()?.nth(index)
+    }
+
+    pub(crate) fn cursor(&self) -> BtreeCursor<K, V> {
+        BtreeCursor::new(self.mem.clone())
+    }
+
     pub(crate) fn extract_from_if<
         'a,
         'a0,
@@ -671,6 +749,109 @@ impl<K: Key + 'static, V: Value + 'static> BtreeMut<'_, K, V> {
         Ok(())
     }
 
+    // Builds the tree bottom-up from `entries`, which must be sorted by key. The tree must be
+    // empty. Stops at the first entry whose key is not greater than the previous one, and returns
+    // it so that the caller can insert it, and any remaining entries, individually
+    pub(crate) fn bulk_load<'k, 'v, KI, VI>(
+        &mut self,
+        entries: &mut impl Iterator<Item = Result<(KI, VI)>>,
+    ) -> Result<(u64, Option<(KI, VI)>)>
+    where
+        KI: Borrow<K::SelfType<'k>>,
+        VI: Borrow<V::SelfType<'v>>,
+        K: 'k,
+        V: 'v,
+    {
+        assert!(self.root.is_none());
+        let mut builder = BtreeBuilder::<K, V>::new(self.mem.clone(), self.allocated_pages.clone());
+        let mut loaded = 0;
+        let mut unsorted = None;
+        for entry in entries.by_ref() {
+            let (key, value) = entry?;
+            let sorted = builder.last_key().is_none_or(|previous| {
+                K::compare(previous, K::as_bytes(key.borrow()).as_ref()).is_lt()
+            });
+            if !sorted {
+                unsorted = Some((key, value));
+                break;
+            }
+            builder.push(
+                K::as_bytes(key.borrow()).as_ref(),
+                V::as_bytes(value.borrow()).as_ref(),
+            )?;
+            loaded += 1;
+        }
+        self.root = builder.finish()?;
+
+        Ok((loaded, unsorted))
+    }
+
+    // Removes all entries in the range, and returns the number removed. Subtrees which lie entirely
+    // within the range are freed without reading their leaves, unless `on_removed` is provided
+    pub(crate) fn remove_range<'a0, T: RangeBounds<KR> + 'a0, KR: Borrow<K::SelfType<'a0>> + 'a0>(
+        &mut self,
+        range: &'_ T,
+        mut on_removed: Option<&mut RangeRemovalCallback>,
+    ) -> Result<u64>
+    where
+        K: 'a0,
+    {
+        let start = range.start_bound().map(|x| K::as_bytes(x.borrow()));
+        let end = range.end_bound().map(|x| K::as_bytes(x.borrow()));
+        let mut removed = {
+            let mut freed_pages = self.freed_pages.lock().unwrap();
+            let mut operation: MutateHelper<'_, '_, K, V> = MutateHelper::new(
+                &mut self.root,
+                self.mem.clone(),
+                freed_pages.as_mut(),
+                self.allocated_pages.clone(),
+            );
+            operation.prune_range(
+                start.as_ref().map(AsRef::as_ref),
+                end.as_ref().map(AsRef::as_ref),
+                &mut on_removed,
+            )?
+        };
+        if matches!(
+            (range.start_bound(), range.end_bound()),
+            (Bound::Unbounded, Bound::Unbounded)
+        ) {
+            return Ok(removed);
+        }
+
+        // Remove the entries in the subtrees which had to be kept
+        let iter = self.range(range)?;
+        let mut freed = vec![];
+        // Do not modify the existing tree, because we're iterating over it concurrently with the removals
+        let mut operation: MutateHelper<'_, '_, K, V> = MutateHelper::new_do_not_modify(
+            &mut self.root,
+            self.mem.clone(),
+            &mut freed,
+            self.allocated_pages.clone(),
+        );
+        for entry in iter {
+            let (page, key_range, value_range) = entry?.into_raw();
+            if let Some(callback) = on_removed.as_mut() {
+                callback(
+                    &page.memory()[key_range.clone()],
+                    &page.memory()[value_range],
+                )?;
+            }
+            let key = K::from_bytes(&page.memory()[key_range]);
+            assert!(operation.delete(&key)?.is_some());
+            removed += 1;
+        }
+        let mut freed_pages = self.freed_pages.lock().unwrap();
+        let mut allocated_pages = self.allocated_pages.lock().unwrap();
+        for page in freed {
+            if !self.mem.free_if_uncommitted(page, &mut allocated_pages) {
+                freed_pages.push(page);
+            }
+        }
+
+        Ok(removed)
+    }
+
     pub(crate) fn len(&self) -> Result<u64> {
         self.read_tree()?.len()
     }
@@ -743,9 +924,16 @@ impl RawBtree {
         Ok(self.root.map_or(0, |x| x.length))
     }
 
+    // Also verifies that the subtree counts, if stored, match the entries in the tree
     pub(crate) fn verify_checksum(&self) -> Result<bool> {
         if let Some(header) = self.root {
-            self.verify_checksum_helper(header.root, header.checksum)
+            if !self.verify_checksum_helper(header.root, header.checksum, SubtreeCount::UNKNOWN)? {
+                return Ok(false);
+            }
+            let count = subtree_count(&self.mem.get_page(header.root)?);
+            Ok(count
+                .entries()
+                .is_none_or(|entries| entries == header.length))
         } else {
             Ok(true)
         }
@@ -755,6 +943,7 @@ impl RawBtree {
         &self,
         page_number: PageNumber,
         expected_checksum: Checksum,
+        expected_count: SubtreeCount,
     ) -> Result<bool> {
         let page = self.mem.get_page(page_number)?;
         let node_mem = page.memory();
@@ -764,6 +953,8 @@ impl RawBtree {
                     leaf_checksum(&page, self.fixed_key_size, self.fixed_value_size)
                 {
                     expected_checksum == computed
+                        && (expected_count == SubtreeCount::UNKNOWN
+                            || expected_count == subtree_count(&page))
                 } else {
                     false
                 }
@@ -777,10 +968,16 @@ impl RawBtree {
                     return Ok(false);
                 }
                 let accessor = BranchAccessor::new(&page, self.fixed_key_size);
+                if expected_count != SubtreeCount::UNKNOWN
+                    && expected_count != accessor.total_count()
+                {
+                    return Ok(false);
+                }
                 for i in 0..accessor.count_children() {
                     if !self.verify_checksum_helper(
                         accessor.child_page(i).unwrap(),
                         accessor.child_checksum(i).unwrap(),
+                        accessor.child_count(i).unwrap(),
                     )? {
                         return Ok(false);
                     }
@@ -811,7 +1008,7 @@ impl<K: Key, V: Value> Btree<K, V> {
         mem: Arc<TransactionalMemory>,
     ) -> Result<Self> {
         let cached_root = if let Some(header) = root {
-            Some(mem.get_page_extended(header.root, hint)?)
+            Some(Self::read_page(&mem, header.root, hint, &guard)?)
         } else {
             None
         };
@@ -826,6 +1023,19 @@ impl<K: Key, V: Value> Btree<K, V> {
         })
     }
 
+    // Pages of read transactions may be memory mapped, in which case they pin the transaction
+    fn read_page(
+        mem: &TransactionalMemory,
+        page_number: PageNumber,
+        hint: PageHint,
+        guard: &Arc<TransactionGuard>,
+    ) -> Result<PageImpl> {
+        match hint {
+            PageHint::Clean => mem.get_page_pinned(page_number, guard),
+            PageHint::None => mem.get_page_extended(page_number, hint),
+        }
+    }
+
     pub(crate) fn transaction_guard(&self) -> &Arc<TransactionGuard> {
         &self.transaction_guard
     }
@@ -882,7 +1092,186 @@ impl<K: Key, V: Value> Btree<K, V> {
             BRANCH => {
                 let accessor = BranchAccessor::new(&page, K::fixed_width());
                 let (_, child_page) = accessor.child_for_key::<K>(query);
-                self.get_helper(self.mem.get_page_extended(child_page, self.hint)?, query)
+                let child =
+                    Self::read_page(&self.mem, child_page, self.hint, &self.transaction_guard)?;
+                self.get_helper(child, query)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Counts the entries in the range. Subtrees which lie entirely within the range are counted
+    // using the counts stored in their parent, so only the pages at the boundaries of the range
+    // are read
+    pub(crate) fn range_len<'a0, T: RangeBounds<KR>, KR: Borrow<K::SelfType<'a0>>>(
+        &self,
+        range: &'_ T,
+    ) -> Result<u64> {
+        let (Some(root), Some(root_page)) = (self.root, &self.cached_root) else {
+            return Ok(0);
+        };
+        if matches!(
+            (range.start_bound(), range.end_bound()),
+            (Bound::Unbounded, Bound::Unbounded)
+        ) {
+            return Ok(root.length);
+        }
+        let start = range.start_bound().map(|x| K::as_bytes(x.borrow()));
+        let end = range.end_bound().map(|x| K::as_bytes(x.borrow()));
+        self.range_len_helper(
+            root_page.clone(),
+            None,
+            None,
+            start.as_ref().map(AsRef::as_ref),
+            end.as_ref().map(AsRef::as_ref),
+        )
+    }
+
+    // All keys in `page` are greater than `lower` and less than or equal to `upper`, if they are
+    // provided
+    fn range_len_helper(
+        &self,
+        page: PageImpl,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Result<u64> {
+        match page.memory()[0] {
+            LEAF => {
+                let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+                let (first, last) = accessor.range_positions::<K>(start, end);
+                Ok((last - first) as u64)
+            }
+            BRANCH => {
+                let accessor = BranchAccessor::new(&page, K::fixed_width());
+                let num_children = accessor.count_children();
+                let mut total = 0;
+                for i in 0..num_children {
+                    let child_lower = if i == 0 { lower } else { accessor.key(i - 1) };
+                    let child_upper = if i == num_children - 1 {
+                        upper
+                    } else {
+                        accessor.key(i)
+                    };
+                    if !range_intersects::<K>(child_lower, child_upper, start, end) {
+                        continue;
+                    }
+                    let covered = range_covers::<K>(child_lower, child_upper, start, end);
+                    if covered {
+                        if let Some(entries) = accessor.child_count(i).unwrap().entries() {
+                            total += entries;
+                            continue;
+                        }
+                    }
+                    let child = self
+                        .mem
+                        .get_page_extended(accessor.child_page(i).unwrap(), self.hint)?;
+                    total += if covered {
+                        self.subtree_len(child)?
+                    } else {
+                        self.range_len_helper(child, child_lower, child_upper, start, end)?
+                    };
+                }
+                Ok(total)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Only used for pages which do not store subtree counts
+    fn subtree_len(&self, page: PageImpl) -> Result<u64> {
+        match page.memory()[0] {
+            LEAF => {
+                let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+                Ok(accessor.num_pairs() as u64)
+            }
+            BRANCH => {
+                let accessor = BranchAccessor::new(&page, K::fixed_width());
+                if let Some(entries) = accessor.total_count().entries() {
+                    return Ok(entries);
+                }
+                let mut total = 0;
+                for i in 0..accessor.count_children() {
+                    let child = self
+                        .mem
+                        .get_page_extended(accessor.child_page(i).unwrap(), self.hint)?;
+                    total += self.subtree_len(child)?;
+                }
+                Ok(total)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Returns the entry at `index`. Subtrees before it are skipped using the counts stored in their
+    // parent. If those are not stored, their leaves are read, starting from whichever end of the
+    // tree is closer
+    pub(crate) fn nth(
+        &self,
+        index: u64,
+    ) -> Result<Option<(AccessGuard<'static, K>, AccessGuard<'static, V>)>> {
+        let (Some(root), Some(root_page)) = (self.root, &self.cached_root) else {
+            return Ok(None);
+        };
+        if index >= root.length {
+            return Ok(None);
+        }
+        let reverse = index >= root.length / 2;
+        let mut remaining = if reverse {
+            root.length - 1 - index
+        } else {
+            index
+        };
+        self.nth_helper(root_page.clone(), &mut remaining, reverse)
+    }
+
+    // Subtracts the number of entries in `page` from `remaining`, unless the entry is in `page`
+    fn nth_helper(
+        &self,
+        page: PageImpl,
+        remaining: &mut u64,
+        reverse: bool,
+    ) -> Result<Option<(AccessGuard<'static, K>, AccessGuard<'static, V>)>> {
+        match page.memory()[0] {
+            LEAF => {
+                let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+                let num_pairs = accessor.num_pairs() as u64;
+                if *remaining >= num_pairs {
+                    *remaining -= num_pairs;
+                    return Ok(None);
+                }
+                let entry = if reverse {
+                    num_pairs - 1 - *remaining
+                } else {
+                    *remaining
+                };
+                let (key_range, value_range) = accessor
+                    .entry_ranges(usize::try_from(entry).unwrap())
+                    .unwrap();
+                let key_guard = AccessGuard::with_page(page.clone(), key_range);
+                let value_guard = AccessGuard::with_page(page, value_range);
+                Ok(Some((key_guard, value_guard)))
+            }
+            BRANCH => {
+                let accessor = BranchAccessor::new(&page, K::fixed_width());
+                let num_children = accessor.count_children();
+                for i in 0..num_children {
+                    let i = if reverse { num_children - 1 - i } else { i };
+                    if let Some(entries) = accessor.child_count(i).unwrap().entries() {
+                        if *remaining >= entries {
+                            *remaining -= entries;
+                            continue;
+                        }
+                    }
+                    let child = self
+                        .mem
+                        .get_page_extended(accessor.child_page(i).unwrap(), self.hint)?;
+                    if let Some(entry) = self.nth_helper(child, remaining, reverse)? {
+                        return Ok(Some(entry));
+                    }
+                }
+                Ok(None)
             }
             _ => unreachable!(),
         }
@@ -960,6 +1349,10 @@ impl<K: Key, V: Value> Btree<K, V> {
         BtreeRangeIter::new(range, self.root.map(|x| x.root), self.mem.clone())
     }
 
+    pub(crate) fn cursor(&self) -> BtreeCursor<K, V> {
+        BtreeCursor::new(self.mem.clone())
+    }
+
     pub(crate) fn len(&self) -> Result<u64> {
         Ok(self.root.map_or(0, |x| x.length))
     }
@@ -1037,6 +1430,101 @@ pub(crate) fn btree_stats(
     }
 }
 
+// A source of pages that can be copied into a database
+pub(crate) trait PageSource {
+    type Page: Page;
+
+    // checksum is the checksum of the page, as recorded by its parent
+    fn get_page(&self, page_number: PageNumber, checksum: Checksum) -> Result<Self::Page>;
+}
+
+impl PageSource for TransactionalMemory {
+    type Page = PageImpl;
+
+    fn get_page(&self, page_number: PageNumber, _checksum: Checksum) -> Result<PageImpl> {
+        TransactionalMemory::get_page(self, page_number)
+    }
+}
+
+// Copies the tree rooted at page_number in `source` to newly allocated pages in `destination`,
+// and returns the root of the copy and its count.
+// The checksums of the copy are deferred, and must be finalized by the caller
+pub(crate) fn copy_btree(
+    page_number: PageNumber,
+    checksum: Checksum,
+    fixed_key_size: Option<usize>,
+    source: &impl PageSource,
+    destination: &TransactionalMemory,
+    allocated_pages: &mut PageTrackerPolicy,
+) -> Result<(PageNumber, SubtreeCount)> {
+    let old_page = source.get_page(page_number, checksum)?;
+    let mut new_page = copy_page(&old_page, fixed_key_size, destination, allocated_pages)?;
+
+    match old_page.memory()[0] {
+        LEAF => {
+            // No-op
+        }
+        BRANCH => {
+            let accessor = BranchAccessor::new(&old_page, fixed_key_size);
+            let mut mutator = BranchMutator::new(&mut new_page);
+            for i in 0..accessor.count_children() {
+                let (new_child, new_count) = copy_btree(
+                    accessor.child_page(i).unwrap(),
+                    accessor.child_checksum(i).unwrap(),
+                    fixed_key_size,
+                    source,
+                    destination,
+                    allocated_pages,
+                )?;
+                mutator.write_child_page(i, new_child, DEFERRED);
+                mutator.write_child_count(i, new_count);
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    Ok((new_page.get_page_number(), subtree_count(&new_page)))
+}
+
+// Visits the pages of the tree rooted at page_number, along with the checksum of each page.
+// The children of a page are skipped if the visitor returns false for it
+pub(crate) fn visit_pages_with_checksums<F>(
+    page_number: PageNumber,
+    checksum: Checksum,
+    fixed_key_size: Option<usize>,
+    mem: &TransactionalMemory,
+    visitor: &mut F,
+) -> Result
+where
+    F: FnMut(&PageImpl, Checksum) -> Result<bool>,
+{
+    let page = mem.get_page(page_number)?;
+    if !visitor(&page, checksum)? {
+        return Ok(());
+    }
+
+    match page.memory()[0] {
+        LEAF => {
+            // No-op
+        }
+        BRANCH => {
+            let accessor = BranchAccessor::new(&page, fixed_key_size);
+            for i in 0..accessor.count_children() {
+                visit_pages_with_checksums(
+                    accessor.child_page(i).unwrap(),
+                    accessor.child_checksum(i).unwrap(),
+                    fixed_key_size,
+                    mem,
+                    visitor,
+                )?;
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
 fn stats_helper(
     page_number: PageNumber,
     mem: &TransactionalMemory,
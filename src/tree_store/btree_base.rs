@@ -3,10 +3,10 @@ use crate::tree_store::{PageNumber, PageTrackerPolicy};
 use crate::types::{Key, MutInPlaceValue, Value};
 use crate::{Result, StorageError};
 use std::borrow::Borrow;
-use std::cmp::Ordering;
+use std::cmp::{Ordering, max};
 use std::marker::PhantomData;
 use std::mem::size_of;
-use std::ops::Range;
+use std::ops::{Bound, Range};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
@@ -17,6 +17,130 @@ pub(crate) type Checksum = u128;
 // Dummy value. Final value will be computed during commit
 pub(crate) const DEFERRED: Checksum = 999;
 
+// Set in the num_keys field of branch pages which store the number of entries below each child
+const COUNTED_BRANCH: u16 = 0x8000;
+pub(super) const MAX_BRANCH_KEYS: usize = 0x7FFF;
+
+// The number of entries in the subtree below a child of a branch page, and whether that child is a
+// leaf. Only stored by branch pages written with file format version 4
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SubtreeCount(u64);
+
+impl SubtreeCount {
+    // The branch page does not store counts
+    pub(crate) const UNKNOWN: Self = Self(u64::MAX);
+    const LEAF_BIT: u64 = 1 << 63;
+
+    pub(crate) fn leaf(entries: usize) -> Self {
+        Self(u64::try_from(entries).unwrap() | Self::LEAF_BIT)
+    }
+
+    pub(crate) fn branch(entries: u64) -> Self {
+        assert!(entries < Self::LEAF_BIT);
+        Self(entries)
+    }
+
+    // Sum of the entries in `children`, as the count of their parent branch
+    pub(crate) fn sum(children: impl IntoIterator<Item = Self>) -> Self {
+        let mut total = 0;
+        for child in children {
+            let Some(entries) = child.entries() else {
+                return Self::UNKNOWN;
+            };
+            total += entries;
+        }
+        Self::branch(total)
+    }
+
+    pub(crate) fn entries(self) -> Option<u64> {
+        if self == Self::UNKNOWN {
+            None
+        } else {
+            Some(self.0 & !Self::LEAF_BIT)
+        }
+    }
+
+    pub(crate) fn is_leaf(self) -> bool {
+        self != Self::UNKNOWN && self.0 & Self::LEAF_BIT != 0
+    }
+
+    fn to_le_bytes(self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+
+    fn from_le_bytes(bytes: [u8; 8]) -> Self {
+        Self(u64::from_le_bytes(bytes))
+    }
+}
+
+// The count of the subtree rooted at `page`
+pub(crate) fn subtree_count<T: Page>(page: &T) -> SubtreeCount {
+    match page.memory()[0] {
+        LEAF => SubtreeCount::leaf(LeafAccessor::new(page.memory(), None, None).num_pairs()),
+        BRANCH => BranchAccessor::new(page, None).total_count(),
+        _ => unreachable!(),
+    }
+}
+
+// Copies `page` to a newly allocated page in `destination`. Branch pages are rewritten if
+// `destination` uses the other branch layout, in which case the caller must write the counts of
+// their children
+pub(crate) fn copy_page<T: Page>(
+    page: &T,
+    fixed_key_size: Option<usize>,
+    destination: &TransactionalMemory,
+    allocated_pages: &mut PageTrackerPolicy,
+) -> Result<PageMut> {
+    if page.memory()[0] == BRANCH {
+        let accessor = BranchAccessor::new(page, fixed_key_size);
+        let counted = destination.stores_subtree_counts();
+        if accessor.counted != counted {
+            let num_keys = accessor.num_keys();
+            let key_bytes = (0..num_keys).map(|i| accessor.key(i).unwrap().len()).sum();
+            let size =
+                RawBranchBuilder::required_bytes(num_keys, key_bytes, fixed_key_size, counted);
+            let mut new_page = destination.allocate(size, allocated_pages)?;
+            let mut builder =
+                RawBranchBuilder::new(&mut new_page, num_keys, fixed_key_size, counted);
+            builder.write_first_page(
+                accessor.child_page(0).unwrap(),
+                accessor.child_checksum(0).unwrap(),
+                SubtreeCount::UNKNOWN,
+            );
+            for i in 0..num_keys {
+                builder.write_nth_key(
+                    accessor.key(i).unwrap(),
+                    accessor.child_page(i + 1).unwrap(),
+                    accessor.child_checksum(i + 1).unwrap(),
+                    SubtreeCount::UNKNOWN,
+                    i,
+                );
+            }
+            drop(builder);
+            return Ok(new_page);
+        }
+    }
+    let mut new_page = destination.allocate(page.memory().len(), allocated_pages)?;
+    new_page.memory_mut().copy_from_slice(page.memory());
+    Ok(new_page)
+}
+
+// Returns the number of keys, and whether the branch page stores subtree counts
+fn branch_num_keys(page: &[u8]) -> (usize, bool) {
+    let raw = u16::from_le_bytes(page[2..4].try_into().unwrap());
+    ((raw & !COUNTED_BRANCH).into(), raw & COUNTED_BRANCH != 0)
+}
+
+// Bytes of metadata stored for each child of a branch page
+fn branch_child_bytes(counted: bool) -> usize {
+    let bytes = PageNumber::serialized_size() + size_of::<Checksum>();
+    if counted {
+        bytes + size_of::<SubtreeCount>()
+    } else {
+        bytes
+    }
+}
+
 pub(super) fn leaf_checksum<T: Page>(
     page: &T,
     fixed_key_size: Option<usize>,
@@ -78,6 +202,46 @@ pub(super) fn branch_checksum<T: Page>(
     }
 }
 
+// Whether every key in (`lower`, `upper`] is within the range. `None` bounds are unbounded
+pub(super) fn range_covers<K: Key>(
+    lower: Option<&[u8]>,
+    upper: Option<&[u8]>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+) -> bool {
+    let after_start = match start {
+        Bound::Unbounded => true,
+        Bound::Included(x) | Bound::Excluded(x) => {
+            lower.is_some_and(|lower| K::compare(lower, x).is_ge())
+        }
+    };
+    let before_end = match end {
+        Bound::Unbounded => true,
+        Bound::Included(x) => upper.is_some_and(|upper| K::compare(upper, x).is_le()),
+        Bound::Excluded(x) => upper.is_some_and(|upper| K::compare(upper, x).is_lt()),
+    };
+    after_start && before_end
+}
+
+// Whether any key in (`lower`, `upper`] may be within the range. `None` bounds are unbounded
+pub(super) fn range_intersects<K: Key>(
+    lower: Option<&[u8]>,
+    upper: Option<&[u8]>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+) -> bool {
+    let before_start = match (start, upper) {
+        (Bound::Included(x), Some(upper)) => K::compare(upper, x).is_lt(),
+        (Bound::Excluded(x), Some(upper)) => K::compare(upper, x).is_le(),
+        _ => false,
+    };
+    let after_end = match (end, lower) {
+        (Bound::Included(x) | Bound::Excluded(x), Some(lower)) => K::compare(lower, x).is_ge(),
+        _ => false,
+    };
+    !before_start && !after_end
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub(crate) struct BtreeHeader {
     pub(crate) root: PageNumber,
@@ -477,6 +641,31 @@ impl<'a> LeafAccessor<'a> {
         (min_entry, false)
     }
 
+    // Returns the positions of the first entry in the range, and of the first entry after it
+    pub(crate) fn range_positions<K: Key>(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> (usize, usize) {
+        let first = match start {
+            Bound::Included(x) => self.position::<K>(x).0,
+            Bound::Excluded(x) => {
+                let (position, found) = self.position::<K>(x);
+                if found { position + 1 } else { position }
+            }
+            Bound::Unbounded => 0,
+        };
+        let last = match end {
+            Bound::Included(x) => {
+                let (position, found) = self.position::<K>(x);
+                if found { position + 1 } else { position }
+            }
+            Bound::Excluded(x) => self.position::<K>(x).0,
+            Bound::Unbounded => self.num_pairs(),
+        };
+        (first, max(first, last))
+    }
+
     pub(crate) fn find_key<K: Key>(&self, query: &[u8]) -> Option<usize> {
         let (entry, found) = self.position::<K>(query);
         if found { Some(entry) } else { None }
@@ -1252,6 +1441,7 @@ impl<'b> LeafMutator<'b> {
 pub(crate) struct BranchAccessor<'a: 'b, 'b, T: Page + 'a> {
     page: &'b T,
     num_keys: usize,
+    counted: bool,
     fixed_key_size: Option<usize>,
     _page_lifetime: PhantomData<&'a ()>,
 }
@@ -1259,10 +1449,11 @@ pub(crate) struct BranchAccessor<'a: 'b, 'b, T: Page + 'a> {
 impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
     pub(crate) fn new(page: &'b T, fixed_key_size: Option<usize>) -> Self {
         debug_assert_eq!(page.memory()[0], BRANCH);
-        let num_keys = u16::from_le_bytes(page.memory()[2..4].try_into().unwrap()) as usize;
+        let (num_keys, counted) = branch_num_keys(page.memory());
         BranchAccessor {
             page,
             num_keys,
+            counted,
             fixed_key_size,
             _page_lifetime: Default::default(),
         }
@@ -1313,10 +1504,10 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
 
     fn key_section_start(&self) -> usize {
         if self.fixed_key_size.is_none() {
-            8 + (PageNumber::serialized_size() + size_of::<Checksum>()) * self.count_children()
+            8 + branch_child_bytes(self.counted) * self.count_children()
                 + size_of::<u32>() * self.num_keys()
         } else {
-            8 + (PageNumber::serialized_size() + size_of::<Checksum>()) * self.count_children()
+            8 + branch_child_bytes(self.counted) * self.count_children()
         }
     }
 
@@ -1332,9 +1523,8 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
         if let Some(fixed) = self.fixed_key_size {
             return Some(self.key_section_start() + fixed * (n + 1));
         }
-        let offset = 8
-            + (PageNumber::serialized_size() + size_of::<Checksum>()) * self.count_children()
-            + size_of::<u32>() * n;
+        let offset =
+            8 + branch_child_bytes(self.counted) * self.count_children() + size_of::<u32>() * n;
         Some(u32::from_le_bytes(
             self.page
                 .memory()
@@ -1384,16 +1574,40 @@ impl<'a: 'b, 'b, T: Page + 'a> BranchAccessor<'a, 'b, T> {
         ))
     }
 
+    // Returns `SubtreeCount::UNKNOWN` if this page does not store counts
+    pub(crate) fn child_count(&self, n: usize) -> Option<SubtreeCount> {
+        if n >= self.count_children() {
+            return None;
+        }
+        if !self.counted {
+            return Some(SubtreeCount::UNKNOWN);
+        }
+
+        let offset = 8
+            + (PageNumber::serialized_size() + size_of::<Checksum>()) * self.count_children()
+            + size_of::<SubtreeCount>() * n;
+        Some(SubtreeCount::from_le_bytes(
+            self.page.memory()[offset..(offset + size_of::<SubtreeCount>())]
+                .try_into()
+                .unwrap(),
+        ))
+    }
+
+    pub(crate) fn total_count(&self) -> SubtreeCount {
+        SubtreeCount::sum((0..self.count_children()).map(|i| self.child_count(i).unwrap()))
+    }
+
     fn num_keys(&self) -> usize {
         self.num_keys
     }
 }
 
 pub(super) struct BranchBuilder<'a, 'b> {
-    children: Vec<(PageNumber, Checksum)>,
+    children: Vec<(PageNumber, Checksum, SubtreeCount)>,
     keys: Vec<&'a [u8]>,
     total_key_bytes: usize,
     fixed_key_size: Option<usize>,
+    counted: bool,
     mem: &'b TransactionalMemory,
     allocated_pages: &'b Mutex<PageTrackerPolicy>,
 }
@@ -1410,17 +1624,29 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
             keys: Vec::with_capacity(child_capacity - 1),
             total_key_bytes: 0,
             fixed_key_size,
+            counted: mem.stores_subtree_counts(),
             mem,
             allocated_pages,
         }
     }
 
-    pub(super) fn replace_child(&mut self, index: usize, child: PageNumber, checksum: Checksum) {
-        self.children[index] = (child, checksum);
+    pub(super) fn replace_child(
+        &mut self,
+        index: usize,
+        child: PageNumber,
+        checksum: Checksum,
+        count: SubtreeCount,
+    ) {
+        self.children[index] = (child, checksum, count);
     }
 
-    pub(super) fn push_child(&mut self, child: PageNumber, checksum: Checksum) {
-        self.children.push((child, checksum));
+    pub(super) fn push_child(
+        &mut self,
+        child: PageNumber,
+        checksum: Checksum,
+        count: SubtreeCount,
+    ) {
+        self.children.push((child, checksum, count));
     }
 
     pub(super) fn push_key(&mut self, key: &'a [u8]) {
@@ -1432,14 +1658,15 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
         for i in 0..accessor.count_children() {
             let child = accessor.child_page(i).unwrap();
             let checksum = accessor.child_checksum(i).unwrap();
-            self.push_child(child, checksum);
+            let count = accessor.child_count(i).unwrap();
+            self.push_child(child, checksum, count);
         }
         for i in 0..(accessor.count_children() - 1) {
             self.push_key(accessor.key(i).unwrap());
         }
     }
 
-    pub(super) fn to_single_child(&self) -> Option<(PageNumber, Checksum)> {
+    pub(super) fn to_single_child(&self) -> Option<(PageNumber, Checksum, SubtreeCount)> {
         if self.children.len() > 1 {
             None
         } else {
@@ -1453,14 +1680,22 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
             self.keys.len(),
             self.total_key_bytes,
             self.fixed_key_size,
+            self.counted,
         );
         let mut allocated_pages = self.allocated_pages.lock().unwrap();
         let mut page = self.mem.allocate(size, &mut allocated_pages)?;
-        let mut builder = RawBranchBuilder::new(&mut page, self.keys.len(), self.fixed_key_size);
-        builder.write_first_page(self.children[0].0, self.children[0].1);
+        let mut builder = RawBranchBuilder::new(
+            &mut page,
+            self.keys.len(),
+            self.fixed_key_size,
+            self.counted,
+        );
+        let (child, checksum, count) = self.children[0];
+        builder.write_first_page(child, checksum, count);
         for i in 1..self.children.len() {
             let key = &self.keys[i - 1];
-            builder.write_nth_key(key.as_ref(), self.children[i].0, self.children[i].1, i - 1);
+            let (child, checksum, count) = self.children[i];
+            builder.write_nth_key(key.as_ref(), child, checksum, count, i - 1);
         }
         drop(builder);
 
@@ -1472,6 +1707,7 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
             self.keys.len(),
             self.total_key_bytes,
             self.fixed_key_size,
+            self.counted,
         );
         size > self.mem.get_page_size() && self.keys.len() >= 3
     }
@@ -1485,19 +1721,21 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
         let division_key = self.keys[division];
         let second_split_key_len = self.total_key_bytes - first_split_key_len - division_key.len();
 
-        let size =
-            RawBranchBuilder::required_bytes(division, first_split_key_len, self.fixed_key_size);
+        let size = RawBranchBuilder::required_bytes(
+            division,
+            first_split_key_len,
+            self.fixed_key_size,
+            self.counted,
+        );
         let mut page1 = self.mem.allocate(size, &mut allocated_pages)?;
-        let mut builder = RawBranchBuilder::new(&mut page1, division, self.fixed_key_size);
-        builder.write_first_page(self.children[0].0, self.children[0].1);
+        let mut builder =
+            RawBranchBuilder::new(&mut page1, division, self.fixed_key_size, self.counted);
+        let (child, checksum, count) = self.children[0];
+        builder.write_first_page(child, checksum, count);
         for i in 0..division {
             let key = &self.keys[i];
-            builder.write_nth_key(
-                key.as_ref(),
-                self.children[i + 1].0,
-                self.children[i + 1].1,
-                i,
-            );
+            let (child, checksum, count) = self.children[i + 1];
+            builder.write_nth_key(key.as_ref(), child, checksum, count, i);
         }
         drop(builder);
 
@@ -1505,22 +1743,21 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
             self.keys.len() - division - 1,
             second_split_key_len,
             self.fixed_key_size,
+            self.counted,
         );
         let mut page2 = self.mem.allocate(size, &mut allocated_pages)?;
         let mut builder = RawBranchBuilder::new(
             &mut page2,
             self.keys.len() - division - 1,
             self.fixed_key_size,
+            self.counted,
         );
-        builder.write_first_page(self.children[division + 1].0, self.children[division + 1].1);
+        let (child, checksum, count) = self.children[division + 1];
+        builder.write_first_page(child, checksum, count);
         for i in (division + 1)..self.keys.len() {
             let key = &self.keys[i];
-            builder.write_nth_key(
-                key.as_ref(),
-                self.children[i + 1].0,
-                self.children[i + 1].1,
-                i - division - 1,
-            );
+            let (child, checksum, count) = self.children[i + 1];
+            builder.write_nth_key(key.as_ref(), child, checksum, count, i - division - 1);
         }
         drop(builder);
 
@@ -1533,12 +1770,14 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
 // Layout is:
 // 1 byte: type
 // 1 byte: padding (padding to 16bits aligned)
-// 2 bytes: num_keys (number of keys)
+// 2 bytes: num_keys (number of keys). The high bit is set if subtree counts are stored
 // 4 byte: padding (padding to 64bits aligned)
 // repeating (num_keys + 1 times):
 // 16 bytes: child page checksum
 // repeating (num_keys + 1 times):
 // 8 bytes: page number
+// (optional) repeating (num_keys + 1 times):
+// * 8 bytes: subtree count. The high bit is set if the child is a leaf
 // (optional) repeating (num_keys times):
 // * 4 bytes: key end. Ending offset of the key, exclusive
 // repeating (num_keys times):
@@ -1546,6 +1785,7 @@ impl<'a, 'b> BranchBuilder<'a, 'b> {
 pub(super) struct RawBranchBuilder<'b> {
     page: &'b mut PageMut,
     fixed_key_size: Option<usize>,
+    counted: bool,
     num_keys: usize,
     keys_written: usize, // used for debugging
 }
@@ -1555,15 +1795,14 @@ impl<'b> RawBranchBuilder<'b> {
         num_keys: usize,
         size_of_keys: usize,
         fixed_key_size: Option<usize>,
+        counted: bool,
     ) -> usize {
         if fixed_key_size.is_none() {
-            let fixed_size = 8
-                + (PageNumber::serialized_size() + size_of::<Checksum>()) * (num_keys + 1)
-                + size_of::<u32>() * num_keys;
+            let fixed_size =
+                8 + branch_child_bytes(counted) * (num_keys + 1) + size_of::<u32>() * num_keys;
             size_of_keys + fixed_size
         } else {
-            let fixed_size =
-                8 + (PageNumber::serialized_size() + size_of::<Checksum>()) * (num_keys + 1);
+            let fixed_size = 8 + branch_child_bytes(counted) * (num_keys + 1);
             size_of_keys + fixed_size
         }
     }
@@ -1573,17 +1812,22 @@ impl<'b> RawBranchBuilder<'b> {
         page: &'b mut PageMut,
         num_keys: usize,
         fixed_key_size: Option<usize>,
+        counted: bool,
     ) -> Self {
         assert!(num_keys > 0);
+        let mut raw_num_keys = u16::try_from(num_keys).unwrap();
+        assert_eq!(raw_num_keys & COUNTED_BRANCH, 0);
+        if counted {
+            raw_num_keys |= COUNTED_BRANCH;
+        }
         page.memory_mut()[0] = BRANCH;
-        page.memory_mut()[2..4].copy_from_slice(&u16::try_from(num_keys).unwrap().to_le_bytes());
+        page.memory_mut()[2..4].copy_from_slice(&raw_num_keys.to_le_bytes());
         #[cfg(debug_assertions)]
         {
             // Poison all the child pointers & key offsets, in case the caller forgets to write them
             let start = 8 + size_of::<Checksum>() * (num_keys + 1);
-            let last = 8
-                + (PageNumber::serialized_size() + size_of::<Checksum>()) * (num_keys + 1)
-                + size_of::<u32>() * num_keys;
+            let last =
+                8 + branch_child_bytes(counted) * (num_keys + 1) + size_of::<u32>() * num_keys;
             for x in &mut page.memory_mut()[start..last] {
                 *x = 0xFF;
             }
@@ -1591,23 +1835,46 @@ impl<'b> RawBranchBuilder<'b> {
         RawBranchBuilder {
             page,
             fixed_key_size,
+            counted,
             num_keys,
             keys_written: 0,
         }
     }
 
-    pub(super) fn write_first_page(&mut self, page_number: PageNumber, checksum: Checksum) {
-        let offset = 8;
+    pub(super) fn write_first_page(
+        &mut self,
+        page_number: PageNumber,
+        checksum: Checksum,
+        count: SubtreeCount,
+    ) {
+        self.write_child(0, page_number, checksum, count);
+    }
+
+    fn write_child(
+        &mut self,
+        i: usize,
+        page_number: PageNumber,
+        checksum: Checksum,
+        count: SubtreeCount,
+    ) {
+        let offset = 8 + size_of::<Checksum>() * i;
         self.page.memory_mut()[offset..(offset + size_of::<Checksum>())]
             .copy_from_slice(&checksum.to_le_bytes());
-        let offset = 8 + size_of::<Checksum>() * (self.num_keys + 1);
+        let offset =
+            8 + size_of::<Checksum>() * (self.num_keys + 1) + PageNumber::serialized_size() * i;
         self.page.memory_mut()[offset..(offset + PageNumber::serialized_size())]
             .copy_from_slice(&page_number.to_le_bytes());
+        if self.counted {
+            let offset = 8
+                + (PageNumber::serialized_size() + size_of::<Checksum>()) * (self.num_keys + 1)
+                + size_of::<SubtreeCount>() * i;
+            self.page.memory_mut()[offset..(offset + size_of::<SubtreeCount>())]
+                .copy_from_slice(&count.to_le_bytes());
+        }
     }
 
     fn key_section_start(&self) -> usize {
-        let mut offset =
-            8 + (PageNumber::serialized_size() + size_of::<Checksum>()) * (self.num_keys + 1);
+        let mut offset = 8 + branch_child_bytes(self.counted) * (self.num_keys + 1);
         if self.fixed_key_size.is_none() {
             offset += size_of::<u32>() * self.num_keys;
         }
@@ -1619,9 +1886,8 @@ impl<'b> RawBranchBuilder<'b> {
         if let Some(fixed) = self.fixed_key_size {
             return self.key_section_start() + fixed * (n + 1);
         }
-        let offset = 8
-            + (PageNumber::serialized_size() + size_of::<Checksum>()) * (self.num_keys + 1)
-            + size_of::<u32>() * n;
+        let offset =
+            8 + branch_child_bytes(self.counted) * (self.num_keys + 1) + size_of::<u32>() * n;
         u32::from_le_bytes(
             self.page.memory()[offset..(offset + size_of::<u32>())]
                 .try_into()
@@ -1636,29 +1902,22 @@ impl<'b> RawBranchBuilder<'b> {
         key: &[u8],
         page_number: PageNumber,
         checksum: Checksum,
+        count: SubtreeCount,
         n: usize,
     ) {
         assert!(n < self.num_keys);
         assert_eq!(n, self.keys_written);
         self.keys_written += 1;
-        let offset = 8 + size_of::<Checksum>() * (n + 1);
-        self.page.memory_mut()[offset..(offset + size_of::<Checksum>())]
-            .copy_from_slice(&checksum.to_le_bytes());
-        let offset = 8
-            + size_of::<Checksum>() * (self.num_keys + 1)
-            + PageNumber::serialized_size() * (n + 1);
-        self.page.memory_mut()[offset..(offset + PageNumber::serialized_size())]
-            .copy_from_slice(&page_number.to_le_bytes());
+        self.write_child(n + 1, page_number, checksum, count);
 
         let data_offset = if n > 0 {
             self.key_end(n - 1)
         } else {
             self.key_section_start()
         };
+        let offset = 8 + branch_child_bytes(self.counted) * (self.num_keys + 1);
         if self.fixed_key_size.is_none() {
-            let offset = 8
-                + (PageNumber::serialized_size() + size_of::<Checksum>()) * (self.num_keys + 1)
-                + size_of::<u32>() * n;
+            let offset = offset + size_of::<u32>() * n;
             self.page.memory_mut()[offset..(offset + size_of::<u32>())].copy_from_slice(
                 &u32::try_from(data_offset + key.len())
                     .unwrap()
@@ -1666,7 +1925,7 @@ impl<'b> RawBranchBuilder<'b> {
             );
         }
 
-        debug_assert!(data_offset > offset);
+        debug_assert!(data_offset >= offset);
         self.page.memory_mut()[data_offset..(data_offset + key.len())].copy_from_slice(key);
     }
 }
@@ -1690,9 +1949,10 @@ impl<'b> BranchMutator<'b> {
     }
 
     fn num_keys(&self) -> usize {
-        u16::from_le_bytes(self.page.memory()[2..4].try_into().unwrap()) as usize
+        branch_num_keys(self.page.memory()).0
     }
 
+    // Replaces the child page. The child's subtree count is left unchanged
     pub(crate) fn write_child_page(
         &mut self,
         i: usize,
@@ -1708,4 +1968,18 @@ impl<'b> BranchMutator<'b> {
         self.page.memory_mut()[offset..(offset + PageNumber::serialized_size())]
             .copy_from_slice(&page_number.to_le_bytes());
     }
+
+    // Does nothing if the page does not store subtree counts
+    pub(crate) fn write_child_count(&mut self, i: usize, count: SubtreeCount) {
+        let (num_keys, counted) = branch_num_keys(self.page.memory());
+        debug_assert!(i <= num_keys);
+        if !counted {
+            return;
+        }
+        let offset = 8
+            + (PageNumber::serialized_size() + size_of::<Checksum>()) * (num_keys + 1)
+            + size_of::<SubtreeCount>() * i;
+        self.page.memory_mut()[offset..(offset + size_of::<SubtreeCount>())]
+            .copy_from_slice(&count.to_le_bytes());
+    }
 }
@@ -0,0 +1,204 @@
+use crate::Result;
+use crate::tree_store::btree_base::{
+    BranchBuilder, BtreeHeader, Checksum, DEFERRED, LeafBuilder, MAX_BRANCH_KEYS, RawBranchBuilder,
+    RawLeafBuilder, SubtreeCount,
+};
+use crate::tree_store::page_store::{Page, TransactionalMemory};
+use crate::tree_store::{PageNumber, PageTrackerPolicy};
+use crate::types::{Key, Value};
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+struct Child {
+    page_number: PageNumber,
+    checksum: Checksum,
+    count: SubtreeCount,
+    // The greatest key in the subtree
+    last_key: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Level {
+    children: Vec<Child>,
+    key_bytes: usize,
+    // A full branch which has not been written yet. It is held back so that the final branch of
+    // the level does not have only one child
+    full: Option<Vec<Child>>,
+}
+
+// Builds a btree bottom-up from entries which are pushed in strictly ascending key order. Leaves
+// and branches are filled to the page size, and each page is written exactly once
+pub(crate) struct BtreeBuilder<K: Key, V: Value> {
+    mem: Arc<TransactionalMemory>,
+    allocated: Arc<Mutex<PageTrackerPolicy>>,
+    pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    pair_bytes: usize,
+    // levels[0] contains the leaves, levels[1] the branches above them, and so on
+    levels: Vec<Level>,
+    length: u64,
+    _key_type: PhantomData<K>,
+    _value_type: PhantomData<V>,
+}
+
+impl<K: Key, V: Value> BtreeBuilder<K, V> {
+    pub(crate) fn new(
+        mem: Arc<TransactionalMemory>,
+        allocated: Arc<Mutex<PageTrackerPolicy>>,
+    ) -> Self {
+        Self {
+            mem,
+            allocated,
+            pairs: vec![],
+            pair_bytes: 0,
+            levels: vec![],
+            length: 0,
+            _key_type: Default::default(),
+            _value_type: Default::default(),
+        }
+    }
+
+    // The last key pushed, if any
+    pub(crate) fn last_key(&self) -> Option<&[u8]> {
+        self.pairs.last().map(|(key, _)| key.as_slice())
+    }
+
+    // The caller must ensure that `key` is greater than all previously pushed keys
+    pub(crate) fn push(&mut self, key: &[u8], value: &[u8]) -> Result {
+        let required = RawLeafBuilder::required_bytes(
+            self.pairs.len() + 1,
+            self.pair_bytes + key.len() + value.len(),
+            K::fixed_width(),
+            V::fixed_width(),
+        );
+        if !self.pairs.is_empty()
+            && (required > self.mem.get_page_size() || self.pairs.len() == u16::MAX.into())
+        {
+            self.write_leaf()?;
+        }
+        self.pair_bytes += key.len() + value.len();
+        self.pairs.push((key.to_vec(), value.to_vec()));
+        self.length += 1;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<Option<BtreeHeader>> {
+        if !self.pairs.is_empty() {
+            self.write_leaf()?;
+        }
+        let mut height = 0;
+        while height < self.levels.len() {
+            let top = height == self.levels.len() - 1;
+            let level = &mut self.levels[height];
+            if top && level.full.is_none() && level.children.len() == 1 {
+                // This is the root
+                let root = level.children.pop().unwrap();
+                return Ok(Some(BtreeHeader::new(
+                    root.page_number,
+                    root.checksum,
+                    self.length,
+                )));
+            }
+            let mut children = mem::take(&mut level.children);
+            if let Some(mut full) = level.full.take() {
+                if children.len() == 1 && full.len() > 2 {
+                    // The full branch has no room for another child, so the last branch takes
+                    // a child from it instead of having only one
+                    children.insert(0, full.pop().unwrap());
+                    self.write_branch(height, full)?;
+                    self.write_branch(height, children)?;
+                } else if children.len() < 2 {
+                    // Merge them into a branch larger than a page
+                    full.append(&mut children);
+                    self.write_branch(height, full)?;
+                } else {
+                    self.write_branch(height, full)?;
+                    self.write_branch(height, children)?;
+                }
+            } else if !children.is_empty() {
+                self.write_branch(height, children)?;
+            }
+            height += 1;
+        }
+
+        Ok(None)
+    }
+
+    fn write_leaf(&mut self) -> Result {
+        let mut builder = LeafBuilder::new(
+            &self.mem,
+            &self.allocated,
+            self.pairs.len(),
+            K::fixed_width(),
+            V::fixed_width(),
+        );
+        for (key, value) in &self.pairs {
+            builder.push(key, value);
+        }
+        let page_number = builder.build()?.get_page_number();
+        let count = SubtreeCount::leaf(self.pairs.len());
+        let (last_key, _) = self.pairs.pop().unwrap();
+        self.pairs.clear();
+        self.pair_bytes = 0;
+        self.push_child(
+            0,
+            Child {
+                page_number,
+                checksum: DEFERRED,
+                count,
+                last_key,
+            },
+        )
+    }
+
+    fn push_child(&mut self, height: usize, child: Child) -> Result {
+        if self.levels.len() == height {
+            self.levels.push(Level::default());
+        }
+        let level = &mut self.levels[height];
+        // Adding the child makes the key of the current last child a separator
+        let required = RawBranchBuilder::required_bytes(
+            level.children.len(),
+            level.key_bytes,
+            K::fixed_width(),
+            self.mem.stores_subtree_counts(),
+        );
+        if level.children.len() >= 2
+            && (required > self.mem.get_page_size() || level.children.len() >= MAX_BRANCH_KEYS)
+        {
+            let full = level.full.replace(mem::take(&mut level.children));
+            level.key_bytes = 0;
+            if let Some(full) = full {
+                self.write_branch(height, full)?;
+            }
+        }
+        let level = &mut self.levels[height];
+        level.key_bytes += child.last_key.len();
+        level.children.push(child);
+        Ok(())
+    }
+
+    // Writes a branch with the given children, and adds it to the level above `height`
+    fn write_branch(&mut self, height: usize, mut children: Vec<Child>) -> Result {
+        let mut builder =
+            BranchBuilder::new(&self.mem, &self.allocated, children.len(), K::fixed_width());
+        for (i, child) in children.iter().enumerate() {
+            if i > 0 {
+                builder.push_key(&children[i - 1].last_key);
+            }
+            builder.push_child(child.page_number, child.checksum, child.count);
+        }
+        let page_number = builder.build()?.get_page_number();
+        let count = SubtreeCount::sum(children.iter().map(|child| child.count));
+        let last_key = children.pop().unwrap().last_key;
+        self.push_child(
+            height + 1,
+            Child {
+                page_number,
+                checksum: DEFERRED,
+                count,
+                last_key,
+            },
+        )
+    }
+}
@@ -9,7 +9,7 @@ use crate::types::{Key, Value};
 use std::borrow::Borrow;
 use std::collections::Bound;
 use std::marker::PhantomData;
-use std::ops::{Range, RangeBounds};
+use std::ops::{Range, RangeBounds, RangeFull};
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
@@ -178,6 +178,128 @@ impl<K: Key, V: Value> EntryGuard<K, V> {
     }
 }
 
+impl<K: Key, V: Value> Clone for EntryGuard<K, V> {
+    fn clone(&self) -> Self {
+        Self::new(
+            self.page.clone(),
+            self.key_range.clone(),
+            self.value_range.clone(),
+        )
+    }
+}
+
+// A position in a btree, which can be moved in either direction. Moving in the same direction as
+// the previous move continues the underlying range iterator, otherwise the iterator is recreated
+// starting from the key of the current position
+pub(crate) struct BtreeCursor<K: Key + 'static, V: Value + 'static> {
+    // Serialized key of the current position. The entry with this key may no longer exist
+    position: Option<Vec<u8>>,
+    // The entry at the current position
+    current: Option<EntryGuard<K, V>>,
+    // Continues from the current position. The flag is true if it moves in reverse
+    iter: Option<(BtreeRangeIter<K, V>, bool)>,
+    manager: Arc<TransactionalMemory>,
+}
+
+impl<K: Key + 'static, V: Value + 'static> BtreeCursor<K, V> {
+    pub(crate) fn new(manager: Arc<TransactionalMemory>) -> Self {
+        Self {
+            position: None,
+            current: None,
+            iter: None,
+            manager,
+        }
+    }
+
+    pub(crate) fn current(&self) -> Option<EntryGuard<K, V>> {
+        self.current.clone()
+    }
+
+    pub(crate) fn position(&self) -> Option<&[u8]> {
+        self.position.as_deref()
+    }
+
+    // Drops all references to pages, so that the tree can be modified. The position is kept, but
+    // there is no current entry until the cursor is moved again
+    pub(crate) fn release(&mut self) {
+        self.current = None;
+        self.iter = None;
+    }
+
+    // Moves to the first entry whose key is greater than or equal to `key`. If there is none, the
+    // cursor is left positioned at `key` with no current entry
+    pub(crate) fn seek(
+        &mut self,
+        root: Option<PageNumber>,
+        key: &[u8],
+    ) -> Result<Option<EntryGuard<K, V>>> {
+        let range = (Bound::Included(K::from_bytes(key)), Bound::Unbounded);
+        let iter = BtreeRangeIter::new(&range, root, self.manager.clone())?;
+        self.position = Some(key.to_vec());
+        self.current = None;
+        self.iter = Some((iter, false));
+        self.advance(false)
+    }
+
+    // Moves to the first entry, or the last if `reverse` is true
+    pub(crate) fn seek_end(
+        &mut self,
+        root: Option<PageNumber>,
+        reverse: bool,
+    ) -> Result<Option<EntryGuard<K, V>>> {
+        let iter =
+            BtreeRangeIter::new::<RangeFull, K::SelfType<'_>>(&(..), root, self.manager.clone())?;
+        self.position = None;
+        self.current = None;
+        self.iter = Some((iter, reverse));
+        self.advance(reverse)
+    }
+
+    // Moves to the next entry, or the previous one if `reverse` is true. If there is none, the
+    // cursor does not move. An unpositioned cursor moves to the first, or last, entry
+    pub(crate) fn step(
+        &mut self,
+        root: Option<PageNumber>,
+        reverse: bool,
+    ) -> Result<Option<EntryGuard<K, V>>> {
+        if !matches!(self.iter, Some((_, iter_reverse)) if iter_reverse == reverse) {
+            let iter = if let Some(position) = &self.position {
+                let key = K::from_bytes(position);
+                let range = if reverse {
+                    (Bound::Unbounded, Bound::Excluded(key))
+                } else {
+                    (Bound::Excluded(key), Bound::Unbounded)
+                };
+                BtreeRangeIter::new(&range, root, self.manager.clone())?
+            } else {
+                BtreeRangeIter::new::<RangeFull, K::SelfType<'_>>(
+                    &(..),
+                    root,
+                    self.manager.clone(),
+                )?
+            };
+            self.iter = Some((iter, reverse));
+        }
+        self.advance(reverse)
+    }
+
+    fn advance(&mut self, reverse: bool) -> Result<Option<EntryGuard<K, V>>> {
+        let (iter, _) = self.iter.as_mut().unwrap();
+        let next = if reverse {
+            iter.next_back()
+        } else {
+            iter.next()
+        };
+        if let Some(entry) = next.transpose()? {
+            self.position = Some(entry.key_data());
+            self.current = Some(entry.clone());
+            Ok(Some(entry))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
 pub(crate) struct AllPageNumbersBtreeIter {
     next: Option<RangeIterState>,
     manager: Arc<TransactionalMemory>,
@@ -1,6 +1,6 @@
 use crate::tree_store::btree_base::{
     BRANCH, BranchAccessor, BranchBuilder, BranchMutator, Checksum, DEFERRED, LEAF, LeafAccessor,
-    LeafBuilder, LeafMutator,
+    LeafBuilder, LeafMutator, SubtreeCount, range_covers, range_intersects, subtree_count,
 };
 use crate::tree_store::btree_mutator::DeletionResult::{
     DeletedBranch, DeletedLeaf, PartialBranch, PartialLeaf, Subtree,
@@ -11,16 +11,20 @@ use crate::tree_store::{
     TransactionalMemory,
 };
 use crate::types::{Key, Value};
-use crate::{AccessGuard, Result};
+use crate::{AccessGuard, Result, StorageError};
 use std::cmp::{max, min};
 use std::marker::PhantomData;
+use std::ops::Bound;
 use std::sync::{Arc, Mutex};
 
+// Called with the key and value of each entry removed by a range removal
+pub(crate) type RangeRemovalCallback<'c> = dyn FnMut(&[u8], &[u8]) -> Result + 'c;
+
 // TODO: it seems like Checksum can be removed from most/all of these, now that we're using deferred checksums
 #[derive(Debug)]
 enum DeletionResult {
     // A proper subtree
-    Subtree(PageNumber, Checksum),
+    Subtree(PageNumber, Checksum, SubtreeCount),
     // A leaf with zero children
     DeletedLeaf,
     // A leaf with fewer entries than desired
@@ -31,7 +35,7 @@ enum DeletionResult {
     // A branch page subtree with fewer children than desired
     PartialBranch(PageNumber, Checksum),
     // Indicates that the branch node was deleted, and includes the only remaining child
-    DeletedBranch(PageNumber, Checksum),
+    DeletedBranch(PageNumber, Checksum, SubtreeCount),
 }
 
 struct InsertionResult<'a, V: Value + 'static> {
@@ -39,14 +43,26 @@ struct InsertionResult<'a, V: Value + 'static> {
     new_root: PageNumber,
     // checksum of the root page
     root_checksum: Checksum,
+    // number of entries below the root page
+    root_count: SubtreeCount,
     // Following sibling, if the root had to be split
-    additional_sibling: Option<(Vec<u8>, PageNumber, Checksum)>,
+    additional_sibling: Option<(Vec<u8>, PageNumber, Checksum, SubtreeCount)>,
     // The inserted value for .insert_reserve() to use
     inserted_value: AccessGuardMutInPlace<'a, V>,
     // The previous value, if any
     old_value: Option<AccessGuard<'a, V>>,
 }
 
+// Computes the new value of an entry from its existing value, if any
+pub(crate) type ComputeValue<'c> = dyn FnMut(Option<&[u8]>) -> Result<Option<Vec<u8>>> + 'c;
+
+// The value to insert, or a function which computes it from the existing value, if any. If the
+// function returns `None`, the tree is not modified
+pub(crate) enum InsertValue<'v> {
+    Bytes(&'v [u8]),
+    Computed(&'v mut ComputeValue<'v>),
+}
+
 pub(crate) struct MutateHelper<'a, 'b, K: Key, V: Value> {
     root: &'b mut Option<BtreeHeader>,
     modify_uncommitted: bool,
@@ -119,7 +135,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                 self.delete_helper(self.mem.get_page(p)?, checksum, K::as_bytes(key).as_ref())?;
             let new_length = if found.is_some() { length - 1 } else { length };
             let new_root = match deletion_result {
-                Subtree(page, checksum) => Some(BtreeHeader::new(page, checksum, new_length)),
+                Subtree(page, checksum, _) => Some(BtreeHeader::new(page, checksum, new_length)),
                 DeletedLeaf => None,
                 PartialLeaf { page, deleted_pair } => {
                     let accessor = LeafAccessor::new(&page, K::fixed_width(), V::fixed_width());
@@ -142,7 +158,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                 PartialBranch(page_number, checksum) => {
                     Some(BtreeHeader::new(page_number, checksum, new_length))
                 }
-                DeletedBranch(remaining_child, checksum) => {
+                DeletedBranch(remaining_child, checksum, _) => {
                     Some(BtreeHeader::new(remaining_child, checksum, new_length))
                 }
             };
@@ -153,24 +169,206 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
         }
     }
 
+    // Frees the subtrees which lie entirely within the range from `start` to `end`. Their entries
+    // are counted using the subtree counts of their parents, so their leaves are only read if
+    // `on_removed` is provided, or the counts are not stored. Every branch keeps at least two
+    // children, so entries in the range may remain and must then be deleted individually.
+    // Returns the number of entries removed
+    pub(crate) fn prune_range(
+        &mut self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        on_removed: &mut Option<&mut RangeRemovalCallback>,
+    ) -> Result<u64> {
+        let Some(BtreeHeader { root, length, .. }) = *self.root else {
+            return Ok(0);
+        };
+        if matches!((start, end), (Bound::Unbounded, Bound::Unbounded)) {
+            self.free_subtree(root, SubtreeCount::UNKNOWN, on_removed)?;
+            *self.root = None;
+            return Ok(length);
+        }
+        let page = self.mem.get_page(root)?;
+        if let Some((new_root, _, removed)) =
+            self.prune_range_helper(page, None, None, start, end, on_removed)?
+        {
+            let Some(new_length) = length.checked_sub(removed) else {
+                return Err(StorageError::Corrupted(format!(
+                    "Table length is {length}, but {removed} entries were removed",
+                )));
+            };
+            *self.root = Some(BtreeHeader::new(new_root, DEFERRED, new_length));
+            Ok(removed)
+        } else {
+            Ok(0)
+        }
+    }
+
+    // All keys in `page` are greater than `lower` and less than or equal to `upper`, if they are
+    // provided. Returns the replacement for `page` and its count, if any subtrees were freed
+    fn prune_range_helper(
+        &mut self,
+        page: PageImpl,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        on_removed: &mut Option<&mut RangeRemovalCallback>,
+    ) -> Result<Option<(PageNumber, SubtreeCount, u64)>> {
+        if page.memory()[0] != BRANCH {
+            return Ok(None);
+        }
+        let original_page_number = page.get_page_number();
+        let accessor = BranchAccessor::new(&page, K::fixed_width());
+        let num_children = accessor.count_children();
+        let child_bounds = |i: usize| {
+            let child_lower = if i == 0 { lower } else { accessor.key(i - 1) };
+            let child_upper = if i == num_children - 1 {
+                upper
+            } else {
+                accessor.key(i)
+            };
+            (child_lower, child_upper)
+        };
+        let covered: Vec<bool> = (0..num_children)
+            .map(|i| {
+                let (child_lower, child_upper) = child_bounds(i);
+                range_covers::<K>(child_lower, child_upper, start, end)
+            })
+            .collect();
+        // Keep enough of the covered children that the branch still has two
+        let uncovered = covered.iter().filter(|x| !**x).count();
+        let mut covered_to_keep = 2usize.saturating_sub(uncovered);
+
+        let mut removed = 0;
+        let mut kept = vec![];
+        for (i, &child_covered) in covered.iter().enumerate() {
+            let child = accessor.child_page(i).unwrap();
+            let child_checksum = accessor.child_checksum(i).unwrap();
+            let child_count = accessor.child_count(i).unwrap();
+            if child_covered {
+                if covered_to_keep == 0 {
+                    removed += self.free_subtree(child, child_count, on_removed)?;
+                    continue;
+                }
+                covered_to_keep -= 1;
+            }
+            let (child_lower, child_upper) = child_bounds(i);
+            if range_intersects::<K>(child_lower, child_upper, start, end) {
+                if let Some((new_child, new_child_count, child_removed)) = self.prune_range_helper(
+                    self.mem.get_page(child)?,
+                    child_lower,
+                    child_upper,
+                    start,
+                    end,
+                    on_removed,
+                )? {
+                    removed += child_removed;
+                    kept.push((i, new_child, DEFERRED, new_child_count));
+                    continue;
+                }
+            }
+            kept.push((i, child, child_checksum, child_count));
+        }
+        if removed == 0 {
+            return Ok(None);
+        }
+
+        let mut builder =
+            BranchBuilder::new(&self.mem, &self.allocated, kept.len(), K::fixed_width());
+        for (j, (i, child, checksum, count)) in kept.iter().enumerate() {
+            if j > 0 {
+                // All keys in the freed children between the previous kept child and this one are
+                // gone, so the key which preceded this child still separates the two
+                builder.push_key(accessor.key(i - 1).unwrap());
+            }
+            builder.push_child(*child, *checksum, *count);
+        }
+        let new_page = builder.build()?;
+        let new_count = subtree_count(&new_page);
+        drop(page);
+        self.conditional_free(original_page_number);
+
+        Ok(Some((new_page.get_page_number(), new_count, removed)))
+    }
+
+    // Frees the subtree rooted at `page_number`, whose count is `count`, and returns the number of
+    // entries it contained. Leaves are freed without being read, unless `on_removed` is provided or
+    // their count is unknown
+    fn free_subtree(
+        &mut self,
+        page_number: PageNumber,
+        count: SubtreeCount,
+        on_removed: &mut Option<&mut RangeRemovalCallback>,
+    ) -> Result<u64> {
+        if on_removed.is_none() && count.is_leaf() {
+            self.conditional_free(page_number);
+            return Ok(count.entries().unwrap());
+        }
+        let page = self.mem.get_page(page_number)?;
+        let removed = match page.memory()[0] {
+            LEAF => {
+                let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
+                if let Some(callback) = on_removed {
+                    for i in 0..accessor.num_pairs() {
+                        let entry = accessor.entry(i).unwrap();
+                        callback(entry.key(), entry.value())?;
+                    }
+                }
+                accessor.num_pairs() as u64
+            }
+            BRANCH => {
+                let accessor = BranchAccessor::new(&page, K::fixed_width());
+                let mut removed = 0;
+                for i in 0..accessor.count_children() {
+                    removed += self.free_subtree(
+                        accessor.child_page(i).unwrap(),
+                        accessor.child_count(i).unwrap(),
+                        on_removed,
+                    )?;
+                }
+                removed
+            }
+            _ => unreachable!(),
+        };
+        drop(page);
+        self.conditional_free(page_number);
+        Ok(removed)
+    }
+
     #[allow(clippy::type_complexity)]
     pub(crate) fn insert(
         &mut self,
         key: &K::SelfType<'_>,
         value: &V::SelfType<'_>,
     ) -> Result<(Option<AccessGuard<'a, V>>, AccessGuardMutInPlace<'a, V>)> {
+        let value = V::as_bytes(value);
+        let result = self.insert_with(key, &mut InsertValue::Bytes(value.as_ref()))?;
+        Ok(result.unwrap())
+    }
+
+    // Returns `None` if the value was computed, and the function returned `None`
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn insert_with(
+        &mut self,
+        key: &K::SelfType<'_>,
+        value: &mut InsertValue<'_>,
+    ) -> Result<Option<(Option<AccessGuard<'a, V>>, AccessGuardMutInPlace<'a, V>)>> {
         let (new_root, old_value, guard) = if let Some(BtreeHeader {
             root: p,
             checksum,
             length,
         }) = *self.root
         {
-            let result = self.insert_helper(
+            let Some(result) = self.insert_helper(
                 self.mem.get_page(p)?,
                 checksum,
                 K::as_bytes(key).as_ref(),
-                V::as_bytes(value).as_ref(),
-            )?;
+                value,
+            )?
+            else {
+                return Ok(None);
+            };
 
             let new_length = if result.old_value.is_some() {
                 length
@@ -178,12 +376,14 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                 length + 1
             };
 
-            let new_root = if let Some((key, page2, page2_checksum)) = result.additional_sibling {
+            let new_root = if let Some((key, page2, page2_checksum, page2_count)) =
+                result.additional_sibling
+            {
                 let mut builder =
                     BranchBuilder::new(&self.mem, &self.allocated, 2, K::fixed_width());
-                builder.push_child(result.new_root, result.root_checksum);
+                builder.push_child(result.new_root, result.root_checksum, result.root_count);
                 builder.push_key(&key);
-                builder.push_child(page2, page2_checksum);
+                builder.push_child(page2, page2_checksum, page2_count);
                 let new_page = builder.build()?;
                 BtreeHeader::new(new_page.get_page_number(), DEFERRED, new_length)
             } else {
@@ -191,10 +391,19 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
             };
             (new_root, result.old_value, result.inserted_value)
         } else {
+            let computed;
+            let value_bytes = match value {
+                InsertValue::Bytes(value) => *value,
+                InsertValue::Computed(f) => {
+                    computed = f(None)?;
+                    let Some(value) = &computed else {
+                        return Ok(None);
+                    };
+                    value.as_slice()
+                }
+            };
             let key_bytes = K::as_bytes(key);
-            let value_bytes = V::as_bytes(value);
             let key_bytes = key_bytes.as_ref();
-            let value_bytes = value_bytes.as_ref();
             let mut builder = LeafBuilder::new(
                 &self.mem,
                 &self.allocated,
@@ -213,7 +422,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
             (BtreeHeader::new(page_num, DEFERRED, 1), None, guard)
         };
         *self.root = Some(new_root);
-        Ok((old_value, guard))
+        Ok(Some((old_value, guard)))
     }
 
     fn insert_helper(
@@ -221,13 +430,24 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
         page: PageImpl,
         page_checksum: Checksum,
         key: &[u8],
-        value: &[u8],
-    ) -> Result<InsertionResult<'a, V>> {
+        value: &mut InsertValue<'_>,
+    ) -> Result<Option<InsertionResult<'a, V>>> {
         let node_mem = page.memory();
-        Ok(match node_mem[0] {
+        Ok(Some(match node_mem[0] {
             LEAF => {
                 let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
                 let (position, found) = accessor.position::<K>(key);
+                let computed;
+                let value = match value {
+                    InsertValue::Bytes(value) => *value,
+                    InsertValue::Computed(f) => {
+                        computed = f(found.then(|| accessor.entry(position).unwrap().value()))?;
+                        let Some(value) = &computed else {
+                            return Ok(None);
+                        };
+                        value.as_slice()
+                    }
+                };
 
                 // Fast-path to avoid re-building and splitting pages with a single large value
                 let single_large_value = accessor.num_pairs() == 1
@@ -247,27 +467,36 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                         LeafAccessor::new(new_page.memory(), K::fixed_width(), V::fixed_width());
                     let offset = new_page_accessor.offset_of_first_value();
                     let guard = AccessGuardMutInPlace::new(new_page, offset, value.len());
+                    let page_count = SubtreeCount::leaf(accessor.num_pairs());
                     return if position == 0 {
-                        Ok(InsertionResult {
+                        Ok(Some(InsertionResult {
                             new_root: new_page_number,
                             root_checksum: DEFERRED,
+                            root_count: SubtreeCount::leaf(1),
                             additional_sibling: Some((
                                 key.to_vec(),
                                 page.get_page_number(),
                                 page_checksum,
+                                page_count,
                             )),
                             inserted_value: guard,
                             old_value: None,
-                        })
+                        }))
                     } else {
                         let split_key = accessor.last_entry().key().to_vec();
-                        Ok(InsertionResult {
+                        Ok(Some(InsertionResult {
                             new_root: page.get_page_number(),
                             root_checksum: page_checksum,
-                            additional_sibling: Some((split_key, new_page_number, DEFERRED)),
+                            root_count: page_count,
+                            additional_sibling: Some((
+                                split_key,
+                                new_page_number,
+                                DEFERRED,
+                                SubtreeCount::leaf(1),
+                            )),
                             inserted_value: guard,
                             old_value: None,
-                        })
+                        }))
                     };
                 }
 
@@ -299,14 +528,16 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                     let new_page_accessor =
                         LeafAccessor::new(page_mut.memory(), K::fixed_width(), V::fixed_width());
                     let offset = new_page_accessor.offset_of_value(position).unwrap();
+                    let count = SubtreeCount::leaf(new_page_accessor.num_pairs());
                     let guard = AccessGuardMutInPlace::new(page_mut, offset, value.len());
-                    return Ok(InsertionResult {
+                    return Ok(Some(InsertionResult {
                         new_root: page_number,
                         root_checksum: DEFERRED,
+                        root_count: count,
                         additional_sibling: None,
                         inserted_value: guard,
                         old_value: existing_value,
-                    });
+                    }));
                 }
 
                 let mut builder = LeafBuilder::new(
@@ -354,11 +585,13 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                     let accessor =
                         LeafAccessor::new(new_page.memory(), K::fixed_width(), V::fixed_width());
                     let offset = accessor.offset_of_value(position).unwrap();
+                    let count = SubtreeCount::leaf(accessor.num_pairs());
                     let guard = AccessGuardMutInPlace::new(new_page, offset, value.len());
 
                     InsertionResult {
                         new_root: new_page_number,
                         root_checksum: DEFERRED,
+                        root_count: count,
                         additional_sibling: None,
                         inserted_value: guard,
                         old_value: existing_value,
@@ -390,6 +623,8 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                     let accessor =
                         LeafAccessor::new(new_page1.memory(), K::fixed_width(), V::fixed_width());
                     let division = accessor.num_pairs();
+                    let count1 = SubtreeCount::leaf(division);
+                    let count2 = subtree_count(&new_page2);
                     let guard = if position < division {
                         let accessor = LeafAccessor::new(
                             new_page1.memory(),
@@ -411,7 +646,8 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                     InsertionResult {
                         new_root: new_page_number,
                         root_checksum: DEFERRED,
-                        additional_sibling: Some((split_key, new_page_number2, DEFERRED)),
+                        root_count: count1,
+                        additional_sibling: Some((split_key, new_page_number2, DEFERRED, count2)),
                         inserted_value: guard,
                         old_value: existing_value,
                     }
@@ -421,8 +657,11 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                 let accessor = BranchAccessor::new(&page, K::fixed_width());
                 let (child_index, child_page) = accessor.child_for_key::<K>(key);
                 let child_checksum = accessor.child_checksum(child_index).unwrap();
-                let sub_result =
-                    self.insert_helper(self.mem.get_page(child_page)?, child_checksum, key, value)?;
+                let Some(sub_result) =
+                    self.insert_helper(self.mem.get_page(child_page)?, child_checksum, key, value)?
+                else {
+                    return Ok(None);
+                };
 
                 if sub_result.additional_sibling.is_none()
                     && self.modify_uncommitted
@@ -437,13 +676,15 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                         sub_result.new_root,
                         sub_result.root_checksum,
                     );
-                    return Ok(InsertionResult {
+                    mutator.write_child_count(child_index, sub_result.root_count);
+                    return Ok(Some(InsertionResult {
                         new_root: mutpage.get_page_number(),
                         root_checksum: DEFERRED,
+                        root_count: subtree_count(&mutpage),
                         additional_sibling: None,
                         inserted_value: sub_result.inserted_value,
                         old_value: sub_result.old_value,
-                    });
+                    }));
                 }
 
                 // A child was added, or we couldn't use the fast-path above
@@ -454,34 +695,44 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                     K::fixed_width(),
                 );
                 if child_index == 0 {
-                    builder.push_child(sub_result.new_root, sub_result.root_checksum);
-                    if let Some((ref index_key2, page2, page2_checksum)) =
+                    builder.push_child(
+                        sub_result.new_root,
+                        sub_result.root_checksum,
+                        sub_result.root_count,
+                    );
+                    if let Some((ref index_key2, page2, page2_checksum, page2_count)) =
                         sub_result.additional_sibling
                     {
                         builder.push_key(index_key2);
-                        builder.push_child(page2, page2_checksum);
+                        builder.push_child(page2, page2_checksum, page2_count);
                     }
                 } else {
                     builder.push_child(
                         accessor.child_page(0).unwrap(),
                         accessor.child_checksum(0).unwrap(),
+                        accessor.child_count(0).unwrap(),
                     );
                 }
                 for i in 1..accessor.count_children() {
                     if let Some(key) = accessor.key(i - 1) {
                         builder.push_key(key);
                         if i == child_index {
-                            builder.push_child(sub_result.new_root, sub_result.root_checksum);
-                            if let Some((ref index_key2, page2, page2_checksum)) =
+                            builder.push_child(
+                                sub_result.new_root,
+                                sub_result.root_checksum,
+                                sub_result.root_count,
+                            );
+                            if let Some((ref index_key2, page2, page2_checksum, page2_count)) =
                                 sub_result.additional_sibling
                             {
                                 builder.push_key(index_key2);
-                                builder.push_child(page2, page2_checksum);
+                                builder.push_child(page2, page2_checksum, page2_count);
                             }
                         } else {
                             builder.push_child(
                                 accessor.child_page(i).unwrap(),
                                 accessor.child_checksum(i).unwrap(),
+                                accessor.child_count(i).unwrap(),
                             );
                         }
                     } else {
@@ -494,10 +745,12 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                     InsertionResult {
                         new_root: new_page1.get_page_number(),
                         root_checksum: DEFERRED,
+                        root_count: subtree_count(&new_page1),
                         additional_sibling: Some((
                             split_key.to_vec(),
                             new_page2.get_page_number(),
                             DEFERRED,
+                            subtree_count(&new_page2),
                         )),
                         inserted_value: sub_result.inserted_value,
                         old_value: sub_result.old_value,
@@ -507,6 +760,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                     InsertionResult {
                         new_root: new_page.get_page_number(),
                         root_checksum: DEFERRED,
+                        root_count: subtree_count(&new_page),
                         additional_sibling: None,
                         inserted_value: sub_result.inserted_value,
                         old_value: sub_result.old_value,
@@ -520,7 +774,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                 result
             }
             _ => unreachable!(),
-        })
+        }))
     }
 
     pub(crate) fn insert_inplace(
@@ -575,7 +829,8 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
         let accessor = LeafAccessor::new(page.memory(), K::fixed_width(), V::fixed_width());
         let (position, found) = accessor.position::<K>(key);
         if !found {
-            return Ok((Subtree(page.get_page_number(), checksum), None));
+            let count = SubtreeCount::leaf(accessor.num_pairs());
+            return Ok((Subtree(page.get_page_number(), checksum, count), None));
         }
         let new_kv_bytes = accessor.length_of_pairs(0, accessor.num_pairs())
             - accessor.length_of_pairs(position, position + 1);
@@ -594,6 +849,8 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
             && accessor.num_pairs() > 1
         {
             let (start, end) = accessor.value_range(position).unwrap();
+            // The entry is removed when the guard is dropped
+            let count = SubtreeCount::leaf(accessor.num_pairs() - 1);
             let page_number = page.get_page_number();
             drop(page);
             let page_mut = self.mem.get_page_mut(page_number)?;
@@ -605,7 +862,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                 position,
                 K::fixed_width(),
             );
-            return Ok((Subtree(page_number, DEFERRED), Some(guard)));
+            return Ok((Subtree(page_number, DEFERRED, count), Some(guard)));
         }
 
         let result = if accessor.num_pairs() == 1 {
@@ -633,7 +890,11 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                 builder.push(entry.key(), entry.value());
             }
             let new_page = builder.build()?;
-            Subtree(new_page.get_page_number(), DEFERRED)
+            Subtree(
+                new_page.get_page_number(),
+                DEFERRED,
+                SubtreeCount::leaf(accessor.num_pairs() - 1),
+            )
         };
         let (start, end) = accessor.value_range(position).unwrap();
         let guard = if uncommitted && self.modify_uncommitted {
@@ -656,8 +917,8 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
         builder: BranchBuilder<'_, '_>,
         page_size: usize,
     ) -> Result<DeletionResult> {
-        let result = if let Some((only_child, checksum)) = builder.to_single_child() {
-            DeletedBranch(only_child, checksum)
+        let result = if let Some((only_child, checksum, count)) = builder.to_single_child() {
+            DeletedBranch(only_child, checksum, count)
         } else {
             // TODO: can we optimize away this page allocation?
             // The PartialInternal gets returned, and then the caller has to merge it immediately
@@ -668,7 +929,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
             if accessor.total_length() < page_size / 3 {
                 PartialBranch(new_page.get_page_number(), DEFERRED)
             } else {
-                Subtree(new_page.get_page_number(), DEFERRED)
+                Subtree(new_page.get_page_number(), DEFERRED, accessor.total_count())
             }
         };
         Ok(result)
@@ -687,30 +948,33 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
         let (result, found) =
             self.delete_helper(self.mem.get_page(child_page_number)?, child_checksum, key)?;
         if found.is_none() {
-            return Ok((Subtree(original_page_number, checksum), None));
+            let count = accessor.total_count();
+            return Ok((Subtree(original_page_number, checksum, count), None));
         }
-        if let Subtree(new_child, new_child_checksum) = result {
-            let result_page =
-                if self.mem.uncommitted(original_page_number) && self.modify_uncommitted {
-                    drop(page);
-                    let mut mutpage = self.mem.get_page_mut(original_page_number)?;
-                    let mut mutator = BranchMutator::new(&mut mutpage);
-                    mutator.write_child_page(child_index, new_child, new_child_checksum);
-                    original_page_number
-                } else {
-                    let mut builder = BranchBuilder::new(
-                        &self.mem,
-                        &self.allocated,
-                        accessor.count_children(),
-                        K::fixed_width(),
-                    );
-                    builder.push_all(&accessor);
-                    builder.replace_child(child_index, new_child, new_child_checksum);
-                    let new_page = builder.build()?;
-                    self.conditional_free(original_page_number);
-                    new_page.get_page_number()
-                };
-            return Ok((Subtree(result_page, DEFERRED), found));
+        if let Subtree(new_child, new_child_checksum, new_child_count) = result {
+            let (result_page, result_count) = if self.mem.uncommitted(original_page_number)
+                && self.modify_uncommitted
+            {
+                drop(page);
+                let mut mutpage = self.mem.get_page_mut(original_page_number)?;
+                let mut mutator = BranchMutator::new(&mut mutpage);
+                mutator.write_child_page(child_index, new_child, new_child_checksum);
+                mutator.write_child_count(child_index, new_child_count);
+                (original_page_number, subtree_count(&mutpage))
+            } else {
+                let mut builder = BranchBuilder::new(
+                    &self.mem,
+                    &self.allocated,
+                    accessor.count_children(),
+                    K::fixed_width(),
+                );
+                builder.push_all(&accessor);
+                builder.replace_child(child_index, new_child, new_child_checksum, new_child_count);
+                let new_page = builder.build()?;
+                self.conditional_free(original_page_number);
+                (new_page.get_page_number(), subtree_count(&new_page))
+            };
+            return Ok((Subtree(result_page, DEFERRED, result_count), found));
         }
 
         // Child is requesting to be merged with a sibling
@@ -722,7 +986,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
         );
 
         let final_result = match result {
-            Subtree(_, _) => {
+            Subtree(..) => {
                 // Handled in the if above
                 unreachable!();
             }
@@ -734,6 +998,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                     builder.push_child(
                         accessor.child_page(i).unwrap(),
                         accessor.child_checksum(i).unwrap(),
+                        accessor.child_count(i).unwrap(),
                     );
                 }
                 let end = if child_index == accessor.count_children() - 1 {
@@ -780,7 +1045,12 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                     child_builder.push_all_except(&partial_child_accessor, Some(deleted_pair));
                     let new_page = child_builder.build()?;
                     builder.push_all(&accessor);
-                    builder.replace_child(child_index, new_page.get_page_number(), DEFERRED);
+                    builder.replace_child(
+                        child_index,
+                        new_page.get_page_number(),
+                        DEFERRED,
+                        subtree_count(&new_page),
+                    );
 
                     let result = Self::finalize_branch_builder(builder, self.mem.get_page_size())?;
 
@@ -819,11 +1089,23 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                         if child_builder.should_split() {
                             let (new_page1, split_key, new_page2) = child_builder.build_split()?;
                             builder.push_key(split_key);
-                            builder.push_child(new_page1.get_page_number(), DEFERRED);
-                            builder.push_child(new_page2.get_page_number(), DEFERRED);
+                            builder.push_child(
+                                new_page1.get_page_number(),
+                                DEFERRED,
+                                subtree_count(&new_page1),
+                            );
+                            builder.push_child(
+                                new_page2.get_page_number(),
+                                DEFERRED,
+                                subtree_count(&new_page2),
+                            );
                         } else {
                             let new_page = child_builder.build()?;
-                            builder.push_child(new_page.get_page_number(), DEFERRED);
+                            builder.push_child(
+                                new_page.get_page_number(),
+                                DEFERRED,
+                                subtree_count(&new_page),
+                            );
                         }
 
                         let merged_key_index = max(child_index, merge_with);
@@ -831,7 +1113,11 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                             builder.push_key(accessor.key(merged_key_index).unwrap());
                         }
                     } else {
-                        builder.push_child(page_number, page_checksum);
+                        builder.push_child(
+                            page_number,
+                            page_checksum,
+                            accessor.child_count(i).unwrap(),
+                        );
                         if i < accessor.count_children() - 1 {
                             builder.push_key(accessor.key(i).unwrap());
                         }
@@ -848,7 +1134,7 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
 
                 result
             }
-            DeletedBranch(only_grandchild, grandchild_checksum) => {
+            DeletedBranch(only_grandchild, grandchild_checksum, grandchild_count) => {
                 let merge_with = if child_index == 0 { 1 } else { child_index - 1 };
                 let merge_with_page = self
                     .mem
@@ -870,22 +1156,42 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                         );
                         let separator_key = accessor.key(min(child_index, merge_with)).unwrap();
                         if child_index < merge_with {
-                            child_builder.push_child(only_grandchild, grandchild_checksum);
+                            child_builder.push_child(
+                                only_grandchild,
+                                grandchild_checksum,
+                                grandchild_count,
+                            );
                             child_builder.push_key(separator_key);
                         }
                         child_builder.push_all(&merge_with_accessor);
                         if child_index > merge_with {
                             child_builder.push_key(separator_key);
-                            child_builder.push_child(only_grandchild, grandchild_checksum);
+                            child_builder.push_child(
+                                only_grandchild,
+                                grandchild_checksum,
+                                grandchild_count,
+                            );
                         }
                         if child_builder.should_split() {
                             let (new_page1, separator, new_page2) = child_builder.build_split()?;
-                            builder.push_child(new_page1.get_page_number(), DEFERRED);
+                            builder.push_child(
+                                new_page1.get_page_number(),
+                                DEFERRED,
+                                subtree_count(&new_page1),
+                            );
                             builder.push_key(separator);
-                            builder.push_child(new_page2.get_page_number(), DEFERRED);
+                            builder.push_child(
+                                new_page2.get_page_number(),
+                                DEFERRED,
+                                subtree_count(&new_page2),
+                            );
                         } else {
                             let new_page = child_builder.build()?;
-                            builder.push_child(new_page.get_page_number(), DEFERRED);
+                            builder.push_child(
+                                new_page.get_page_number(),
+                                DEFERRED,
+                                subtree_count(&new_page),
+                            );
                         }
 
                         let merged_key_index = max(child_index, merge_with);
@@ -893,7 +1199,11 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                             builder.push_key(accessor.key(merged_key_index).unwrap());
                         }
                     } else {
-                        builder.push_child(page_number, page_checksum);
+                        builder.push_child(
+                            page_number,
+                            page_checksum,
+                            accessor.child_count(i).unwrap(),
+                        );
                         if i < accessor.count_children() - 1 {
                             builder.push_key(accessor.key(i).unwrap());
                         }
@@ -943,12 +1253,24 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                         }
                         if child_builder.should_split() {
                             let (new_page1, separator, new_page2) = child_builder.build_split()?;
-                            builder.push_child(new_page1.get_page_number(), DEFERRED);
+                            builder.push_child(
+                                new_page1.get_page_number(),
+                                DEFERRED,
+                                subtree_count(&new_page1),
+                            );
                             builder.push_key(separator);
-                            builder.push_child(new_page2.get_page_number(), DEFERRED);
+                            builder.push_child(
+                                new_page2.get_page_number(),
+                                DEFERRED,
+                                subtree_count(&new_page2),
+                            );
                         } else {
                             let new_page = child_builder.build()?;
-                            builder.push_child(new_page.get_page_number(), DEFERRED);
+                            builder.push_child(
+                                new_page.get_page_number(),
+                                DEFERRED,
+                                subtree_count(&new_page),
+                            );
                         }
 
                         let merged_key_index = max(child_index, merge_with);
@@ -956,7 +1278,11 @@ impl<'a, 'b, K: Key, V: Value> MutateHelper<'a, 'b, K, V> {
                             builder.push_key(accessor.key(merged_key_index).unwrap());
                         }
                     } else {
-                        builder.push_child(page_number, page_checksum);
+                        builder.push_child(
+                            page_number,
+                            page_checksum,
+                            accessor.child_count(i).unwrap(),
+                        );
                         if i < accessor.count_children() - 1 {
                             builder.push_key(accessor.key(i).unwrap());
                         }
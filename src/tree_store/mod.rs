@@ -1,5 +1,6 @@
 mod btree;
 mod btree_base;
+mod btree_builder;
 mod btree_iters;
 mod btree_mutator;
 mod page_store;
@@ -7,18 +8,21 @@ mod table_tree;
 mod table_tree_base;
 
 pub(crate) use btree::{
-    Btree, BtreeMut, BtreeStats, PagePath, RawBtree, UntypedBtree, UntypedBtreeMut, btree_stats,
+    Btree, BtreeMut, BtreeStats, PagePath, PageSource, RawBtree, UntypedBtree, UntypedBtreeMut,
+    btree_stats, copy_btree, visit_pages_with_checksums,
 };
 pub use btree_base::{AccessGuard, AccessGuardMut, AccessGuardMutInPlace};
 pub(crate) use btree_base::{
     BRANCH, BranchAccessor, BranchMutator, BtreeHeader, Checksum, DEFERRED, LEAF, LeafAccessor,
-    LeafMutator, RawLeafBuilder,
+    LeafMutator, RawLeafBuilder, SubtreeCount, copy_page, subtree_count,
+};
+pub(crate) use btree_iters::{
+    AllPageNumbersBtreeIter, BtreeCursor, BtreeExtractIf, BtreeRangeIter, EntryGuard,
 };
-pub(crate) use btree_iters::{AllPageNumbersBtreeIter, BtreeExtractIf, BtreeRangeIter};
 pub(crate) use page_store::{
-    FILE_FORMAT_VERSION3, MAX_PAIR_LENGTH, MAX_VALUE_LENGTH, PAGE_SIZE, Page, PageHint, PageNumber,
-    PageTrackerPolicy, SerializedSavepoint, TransactionalMemory,
+    FILE_FORMAT_VERSION3, MAX_PAIR_LENGTH, MAX_VALUE_LENGTH, PAGE_SIZE, Page, PageHint, PageImpl,
+    PageNumber, PageTrackerPolicy, SerializedSavepoint, TransactionalMemory,
 };
-pub use page_store::{InMemoryBackend, Savepoint, file_backend};
+pub use page_store::{InMemoryBackend, InMemoryImage, Savepoint, file_backend};
 pub(crate) use table_tree::{PageListMut, TableTree, TableTreeMut};
 pub(crate) use table_tree_base::{InternalTableDefinition, TableType};
@@ -1,4 +1,6 @@
 use crate::tree_store::page_store::cached_file::WritablePage;
+#[cfg(target_os = "linux")]
+use crate::tree_store::page_store::memory_map::MappedPage;
 use crate::tree_store::page_store::page_manager::MAX_MAX_PAGE_ORDER;
 use std::cmp::Ordering;
 #[cfg(debug_assertions)]
@@ -160,8 +162,26 @@ pub(crate) trait Page {
     fn get_page_number(&self) -> PageNumber;
 }
 
+// The contents of a page, either read into memory or, for committed pages, memory mapped
+#[derive(Clone)]
+pub(super) enum PageBuffer {
+    Owned(Arc<[u8]>),
+    #[cfg(target_os = "linux")]
+    Mapped(MappedPage),
+}
+
+impl PageBuffer {
+    fn memory(&self) -> &[u8] {
+        match self {
+            PageBuffer::Owned(mem) => mem,
+            #[cfg(target_os = "linux")]
+            PageBuffer::Mapped(page) => page.memory(),
+        }
+    }
+}
+
 pub struct PageImpl {
-    pub(super) mem: Arc<[u8]>,
+    pub(super) mem: PageBuffer,
     pub(super) page_number: PageNumber,
     #[cfg(debug_assertions)]
     pub(super) open_pages: Arc<Mutex<HashMap<PageNumber, u64>>>,
@@ -169,7 +189,11 @@ pub struct PageImpl {
 
 impl PageImpl {
     pub(crate) fn to_arc(&self) -> Arc<[u8]> {
-        self.mem.clone()
+        match &self.mem {
+            PageBuffer::Owned(mem) => mem.clone(),
+            #[cfg(target_os = "linux")]
+            PageBuffer::Mapped(page) => page.memory().into(),
+        }
     }
 }
 
@@ -194,7 +218,7 @@ impl Drop for PageImpl {
 
 impl Page for PageImpl {
     fn memory(&self) -> &[u8] {
-        self.mem.as_ref()
+        self.mem.memory()
     }
 
     fn get_page_number(&self) -> PageNumber {
@@ -300,6 +324,19 @@ impl PageTrackerPolicy {
         }
     }
 
+    // Like remove(), but the page need not be tracked
+    pub(super) fn remove_if_tracked(&mut self, page: PageNumber) {
+        match self {
+            PageTrackerPolicy::Ignore => {}
+            PageTrackerPolicy::Track(x) => {
+                x.remove(&page);
+            }
+            PageTrackerPolicy::Closed => {
+                panic!("Page tracker is closed");
+            }
+        }
+    }
+
     pub(crate) fn close(&mut self) -> HashSet<PageNumber> {
         let old = mem::replace(self, PageTrackerPolicy::Closed);
         match old {
@@ -1,6 +1,13 @@
-use crate::tree_store::page_store::base::PageHint;
+use crate::db::TransactionGuard;
+use crate::tree_store::page_store::base::{PageBuffer, PageHint};
 use crate::tree_store::page_store::lru_cache::LRUCache;
-use crate::{CacheStats, DatabaseError, Result, StorageBackend, StorageError};
+#[cfg(target_os = "linux")]
+use crate::tree_store::page_store::memory_map::MemoryMap;
+use crate::tree_store::page_store::xxh3_checksum;
+use crate::{CacheStats, DatabaseError, PageEncryption, Result, StorageBackend, StorageError};
+use std::fs::File;
+use std::io;
+use std::iter;
 use std::ops::{Index, IndexMut};
 use std::slice::SliceIndex;
 #[cfg(feature = "cache_metrics")]
@@ -8,6 +15,10 @@ use std::sync::atomic::AtomicU64;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 
+// When encryption is enabled, pages are encrypted into a buffer of this size and written in batches,
+// rather than copying the whole write buffer at once
+const ENCRYPTED_WRITE_BATCH_BYTES: usize = 4 * 1024 * 1024;
+
 pub(super) struct WritablePage {
     buffer: Arc<Mutex<LRUWriteCache>>,
     offset: u64,
@@ -109,14 +120,14 @@ impl LRUWriteCache {
 }
 
 #[derive(Debug)]
-struct CheckedBackend {
+pub(super) struct CheckedBackend {
     file: Box<dyn StorageBackend>,
     io_failed: AtomicBool,
     closed: AtomicBool,
 }
 
 impl CheckedBackend {
-    fn new(file: Box<dyn StorageBackend>) -> Self {
+    pub(super) fn new(file: Box<dyn StorageBackend>) -> Self {
         Self {
             file,
             io_failed: AtomicBool::new(false),
@@ -124,7 +135,7 @@ impl CheckedBackend {
         }
     }
 
-    fn check_failure(&self) -> Result<()> {
+    pub(super) fn check_failure(&self) -> Result<()> {
         if self.io_failed.load(Ordering::Acquire) {
             if self.closed.load(Ordering::Acquire) {
                 Err(StorageError::DatabaseClosed)
@@ -136,7 +147,7 @@ impl CheckedBackend {
         }
     }
 
-    fn close(&self) -> Result {
+    pub(super) fn close(&self) -> Result {
         self.closed.store(true, Ordering::Release);
         self.io_failed.store(true, Ordering::Release);
         self.file.close()?;
@@ -144,7 +155,7 @@ impl CheckedBackend {
         Ok(())
     }
 
-    fn len(&self) -> Result<u64> {
+    pub(super) fn len(&self) -> Result<u64> {
         self.check_failure()?;
         let result = self.file.len();
         if result.is_err() {
@@ -153,7 +164,7 @@ impl CheckedBackend {
         result.map_err(StorageError::from)
     }
 
-    fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+    pub(super) fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
         self.check_failure()?;
         let result = self.file.read(offset, len);
         if result.is_err() {
@@ -162,7 +173,16 @@ impl CheckedBackend {
         result.map_err(StorageError::from)
     }
 
-    fn set_len(&self, len: u64) -> Result<()> {
+    pub(super) fn read_into(&self, offset: u64, buffer: &mut [u8]) -> Result<()> {
+        self.check_failure()?;
+        let result = self.file.read_into(offset, buffer);
+        if result.is_err() {
+            self.io_failed.store(true, Ordering::Release);
+        }
+        result.map_err(StorageError::from)
+    }
+
+    pub(super) fn set_len(&self, len: u64) -> Result<()> {
         self.check_failure()?;
         let result = self.file.set_len(len);
         if result.is_err() {
@@ -171,7 +191,7 @@ impl CheckedBackend {
         result.map_err(StorageError::from)
     }
 
-    fn sync_data(&self, eventual: bool) -> Result<()> {
+    pub(super) fn sync_data(&self, eventual: bool) -> Result<()> {
         self.check_failure()?;
         let result = self.file.sync_data(eventual);
         if result.is_err() {
@@ -180,7 +200,7 @@ impl CheckedBackend {
         result.map_err(StorageError::from)
     }
 
-    fn write(&self, offset: u64, data: &[u8]) -> Result<()> {
+    pub(super) fn write(&self, offset: u64, data: &[u8]) -> Result<()> {
         self.check_failure()?;
         let result = self.file.write(offset, data);
         if result.is_err() {
@@ -188,10 +208,23 @@ impl CheckedBackend {
         }
         result.map_err(StorageError::from)
     }
+
+    pub(super) fn write_vectored(&self, writes: &[(u64, &[u8])]) -> Result<()> {
+        self.check_failure()?;
+        let result = self.file.write_vectored(writes);
+        if result.is_err() {
+            self.io_failed.store(true, Ordering::Release);
+        }
+        result.map_err(StorageError::from)
+    }
 }
 
 pub(super) struct PagedCachedFile {
     file: CheckedBackend,
+    // Applied to everything after the first page, which contains the header
+    encryption: Option<Arc<dyn PageEncryption>>,
+    #[cfg(target_os = "linux")]
+    memory_map: Option<MemoryMap>,
     page_size: u64,
     max_read_cache_bytes: usize,
     read_cache_bytes: AtomicUsize,
@@ -214,13 +247,26 @@ impl PagedCachedFile {
         page_size: u64,
         max_read_cache_bytes: usize,
         max_write_buffer_bytes: usize,
+        encryption: Option<Arc<dyn PageEncryption>>,
+        // A clone of the database file, if committed pages should be read from a memory map
+        memory_map: Option<File>,
     ) -> Result<Self, DatabaseError> {
         let read_cache = (0..Self::lock_stripes())
             .map(|_| RwLock::new(LRUCache::new()))
             .collect();
+        // Encrypted pages have to be decrypted into memory
+        #[cfg(target_os = "linux")]
+        let memory_map = memory_map
+            .filter(|_| encryption.is_none())
+            .map(MemoryMap::new);
+        #[cfg(not(target_os = "linux"))]
+        assert!(memory_map.is_none());
 
         Ok(Self {
             file: CheckedBackend::new(file),
+            encryption,
+            #[cfg(target_os = "linux")]
+            memory_map,
             page_size,
             max_read_cache_bytes,
             read_cache_bytes: AtomicUsize::new(0),
@@ -263,12 +309,113 @@ impl PagedCachedFile {
         131
     }
 
+    pub(super) fn encryption(&self) -> Option<Arc<dyn PageEncryption>> {
+        self.encryption.clone()
+    }
+
+    // Checksum of an empty page encrypted at offset 0. The first page is never encrypted, so this
+    // does not reveal anything about the contents of the database
+    pub(super) fn encryption_key_check(&self) -> Result<Option<u128>> {
+        if let Some(encryption) = &self.encryption {
+            let mut page = vec![0; self.page_size.try_into().unwrap()];
+            encryption.encrypt(0, &mut page)?;
+            Ok(Some(xxh3_checksum(&page)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Encrypts, in place, data that will be stored at `offset`
+    pub(super) fn encrypt(&self, offset: u64, data: &mut [u8]) -> Result {
+        self.transform_pages(offset, data, |encryption, offset, page| {
+            encryption.encrypt(offset, page)
+        })
+    }
+
+    // Decrypts, in place, data that was stored at `offset`
+    pub(super) fn decrypt(&self, offset: u64, data: &mut [u8]) -> Result {
+        self.transform_pages(offset, data, |encryption, offset, page| {
+            encryption.decrypt(offset, page)
+        })
+    }
+
+    fn transform_pages(
+        &self,
+        offset: u64,
+        data: &mut [u8],
+        transform: impl Fn(&dyn PageEncryption, u64, &mut [u8]) -> io::Result<()>,
+    ) -> Result {
+        let Some(encryption) = &self.encryption else {
+            return Ok(());
+        };
+        if offset < self.page_size {
+            // The header is not encrypted
+            debug_assert!(offset + data.len() as u64 <= self.page_size);
+            return Ok(());
+        }
+        debug_assert_eq!(0, offset % self.page_size);
+        for (i, page) in data
+            .chunks_mut(self.page_size.try_into().unwrap())
+            .enumerate()
+        {
+            transform(
+                encryption.as_ref(),
+                offset + i as u64 * self.page_size,
+                page,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_file(&self, writes: &[(u64, &[u8])]) -> Result {
+        if self.encryption.is_none() {
+            return self.file.write_vectored(writes);
+        }
+        let mut encrypted = Vec::with_capacity(ENCRYPTED_WRITE_BATCH_BYTES);
+        let mut remaining = writes;
+        while !remaining.is_empty() {
+            // Each batch contains at least one write, even if it is larger than the limit
+            let mut batch_len = 1;
+            let mut batch_bytes = remaining[0].1.len();
+            while batch_len < remaining.len()
+                && batch_bytes + remaining[batch_len].1.len() <= ENCRYPTED_WRITE_BATCH_BYTES
+            {
+                batch_bytes += remaining[batch_len].1.len();
+                batch_len += 1;
+            }
+            let (batch, rest) = remaining.split_at(batch_len);
+            remaining = rest;
+
+            encrypted.clear();
+            for (offset, data) in batch {
+                let start = encrypted.len();
+                encrypted.extend_from_slice(data);
+                self.encrypt(*offset, &mut encrypted[start..])?;
+            }
+            let mut start = 0;
+            let batch: Vec<(u64, &[u8])> = batch
+                .iter()
+                .map(|(offset, data)| {
+                    let page = &encrypted[start..(start + data.len())];
+                    start += data.len();
+                    (*offset, page)
+                })
+                .collect();
+            self.file.write_vectored(&batch)?;
+        }
+        Ok(())
+    }
+
     fn flush_write_buffer(&self) -> Result {
         let mut write_buffer = self.write_buffer.lock().unwrap();
 
-        for (offset, buffer) in write_buffer.cache.iter() {
-            self.file.write(*offset, buffer.as_ref().unwrap())?;
-        }
+        let writes: Vec<(u64, &[u8])> = write_buffer
+            .cache
+            .iter()
+            .map(|(offset, buffer)| (*offset, buffer.as_deref().unwrap()))
+            .collect();
+        self.write_file(&writes)?;
+        drop(writes);
         for (offset, buffer) in write_buffer.cache.iter_mut() {
             let buffer = buffer.take().unwrap();
             let cache_size = self
@@ -314,9 +461,53 @@ impl PagedCachedFile {
         self.flush_write_buffer()
     }
 
+    // Write directly to the file, bypassing the write buffer. The caller must ensure that the
+    // range is not in the write buffer
+    pub(super) fn write_direct(&self, offset: u64, data: &[u8]) -> Result {
+        self.invalidate_cache(offset, data.len());
+        self.write_file(&[(offset, data)])
+    }
+
+    // Make writes which have already left the write buffer durable, without flushing it
+    pub(super) fn sync_written(&self) -> Result {
+        self.file.sync_data(false)
+    }
+
     // Read directly from the file, ignoring any cached data
     pub(super) fn read_direct(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
-        self.file.read(offset, len)
+        let mut data = vec![0; len];
+        self.file.read_into(offset, &mut data)?;
+        self.decrypt(offset, &mut data)?;
+        Ok(data)
+    }
+
+    // Same as read_direct(), but reads into a buffer which can be cached
+    fn read_direct_shared(&self, offset: u64, len: usize) -> Result<Arc<[u8]>> {
+        // Collecting from an iterator of known length allocates the Arc without an extra copy
+        let mut buffer: Arc<[u8]> = iter::repeat_n(0, len).collect();
+        let data = Arc::get_mut(&mut buffer).unwrap();
+        self.file.read_into(offset, data)?;
+        self.decrypt(offset, data)?;
+        Ok(buffer)
+    }
+
+    // Read a committed page from the memory map, bypassing the caches. Returns None if reads are
+    // not memory mapped. `pin` must be the guard of a read transaction whose snapshot contains the
+    // page
+    #[allow(clippy::unused_self, unused_variables)]
+    pub(super) fn read_mapped(
+        &self,
+        offset: u64,
+        len: usize,
+        pin: &Arc<TransactionGuard>,
+    ) -> Result<Option<PageBuffer>> {
+        #[cfg(target_os = "linux")]
+        if let Some(memory_map) = &self.memory_map {
+            self.file.check_failure()?;
+            let page = memory_map.page(offset, len, pin)?;
+            return Ok(Some(PageBuffer::Mapped(page)));
+        }
+        Ok(None)
     }
 
     // Read with caching. Caller must not read overlapping ranges without first calling invalidate_cache().
@@ -347,7 +538,7 @@ impl PagedCachedFile {
             }
         }
 
-        let buffer: Arc<[u8]> = self.read_direct(offset, len)?.into();
+        let buffer = self.read_direct_shared(offset, len)?;
         let cache_size = self.read_cache_bytes.fetch_add(len, Ordering::AcqRel);
         let mut write_lock = self.read_cache[cache_slot].write().unwrap();
         let cache_size = if let Some(replaced) = write_lock.insert(offset, buffer.clone()) {
@@ -440,33 +631,44 @@ impl PagedCachedFile {
         } else {
             let previous = self.write_buffer_bytes.fetch_add(len, Ordering::AcqRel);
             if previous + len > self.max_write_buffer_bytes {
+                let mut removed = vec![];
                 let mut removed_bytes = 0;
                 while removed_bytes < len {
                     if let Some((offset, buffer)) = lock.pop_lowest_priority() {
-                        let removed_len = buffer.len();
-                        let result = self.file.write(offset, &buffer);
-                        if result.is_err() {
-                            lock.insert(offset, buffer);
-                        }
-                        result?;
-                        self.write_buffer_bytes
-                            .fetch_sub(removed_len, Ordering::Release);
-                        #[cfg(feature = "cache_metrics")]
-                        {
-                            self.evictions.fetch_add(1, Ordering::Relaxed);
-                        }
-                        removed_bytes += removed_len;
+                        removed_bytes += buffer.len();
+                        removed.push((offset, buffer));
                     } else {
                         break;
                     }
                 }
+                let writes: Vec<(u64, &[u8])> = removed
+                    .iter()
+                    .map(|(offset, buffer)| (*offset, buffer.as_ref()))
+                    .collect();
+                let result = self.write_file(&writes);
+                drop(writes);
+                #[cfg(feature = "cache_metrics")]
+                let evicted_pages = removed.len();
+                if result.is_err() {
+                    for (offset, buffer) in removed {
+                        lock.insert(offset, buffer);
+                    }
+                }
+                result?;
+                self.write_buffer_bytes
+                    .fetch_sub(removed_bytes, Ordering::Release);
+                #[cfg(feature = "cache_metrics")]
+                {
+                    self.evictions
+                        .fetch_add(evicted_pages as u64, Ordering::Relaxed);
+                }
             }
             let result = if let Some(data) = existing {
                 data
             } else if overwrite {
                 vec![0; len].into()
             } else {
-                self.read_direct(offset, len)?.into()
+                self.read_direct_shared(offset, len)?
             };
             lock.insert(offset, result);
             lock.take_value(offset).unwrap()
@@ -481,18 +683,79 @@ impl PagedCachedFile {
 
 #[cfg(test)]
 mod test {
-    use crate::StorageBackend;
     use crate::backends::InMemoryBackend;
     use crate::tree_store::PageHint;
-    use crate::tree_store::page_store::cached_file::PagedCachedFile;
+    use crate::tree_store::page_store::cached_file::{
+        ENCRYPTED_WRITE_BATCH_BYTES, PagedCachedFile,
+    };
+    use crate::{PageEncryption, StorageBackend};
+    use std::io;
     use std::sync::Arc;
     use std::sync::atomic::Ordering;
 
+    #[derive(Debug)]
+    struct XorEncryption;
+
+    impl PageEncryption for XorEncryption {
+        fn encrypt(&self, _: u64, page: &mut [u8]) -> Result<(), io::Error> {
+            for byte in page {
+                *byte ^= 0x5a;
+            }
+            Ok(())
+        }
+
+        fn decrypt(&self, offset: u64, page: &mut [u8]) -> Result<(), io::Error> {
+            self.encrypt(offset, page)
+        }
+    }
+
+    #[test]
+    fn encrypted_write_batches() {
+        const PAGE_SIZE: usize = 4096;
+        let pages = 3 * ENCRYPTED_WRITE_BATCH_BYTES / PAGE_SIZE + 1;
+        let len = (pages + 1) * PAGE_SIZE;
+        let backend = InMemoryBackend::new();
+        backend.set_len(len as u64).unwrap();
+        let image = backend.image();
+        let fill = |i: usize| u8::try_from(i % 251).unwrap();
+        let cached_file = PagedCachedFile::new(
+            Box::new(backend),
+            PAGE_SIZE as u64,
+            0,
+            len,
+            Some(Arc::new(XorEncryption)),
+            None,
+        )
+        .unwrap();
+
+        for i in 1..=pages {
+            let mut page = cached_file
+                .write((i * PAGE_SIZE) as u64, PAGE_SIZE, true)
+                .unwrap();
+            page.mem_mut().fill(fill(i));
+        }
+        cached_file.flush(false).unwrap();
+        for i in 1..=pages {
+            let page = cached_file
+                .read((i * PAGE_SIZE) as u64, PAGE_SIZE, PageHint::None)
+                .unwrap();
+            assert!(page.iter().all(|x| *x == fill(i)));
+        }
+        drop(cached_file);
+
+        let image = image.into_bytes().unwrap();
+        for i in 1..=pages {
+            let page = &image[(i * PAGE_SIZE)..((i + 1) * PAGE_SIZE)];
+            assert!(page.iter().all(|x| *x == fill(i) ^ 0x5a));
+        }
+    }
+
     #[test]
     fn cache_leak() {
         let backend = InMemoryBackend::new();
         backend.set_len(1024).unwrap();
-        let cached_file = PagedCachedFile::new(Box::new(backend), 128, 1024, 128).unwrap();
+        let cached_file =
+            PagedCachedFile::new(Box::new(backend), 128, 1024, 128, None, None).unwrap();
         let cached_file = Arc::new(cached_file);
 
         let t1 = {
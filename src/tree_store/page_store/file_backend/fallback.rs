@@ -26,10 +26,14 @@ impl StorageBackend for FileBackend {
 
     fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, io::Error> {
         let mut result = vec![0; len];
+        self.read_into(offset, &mut result)?;
+        Ok(result)
+    }
+
+    fn read_into(&self, offset: u64, buffer: &mut [u8]) -> Result<(), io::Error> {
         let mut file = self.file.lock().unwrap();
         file.seek(SeekFrom::Start(offset))?;
-        file.read_exact(&mut result)?;
-        Ok(result)
+        file.read_exact(buffer)
     }
 
     fn set_len(&self, len: u64) -> Result<(), io::Error> {
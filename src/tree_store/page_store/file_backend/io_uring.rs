@@ -0,0 +1,235 @@
+use crate::tree_store::file_backend::FileBackend;
+use crate::{DatabaseError, Result, StorageBackend};
+use io_uring::{IoUring, opcode, squeue, types};
+use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io;
+use std::os::unix::{fs::FileExt, io::AsRawFd};
+use std::sync::Mutex;
+
+// Writes are submitted once this many are pending, and at most this many are submitted together.
+// One more entry is needed for the fsync
+const MAX_PENDING_WRITES: usize = 127;
+const QUEUE_ENTRIES: u32 = 128;
+// Writes are submitted once this many bytes are pending, and larger writes are not queued at all
+const MAX_PENDING_BYTES: usize = 16 * 1024 * 1024;
+
+/// Stores a database as a file on-disk, using `io_uring` to write it
+///
+/// Writes are queued, and submitted together when the queue is full or when the data is synced,
+/// in which case the `fsync` is linked after them. This replaces a `pwrite` system call per page
+/// with a single `io_uring_enter` when a transaction is committed. Writes passed to
+/// [`StorageBackend::write_vectored`] are submitted directly from the caller's buffers. Reads use
+/// `pread`, like [`FileBackend`].
+///
+/// Since writes are deferred, an error writing a page may be returned by a later call, such as
+/// [`StorageBackend::sync_data`].
+pub struct IoUringBackend {
+    file: FileBackend,
+    state: Mutex<State>,
+}
+
+struct State {
+    ring: IoUring,
+    pending: Vec<(u64, Vec<u8>)>,
+    pending_bytes: usize,
+    // Set if the ring failed while entries may still have been queued in it. It is not used
+    // after that, since those entries could refer to buffers which have been freed
+    failed: bool,
+}
+
+impl IoUringBackend {
+    /// Creates a new backend which stores data to the given file.
+    pub fn new(file: File) -> Result<Self, DatabaseError> {
+        let ring = IoUring::new(QUEUE_ENTRIES)?;
+        Ok(Self {
+            file: FileBackend::new(file)?,
+            state: Mutex::new(State {
+                ring,
+                pending: vec![],
+                pending_bytes: 0,
+                failed: false,
+            }),
+        })
+    }
+
+    fn file(&self) -> &File {
+        self.file.file()
+    }
+
+    // Submits the pending writes, followed by an fsync if `sync` is true, and waits for them
+    fn submit_pending(&self, state: &mut State, sync: bool) -> Result<(), io::Error> {
+        if state.pending.is_empty() && !sync {
+            return Ok(());
+        }
+        let pending = std::mem::take(&mut state.pending);
+        state.pending_bytes = 0;
+        let writes: Vec<(u64, &[u8])> = pending
+            .iter()
+            .map(|(offset, data)| (*offset, data.as_slice()))
+            .collect();
+        self.submit(state, &writes, sync)
+    }
+
+    // Submits `writes`, followed by an fsync if `sync` is true, and waits for them. There must be
+    // fewer than `QUEUE_ENTRIES` writes. They are linked, so that they are performed in order,
+    // and an earlier write can't overwrite a later one to the same offset
+    fn submit(
+        &self,
+        state: &mut State,
+        writes: &[(u64, &[u8])],
+        sync: bool,
+    ) -> Result<(), io::Error> {
+        if state.failed {
+            return Err(io::Error::other("io_uring submission failed previously"));
+        }
+        let fd = types::Fd(self.file().as_raw_fd());
+        let expected = writes.len() + usize::from(sync);
+
+        {
+            let mut submission = state.ring.submission();
+            for (i, (offset, data)) in writes.iter().enumerate() {
+                let mut entry =
+                    opcode::Write::new(fd, data.as_ptr(), data.len().try_into().unwrap())
+                        .offset(*offset)
+                        .build()
+                        .user_data(i as u64);
+                if i + 1 < expected {
+                    entry = entry.flags(squeue::Flags::IO_LINK);
+                }
+                // Safety: the buffer is kept alive until the write has completed
+                unsafe { submission.push(&entry) }.unwrap();
+            }
+            if sync {
+                let entry = opcode::Fsync::new(fd)
+                    .flags(types::FsyncFlags::DATASYNC)
+                    .build()
+                    .user_data(writes.len() as u64);
+                unsafe { submission.push(&entry) }.unwrap();
+            }
+        }
+
+        let mut results = vec![None; expected];
+        let mut completed = 0;
+        let mut submitted = false;
+        while completed < expected {
+            match state.ring.submit_and_wait(expected - completed) {
+                Ok(_) => {
+                    submitted = true;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) if !submitted => {
+                    state.failed = true;
+                    return Err(err);
+                }
+                Err(_) => {
+                    // The submitted writes still read from their buffers, so they must be waited
+                    // for before returning
+                }
+            }
+            for entry in state.ring.completion() {
+                results[usize::try_from(entry.user_data()).unwrap()] = Some(entry.result());
+                completed += 1;
+            }
+        }
+
+        // A failed or short write cancels the rest of the chain, so those are finished here
+        let mut needs_sync = false;
+        for (i, result) in results.into_iter().enumerate() {
+            let result = result.unwrap();
+            if result == -libc::ECANCELED {
+                if i == writes.len() {
+                    needs_sync = true;
+                } else {
+                    let (offset, data) = writes[i];
+                    self.file().write_all_at(data, offset)?;
+                }
+            } else if result < 0 {
+                return Err(io::Error::from_raw_os_error(-result));
+            } else if i < writes.len() {
+                let (offset, data) = writes[i];
+                let written = usize::try_from(result).unwrap();
+                if written < data.len() {
+                    self.file()
+                        .write_all_at(&data[written..], offset + written as u64)?;
+                }
+            }
+        }
+        if needs_sync {
+            self.file().sync_data()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        let mut state = self.state.lock().unwrap();
+        self.submit_pending(&mut state, false)
+    }
+}
+
+impl Debug for IoUringBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IoUringBackend")
+            .field("file", &self.file)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StorageBackend for IoUringBackend {
+    fn len(&self) -> Result<u64, io::Error> {
+        self.flush()?;
+        self.file.len()
+    }
+
+    fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, io::Error> {
+        self.flush()?;
+        self.file.read(offset, len)
+    }
+
+    fn read_into(&self, offset: u64, buffer: &mut [u8]) -> Result<(), io::Error> {
+        self.flush()?;
+        self.file.read_into(offset, buffer)
+    }
+
+    fn set_len(&self, len: u64) -> Result<(), io::Error> {
+        let mut state = self.state.lock().unwrap();
+        self.submit_pending(&mut state, false)?;
+        self.file.set_len(len)
+    }
+
+    fn sync_data(&self, _: bool) -> Result<(), io::Error> {
+        let mut state = self.state.lock().unwrap();
+        self.submit_pending(&mut state, true)
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> Result<(), io::Error> {
+        let mut state = self.state.lock().unwrap();
+        if data.len() > MAX_PENDING_BYTES {
+            self.submit_pending(&mut state, false)?;
+            return self.file.write(offset, data);
+        }
+        if state.pending.len() == MAX_PENDING_WRITES
+            || state.pending_bytes + data.len() > MAX_PENDING_BYTES
+        {
+            self.submit_pending(&mut state, false)?;
+        }
+        state.pending.push((offset, data.to_vec()));
+        state.pending_bytes += data.len();
+        Ok(())
+    }
+
+    fn write_vectored(&self, writes: &[(u64, &[u8])]) -> Result<(), io::Error> {
+        let mut state = self.state.lock().unwrap();
+        self.submit_pending(&mut state, false)?;
+        for chunk in writes.chunks(MAX_PENDING_WRITES) {
+            self.submit(&mut state, chunk, false)?;
+        }
+        Ok(())
+    }
+
+    fn close(&self) -> Result<(), io::Error> {
+        self.flush()?;
+        self.file.close()
+    }
+}
@@ -3,6 +3,11 @@ mod unix;
 #[cfg(any(unix, target_os = "wasi"))]
 pub use unix::FileBackend;
 
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+mod io_uring;
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+pub use io_uring::IoUringBackend;
+
 #[cfg(windows)]
 mod windows;
 #[cfg(windows)]
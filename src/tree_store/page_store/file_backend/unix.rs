@@ -8,6 +8,8 @@
 use crate::{DatabaseError, Result, StorageBackend};
 use std::fs::File;
 use std::io;
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
 
 #[cfg(unix)]
 use std::os::unix::{fs::FileExt, io::AsRawFd};
@@ -15,10 +17,20 @@ use std::os::unix::{fs::FileExt, io::AsRawFd};
 #[cfg(target_os = "wasi")]
 use std::os::wasi::{fs::FileExt, io::AsRawFd};
 
+// O_DIRECT requires the offset, length and memory address of every read and write to be aligned
+// to the logical block size of the file system. This is the largest block size in common use
+#[cfg(target_os = "linux")]
+const DIRECT_IO_ALIGNMENT: usize = 4096;
+
 /// Stores a database as a file on-disk.
 #[derive(Debug)]
 pub struct FileBackend {
     file: File,
+    unlock_on_close: bool,
+    // Set if the file was opened for direct I/O. Serializes writes, since a write which is not
+    // aligned has to read, and then rewrite, the blocks around it
+    #[cfg(target_os = "linux")]
+    direct_io: Option<Mutex<()>>,
 }
 
 impl FileBackend {
@@ -27,7 +39,10 @@ impl FileBackend {
     // Delete this function when we get flock.
     #[cfg(target_os = "wasi")]
     pub fn new(file: File) -> Result<Self, DatabaseError> {
-        Ok(Self { file })
+        Ok(Self {
+            file,
+            unlock_on_close: true,
+        })
     }
 
     /// Creates a new backend which stores data to the given file.
@@ -43,8 +58,152 @@ impl FileBackend {
                 Err(err.into())
             }
         } else {
-            Ok(Self { file })
+            Ok(Self {
+                file,
+                unlock_on_close: true,
+                #[cfg(target_os = "linux")]
+                direct_io: None,
+            })
+        }
+    }
+
+    /// Creates a new backend which stores data to the given file, bypassing the page cache of the
+    /// operating system.
+    ///
+    /// The file is switched to `O_DIRECT`, so that pages which are already cached by redb, see
+    /// [`crate::Builder::set_cache_size`], are not cached a second time by the kernel. Reads and
+    /// writes go through buffers aligned to 4KiB, and a write which is not aligned to 4KiB first
+    /// reads the blocks around it. Returns an error if the file system does not support `O_DIRECT`.
+    #[cfg(target_os = "linux")]
+    pub fn new_direct(file: File) -> Result<Self, DatabaseError> {
+        let mut backend = Self::new(file)?;
+        let fd = backend.file.as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags == -1 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_DIRECT) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        backend.direct_io = Some(Mutex::new(()));
+        Ok(backend)
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io_uring"))]
+    pub(super) fn file(&self) -> &File {
+        &self.file
+    }
+
+    // Leave the file locked when the database is closed. The lock is then released when the last
+    // handle to the file is closed, which allows it to be held by the memory map
+    #[cfg(target_os = "linux")]
+    pub(crate) fn keep_lock_on_close(&mut self) {
+        self.unlock_on_close = false;
+    }
+
+    #[cfg(target_os = "linux")]
+    fn read_aligned(&self, offset: u64, output: &mut [u8]) -> Result<(), io::Error> {
+        if output.is_empty() {
+            return Ok(());
+        }
+        let (start, buffer_len) = aligned_range(offset, output.len());
+        let mut buffer = AlignedBuffer::new(buffer_len);
+        let read = self.read_until_eof(&mut buffer, start)?;
+        let skip: usize = (offset - start).try_into().unwrap();
+        if read < skip + output.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        output.copy_from_slice(&buffer[skip..(skip + output.len())]);
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn write_aligned(&self, offset: u64, data: &[u8]) -> Result<(), io::Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let (start, buffer_len) = aligned_range(offset, data.len());
+        let mut buffer = AlignedBuffer::new(buffer_len);
+        let skip: usize = (offset - start).try_into().unwrap();
+        let partial = skip != 0 || data.len() != buffer_len;
+        let file_len = if partial {
+            self.read_until_eof(&mut buffer, start)?;
+            self.file.metadata()?.len()
+        } else {
+            0
+        };
+        buffer[skip..(skip + data.len())].copy_from_slice(data);
+        self.file.write_all_at(&buffer, start)?;
+        // Writing whole blocks may have extended the file past the end of the data
+        let end = offset + data.len() as u64;
+        if partial && start + buffer_len as u64 > file_len.max(end) {
+            self.file.set_len(file_len.max(end))?;
+        }
+        Ok(())
+    }
+
+    // Returns the number of bytes read, which is less than the length of `buffer` if the end of
+    // the file was reached
+    #[cfg(target_os = "linux")]
+    fn read_until_eof(&self, buffer: &mut [u8], offset: u64) -> Result<usize, io::Error> {
+        let mut read = 0;
+        while read < buffer.len() {
+            match self.file.read_at(&mut buffer[read..], offset + read as u64) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
         }
+        Ok(read)
+    }
+}
+
+// Returns the start and length of the smallest range of whole blocks which contains the given range
+#[cfg(target_os = "linux")]
+fn aligned_range(offset: u64, len: usize) -> (u64, usize) {
+    let alignment = DIRECT_IO_ALIGNMENT as u64;
+    let start = offset - offset % alignment;
+    let end = (offset + len as u64).next_multiple_of(alignment);
+    (start, (end - start).try_into().unwrap())
+}
+
+#[cfg(target_os = "linux")]
+struct AlignedBuffer {
+    data: std::ptr::NonNull<u8>,
+    layout: std::alloc::Layout,
+}
+
+#[cfg(target_os = "linux")]
+impl AlignedBuffer {
+    // `len` must not be zero
+    fn new(len: usize) -> Self {
+        let layout = std::alloc::Layout::from_size_align(len, DIRECT_IO_ALIGNMENT).unwrap();
+        let data = unsafe { std::alloc::alloc_zeroed(layout) };
+        let Some(data) = std::ptr::NonNull::new(data) else {
+            std::alloc::handle_alloc_error(layout);
+        };
+        Self { data, layout }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::ops::Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr(), self.layout.size()) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::ops::DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.data.as_ptr(), self.layout.size()) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.data.as_ptr(), self.layout) };
     }
 }
 
@@ -55,10 +214,18 @@ impl StorageBackend for FileBackend {
 
     fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, io::Error> {
         let mut buffer = vec![0; len];
-        self.file.read_exact_at(&mut buffer, offset)?;
+        self.read_into(offset, &mut buffer)?;
         Ok(buffer)
     }
 
+    fn read_into(&self, offset: u64, buffer: &mut [u8]) -> Result<(), io::Error> {
+        #[cfg(target_os = "linux")]
+        if self.direct_io.is_some() {
+            return self.read_aligned(offset, buffer);
+        }
+        self.file.read_exact_at(buffer, offset)
+    }
+
     fn set_len(&self, len: u64) -> Result<(), io::Error> {
         self.file.set_len(len)
     }
@@ -83,12 +250,19 @@ impl StorageBackend for FileBackend {
     }
 
     fn write(&self, offset: u64, data: &[u8]) -> Result<(), io::Error> {
+        #[cfg(target_os = "linux")]
+        if let Some(lock) = &self.direct_io {
+            let _guard = lock.lock().unwrap();
+            return self.write_aligned(offset, data);
+        }
         self.file.write_all_at(data, offset)
     }
 
     #[cfg(unix)] // remove this line when wasi-libc gets flock
     fn close(&self) -> Result<(), io::Error> {
-        unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) };
+        if self.unlock_on_close {
+            unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) };
+        }
 
         Ok(())
     }
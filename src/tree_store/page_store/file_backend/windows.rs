@@ -65,15 +65,20 @@ impl StorageBackend for FileBackend {
         Ok(self.file.metadata()?.len())
     }
 
-    fn read(&self, mut offset: u64, len: usize) -> Result<Vec<u8>, io::Error> {
+    fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, io::Error> {
         let mut buffer = vec![0; len];
+        self.read_into(offset, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn read_into(&self, mut offset: u64, buffer: &mut [u8]) -> Result<(), io::Error> {
         let mut data_offset = 0;
         while data_offset < buffer.len() {
             let read = self.file.seek_read(&mut buffer[data_offset..], offset)?;
             offset += read as u64;
             data_offset += read;
         }
-        Ok(buffer)
+        Ok(())
     }
 
     fn set_len(&self, len: u64) -> Result<(), io::Error> {
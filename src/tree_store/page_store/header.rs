@@ -3,7 +3,8 @@ use crate::tree_store::Checksum;
 use crate::tree_store::btree_base::BtreeHeader;
 use crate::tree_store::page_store::layout::{DatabaseLayout, RegionLayout};
 use crate::tree_store::page_store::page_manager::{
-    FILE_FORMAT_VERSION1, FILE_FORMAT_VERSION2, FILE_FORMAT_VERSION3, xxh3_checksum,
+    FILE_FORMAT_VERSION1, FILE_FORMAT_VERSION2, FILE_FORMAT_VERSION3, FILE_FORMAT_VERSION4,
+    xxh3_checksum,
 };
 use crate::{DatabaseError, Result, StorageError};
 use std::mem::size_of;
@@ -21,6 +22,10 @@ use std::mem::size_of;
 // Definition of region
 // 4 bytes: region header pages
 // 4 bytes: region max data pages
+// 4 bytes: number of full regions
+// 4 bytes: data pages in partial trailing region
+// 8 bytes: unused: formerly region tracker page number
+// 16 bytes: encryption key check, if the database is encrypted
 //
 // Commit slot 0 (next 128 bytes):
 // 1 byte: version
@@ -32,9 +37,6 @@ use std::mem::size_of;
 // 8 bytes: unused: formerly freed table root page
 // 16 bytes: unused: formerly freed table root checksum
 // 8 bytes: last committed transaction id
-// 4 bytes: number of full regions
-// 4 bytes: data pages in partial trailing region
-// 8 bytes: unused: formerly region tracker page number
 // 16 bytes: slot checksum
 //
 // Commit slot 1 (next 128 bytes):
@@ -50,6 +52,7 @@ const NUM_FULL_REGIONS_OFFSET: usize = REGION_MAX_DATA_PAGES_OFFSET + size_of::<
 const TRAILING_REGION_DATA_PAGES_OFFSET: usize = NUM_FULL_REGIONS_OFFSET + size_of::<u32>();
 // Formerly the region tracker page
 const _UNUSED3_OFFSET: usize = TRAILING_REGION_DATA_PAGES_OFFSET + size_of::<u32>();
+const KEY_CHECK_OFFSET: usize = _UNUSED3_OFFSET + size_of::<u64>();
 const TRANSACTION_SIZE: usize = 128;
 const TRANSACTION_0_OFFSET: usize = 64;
 const TRANSACTION_1_OFFSET: usize = TRANSACTION_0_OFFSET + TRANSACTION_SIZE;
@@ -59,6 +62,8 @@ pub(super) const DB_HEADER_SIZE: usize = TRANSACTION_1_OFFSET + TRANSACTION_SIZE
 const PRIMARY_BIT: u8 = 1;
 const RECOVERY_REQUIRED: u8 = 2;
 const TWO_PHASE_COMMIT: u8 = 4;
+const ENCRYPTED: u8 = 8;
+const KNOWN_FLAGS: u8 = PRIMARY_BIT | RECOVERY_REQUIRED | TWO_PHASE_COMMIT | ENCRYPTED;
 
 // Structure of each commit slot
 const VERSION_OFFSET: usize = 0;
@@ -97,6 +102,8 @@ pub(super) struct DatabaseHeader {
     primary_slot: usize,
     pub(super) recovery_required: bool,
     pub(super) two_phase_commit: bool,
+    // Identifies the encryption key, if the database is encrypted
+    pub(super) key_check: Option<u128>,
     page_size: u32,
     region_header_pages: u32,
     region_max_data_pages: u32,
@@ -110,6 +117,7 @@ impl DatabaseHeader {
         #[allow(clippy::assertions_on_constants)]
         {
             assert!(TRANSACTION_LAST_FIELD <= SLOT_CHECKSUM_OFFSET);
+            assert!(KEY_CHECK_OFFSET + size_of::<u128>() <= TRANSACTION_0_OFFSET);
         }
 
         let slot = TransactionHeader::new(transaction_id);
@@ -117,6 +125,7 @@ impl DatabaseHeader {
             primary_slot: 0,
             recovery_required: true,
             two_phase_commit: false,
+            key_check: None,
             page_size: layout.full_region_layout().page_size(),
             region_header_pages: layout.full_region_layout().get_header_pages(),
             region_max_data_pages: layout.full_region_layout().num_pages(),
@@ -230,9 +239,34 @@ impl DatabaseHeader {
     pub(super) fn from_bytes(data: &[u8]) -> Result<(Self, HeaderRepairInfo), DatabaseError> {
         let invalid_magic_number = data[..MAGICNUMBER.len()] != MAGICNUMBER;
 
+        if !invalid_magic_number && data[GOD_BYTE_OFFSET] & !KNOWN_FLAGS != 0 {
+            return Err(StorageError::Corrupted(format!(
+                "Unknown flags in god byte: {:#x}",
+                data[GOD_BYTE_OFFSET] & !KNOWN_FLAGS
+            ))
+            .into());
+        }
         let primary_slot = usize::from(data[GOD_BYTE_OFFSET] & PRIMARY_BIT != 0);
         let recovery_required = (data[GOD_BYTE_OFFSET] & RECOVERY_REQUIRED) != 0;
         let two_phase_commit = (data[GOD_BYTE_OFFSET] & TWO_PHASE_COMMIT) != 0;
+        let raw_key_check = u128::from_le_bytes(
+            data[KEY_CHECK_OFFSET..(KEY_CHECK_OFFSET + size_of::<u128>())]
+                .try_into()
+                .unwrap(),
+        );
+        let key_check = if (data[GOD_BYTE_OFFSET] & ENCRYPTED) != 0 {
+            Some(raw_key_check)
+        } else if raw_key_check != 0 {
+            // Version 4 does not imply encryption, so the key check is what prevents an encrypted
+            // database, whose flag was lost, from being read as plaintext
+            return Err(StorageError::Corrupted(
+                "Encryption key check found, but the database is not flagged as encrypted"
+                    .to_string(),
+            )
+            .into());
+        } else {
+            None
+        };
         let page_size = get_u32(&data[PAGE_SIZE_OFFSET..]);
         let region_header_pages = get_u32(&data[REGION_HEADER_PAGES_OFFSET..]);
         let region_max_data_pages = get_u32(&data[REGION_MAX_DATA_PAGES_OFFSET..]);
@@ -240,9 +274,11 @@ impl DatabaseHeader {
         let trailing_data_pages = get_u32(&data[TRAILING_REGION_DATA_PAGES_OFFSET..]);
         let (slot0, slot0_corrupted) = TransactionHeader::from_bytes(
             &data[TRANSACTION_0_OFFSET..(TRANSACTION_0_OFFSET + TRANSACTION_SIZE)],
+            key_check.is_some(),
         )?;
         let (slot1, slot1_corrupted) = TransactionHeader::from_bytes(
             &data[TRANSACTION_1_OFFSET..(TRANSACTION_1_OFFSET + TRANSACTION_SIZE)],
+            key_check.is_some(),
         )?;
         let (primary_corrupted, secondary_corrupted) = if primary_slot == 0 {
             (slot0_corrupted, slot1_corrupted)
@@ -254,6 +290,7 @@ impl DatabaseHeader {
             primary_slot,
             recovery_required,
             two_phase_commit,
+            key_check,
             page_size,
             region_header_pages,
             region_max_data_pages,
@@ -281,6 +318,11 @@ impl DatabaseHeader {
         if self.two_phase_commit {
             result[GOD_BYTE_OFFSET] |= TWO_PHASE_COMMIT;
         }
+        if let Some(key_check) = self.key_check {
+            result[GOD_BYTE_OFFSET] |= ENCRYPTED;
+            result[KEY_CHECK_OFFSET..(KEY_CHECK_OFFSET + size_of::<u128>())]
+                .copy_from_slice(&key_check.to_le_bytes());
+        }
         result[PAGE_SIZE_OFFSET..(PAGE_SIZE_OFFSET + size_of::<u32>())]
             .copy_from_slice(&self.page_size.to_le_bytes());
         result[REGION_HEADER_PAGES_OFFSET..(REGION_HEADER_PAGES_OFFSET + size_of::<u32>())]
@@ -292,9 +334,10 @@ impl DatabaseHeader {
         result[TRAILING_REGION_DATA_PAGES_OFFSET
             ..(TRAILING_REGION_DATA_PAGES_OFFSET + size_of::<u32>())]
             .copy_from_slice(&self.trailing_partial_region_pages.to_le_bytes());
-        let slot0 = self.transaction_slots[0].to_bytes();
+        let encrypted = self.key_check.is_some();
+        let slot0 = self.transaction_slots[0].to_bytes(encrypted);
         result[TRANSACTION_0_OFFSET..(TRANSACTION_0_OFFSET + slot0.len())].copy_from_slice(&slot0);
-        let slot1 = self.transaction_slots[1].to_bytes();
+        let slot1 = self.transaction_slots[1].to_bytes(encrypted);
         result[TRANSACTION_1_OFFSET..(TRANSACTION_1_OFFSET + slot1.len())].copy_from_slice(&slot1);
 
         result
@@ -312,7 +355,7 @@ pub(super) struct TransactionHeader {
 impl TransactionHeader {
     fn new(transaction_id: TransactionId) -> Self {
         Self {
-            version: FILE_FORMAT_VERSION3,
+            version: FILE_FORMAT_VERSION4,
             user_root: None,
             system_root: None,
             transaction_id,
@@ -320,16 +363,23 @@ impl TransactionHeader {
     }
 
     // Returned bool indicates whether the checksum was corrupted
-    pub(super) fn from_bytes(data: &[u8]) -> Result<(Self, bool), DatabaseError> {
+    pub(super) fn from_bytes(data: &[u8], encrypted: bool) -> Result<(Self, bool), DatabaseError> {
         let version = data[VERSION_OFFSET];
         match version {
             FILE_FORMAT_VERSION1 | FILE_FORMAT_VERSION2 => {
                 return Err(DatabaseError::UpgradeRequired(version));
             }
-            FILE_FORMAT_VERSION3 => {}
+            FILE_FORMAT_VERSION3 if !encrypted => {}
+            FILE_FORMAT_VERSION4 => {}
+            FILE_FORMAT_VERSION3 => {
+                return Err(StorageError::Corrupted(format!(
+                    "File format version {version} does not support encryption",
+                ))
+                .into());
+            }
             _ => {
                 return Err(StorageError::Corrupted(format!(
-                    "Expected file format version <= {FILE_FORMAT_VERSION3}, found {version}",
+                    "Expected file format version <= {FILE_FORMAT_VERSION4}, found {version}",
                 ))
                 .into());
             }
@@ -371,8 +421,8 @@ impl TransactionHeader {
         Ok((result, corrupted))
     }
 
-    pub(super) fn to_bytes(&self) -> [u8; TRANSACTION_SIZE] {
-        assert_eq!(self.version, FILE_FORMAT_VERSION3);
+    pub(super) fn to_bytes(&self, encrypted: bool) -> [u8; TRANSACTION_SIZE] {
+        assert!(self.version == FILE_FORMAT_VERSION4 || !encrypted);
         let mut result = [0; TRANSACTION_SIZE];
         result[VERSION_OFFSET] = self.version;
         if let Some(header) = self.user_root {
@@ -473,6 +523,9 @@ mod test {
                 None,
                 0,
                 0,
+                None,
+                None,
+                None,
             )
             .unwrap()
             .needs_repair()
@@ -565,6 +618,9 @@ mod test {
                 None,
                 0,
                 0,
+                None,
+                None,
+                None,
             )
             .unwrap()
             .needs_repair()
@@ -632,6 +688,9 @@ mod test {
                 None,
                 0,
                 0,
+                None,
+                None,
+                None,
             )
             .unwrap()
             .needs_repair()
@@ -691,6 +750,9 @@ mod test {
                 None,
                 0,
                 0,
+                None,
+                None,
+                None,
             )
             .unwrap()
             .needs_repair()
@@ -1,10 +1,17 @@
 use crate::StorageBackend;
 use std::io;
-use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 
 /// Acts as temporal in-memory database storage.
+///
+/// The image of a database stored in this backend can be retrieved after the
+/// [`crate::Database`] has been closed, with a handle returned by [`InMemoryBackend::image`].
 #[derive(Debug, Default)]
-pub struct InMemoryBackend(RwLock<Vec<u8>>);
+pub struct InMemoryBackend {
+    data: Arc<RwLock<Vec<u8>>>,
+    // Only held by the backend, so that handles can tell whether it has been dropped
+    live: Arc<()>,
+}
 
 impl InMemoryBackend {
     fn out_of_range() -> io::Error {
@@ -18,14 +25,86 @@ impl InMemoryBackend {
         Self::default()
     }
 
+    /// Creates a memory backend which contains the given image of a database, such as one returned
+    /// by [`InMemoryImage::into_bytes`], or the contents of a database file.
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self {
+            data: Arc::new(RwLock::new(data)),
+            live: Arc::new(()),
+        }
+    }
+
+    /// Returns a read-only handle to the contents of this backend, which can be retrieved once the
+    /// backend has been dropped.
+    pub fn image(&self) -> InMemoryImage {
+        InMemoryImage {
+            data: self.data.clone(),
+            live: Arc::downgrade(&self.live),
+        }
+    }
+
+    /// Returns the contents of this backend. They are copied if an [`InMemoryImage`] handle to
+    /// this backend exists.
+    pub fn into_bytes(self) -> Vec<u8> {
+        match Arc::try_unwrap(self.data) {
+            Ok(lock) => lock.into_inner().expect("Could not acquire read lock."),
+            Err(shared) => shared.read().expect("Could not acquire read lock.").clone(),
+        }
+    }
+
     /// Gets a read guard for this backend.
     fn read(&self) -> RwLockReadGuard<'_, Vec<u8>> {
-        self.0.read().expect("Could not acquire read lock.")
+        self.data.read().expect("Could not acquire read lock.")
     }
 
     /// Gets a write guard for this backend.
     fn write(&self) -> RwLockWriteGuard<'_, Vec<u8>> {
-        self.0.write().expect("Could not acquire write lock.")
+        self.data.write().expect("Could not acquire write lock.")
+    }
+}
+
+/// A read-only handle to the contents of an [`InMemoryBackend`], returned by
+/// [`InMemoryBackend::image`].
+///
+/// The contents are only available once the backend has been dropped, which happens when the
+/// [`crate::Database`] using it is closed. They are then an image of the database which can be
+/// reopened without repair, either with [`InMemoryBackend::from_bytes`] or by writing it to a
+/// file.
+#[derive(Debug)]
+pub struct InMemoryImage {
+    data: Arc<RwLock<Vec<u8>>>,
+    live: Weak<()>,
+}
+
+impl InMemoryImage {
+    /// Returns `true` if the backend has been dropped, so that its contents are available.
+    pub fn is_closed(&self) -> bool {
+        self.live.strong_count() == 0
+    }
+
+    /// Returns a copy of the contents of the backend, or `None` if it is still in use.
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        if !self.is_closed() {
+            return None;
+        }
+        Some(
+            self.data
+                .read()
+                .expect("Could not acquire read lock.")
+                .clone(),
+        )
+    }
+
+    /// Returns the contents of the backend, or this handle if the backend is still in use. They
+    /// are only copied if another handle to the same backend exists.
+    pub fn into_bytes(self) -> Result<Vec<u8>, Self> {
+        if !self.is_closed() {
+            return Err(self);
+        }
+        match Arc::try_unwrap(self.data) {
+            Ok(lock) => Ok(lock.into_inner().expect("Could not acquire read lock.")),
+            Err(shared) => Ok(shared.read().expect("Could not acquire read lock.").clone()),
+        }
     }
 }
 
@@ -44,6 +123,17 @@ impl StorageBackend for InMemoryBackend {
         }
     }
 
+    fn read_into(&self, offset: u64, buffer: &mut [u8]) -> Result<(), io::Error> {
+        let guard = self.read();
+        let offset = usize::try_from(offset).map_err(|_| Self::out_of_range())?;
+        if offset + buffer.len() <= guard.len() {
+            buffer.copy_from_slice(&guard[offset..offset + buffer.len()]);
+            Ok(())
+        } else {
+            Err(Self::out_of_range())
+        }
+    }
+
     fn set_len(&self, len: u64) -> Result<(), io::Error> {
         let mut guard = self.write();
         let len = usize::try_from(len).map_err(|_| Self::out_of_range())?;
@@ -74,4 +164,17 @@ impl StorageBackend for InMemoryBackend {
             Err(Self::out_of_range())
         }
     }
+
+    fn write_vectored(&self, writes: &[(u64, &[u8])]) -> Result<(), io::Error> {
+        let mut guard = self.write();
+        for (offset, data) in writes {
+            let offset = usize::try_from(*offset).map_err(|_| Self::out_of_range())?;
+            if offset + data.len() <= guard.len() {
+                guard[offset..offset + data.len()].copy_from_slice(data);
+            } else {
+                return Err(Self::out_of_range());
+            }
+        }
+        Ok(())
+    }
 }
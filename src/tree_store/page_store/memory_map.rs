@@ -0,0 +1,121 @@
+use crate::db::TransactionGuard;
+use std::cmp::max;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::sync::{Arc, RwLock};
+use std::{ptr, slice};
+
+// A read-only mapping of the database file, which allows committed pages to be read without
+// copying them into the read cache
+pub(super) struct MemoryMap {
+    // Shares the open file description, and therefore the lock, of the database file. Every
+    // mapping holds it, so that the database can't be reopened while mapped pages are referenced
+    file: Arc<File>,
+    mapping: RwLock<Option<Arc<Mapping>>>,
+}
+
+impl MemoryMap {
+    pub(super) fn new(file: File) -> Self {
+        Self {
+            file: Arc::new(file),
+            mapping: RwLock::new(None),
+        }
+    }
+
+    // `pin` must be the guard of a read transaction whose snapshot contains the page. It is held
+    // until the returned page is dropped, so that the page is not freed and overwritten while it
+    // is referenced
+    pub(super) fn page(
+        &self,
+        offset: u64,
+        len: usize,
+        pin: &Arc<TransactionGuard>,
+    ) -> io::Result<MappedPage> {
+        let mapping = self.mapping_covering(offset + len as u64)?;
+        Ok(MappedPage {
+            mapping,
+            offset: offset.try_into().unwrap(),
+            len,
+            _pin: pin.clone(),
+        })
+    }
+
+    fn mapping_covering(&self, end: u64) -> io::Result<Arc<Mapping>> {
+        if let Some(mapping) = self.mapping.read().unwrap().as_ref() {
+            if mapping.len as u64 >= end {
+                return Ok(mapping.clone());
+            }
+        }
+
+        let mut current = self.mapping.write().unwrap();
+        let current_len = if let Some(mapping) = current.as_ref() {
+            if mapping.len as u64 >= end {
+                return Ok(mapping.clone());
+            }
+            mapping.len as u64
+        } else {
+            0
+        };
+        // Map more than is needed, so that the file can grow without being remapped every time.
+        // A replaced mapping is unmapped once all of its pages have been dropped
+        let len: usize = max(end, current_len * 2).try_into().unwrap();
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                self.file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let mapping = Arc::new(Mapping {
+            ptr,
+            len,
+            _file: self.file.clone(),
+        });
+        *current = Some(mapping.clone());
+
+        Ok(mapping)
+    }
+}
+
+struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+    _file: Arc<File>,
+}
+
+// The mapping is read-only, and is only unmapped when dropped
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct MappedPage {
+    mapping: Arc<Mapping>,
+    offset: usize,
+    len: usize,
+    _pin: Arc<TransactionGuard>,
+}
+
+impl MappedPage {
+    pub(super) fn memory(&self) -> &[u8] {
+        // The page is within the file, and is not written while it is pinned
+        unsafe {
+            slice::from_raw_parts(
+                self.mapping.ptr.cast::<u8>().cast_const().add(self.offset),
+                self.len,
+            )
+        }
+    }
+}
@@ -7,9 +7,12 @@ mod header;
 mod in_memory_backend;
 mod layout;
 mod lru_cache;
+#[cfg(target_os = "linux")]
+mod memory_map;
 mod page_manager;
 mod region;
 mod savepoint;
+mod write_ahead_log;
 #[allow(clippy::pedantic, dead_code)]
 mod xxh3;
 
@@ -17,10 +20,11 @@ pub(crate) use base::{
     MAX_PAIR_LENGTH, MAX_VALUE_LENGTH, Page, PageHint, PageNumber, PageTrackerPolicy,
 };
 pub(crate) use header::PAGE_SIZE;
-pub use in_memory_backend::InMemoryBackend;
+pub use in_memory_backend::{InMemoryBackend, InMemoryImage};
 pub(crate) use page_manager::{FILE_FORMAT_VERSION3, TransactionalMemory, xxh3_checksum};
 pub use savepoint::Savepoint;
 pub(crate) use savepoint::SerializedSavepoint;
 
-pub(super) use base::{PageImpl, PageMut};
+pub(crate) use base::PageImpl;
+pub(super) use base::PageMut;
 pub(super) use xxh3::hash128_with_seed;
@@ -1,26 +1,28 @@
+use crate::db::TransactionGuard;
 use crate::transaction_tracker::TransactionId;
 use crate::transactions::{AllocatorStateKey, AllocatorStateTree};
 use crate::tree_store::btree_base::{BtreeHeader, Checksum};
-use crate::tree_store::page_store::base::{MAX_PAGE_INDEX, PageHint};
+use crate::tree_store::page_store::base::{MAX_PAGE_INDEX, PageBuffer, PageHint};
 use crate::tree_store::page_store::buddy_allocator::BuddyAllocator;
 use crate::tree_store::page_store::cached_file::PagedCachedFile;
 use crate::tree_store::page_store::header::{DB_HEADER_SIZE, DatabaseHeader, MAGICNUMBER};
 use crate::tree_store::page_store::layout::DatabaseLayout;
 use crate::tree_store::page_store::region::{Allocators, RegionTracker};
+use crate::tree_store::page_store::write_ahead_log::{CHECKPOINT_BYTES, WriteAheadLog};
 use crate::tree_store::page_store::{PageImpl, PageMut, hash128_with_seed};
 use crate::tree_store::{Page, PageNumber, PageTrackerPolicy};
-use crate::{CacheStats, StorageBackend};
+use crate::{CacheStats, PageEncryption, StorageBackend};
 use crate::{DatabaseError, Result, StorageError};
 use std::cmp::{max, min};
 #[cfg(debug_assertions)]
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::convert::TryInto;
+use std::fs::File;
 use std::io::ErrorKind;
-#[cfg(debug_assertions)]
-use std::sync::Arc;
-use std::sync::Mutex;
+use std::mem;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
 
 // Regions have a maximum size of 4GiB. A `4GiB - overhead` value is the largest that can be represented,
@@ -45,6 +47,10 @@ pub(crate) const FILE_FORMAT_VERSION2: u8 = 2;
 //   This is a system table. It is only written when a savepoint exists
 // * New persistent savepoint format
 pub(crate) const FILE_FORMAT_VERSION3: u8 = 3;
+// New file format:
+// * Branch pages store the number of entries below each child
+// * Pages may be encrypted. Encrypted databases always use this version
+pub(crate) const FILE_FORMAT_VERSION4: u8 = 4;
 
 fn ceil_log2(x: usize) -> u8 {
     if x.is_power_of_two() {
@@ -91,6 +97,7 @@ pub(crate) struct TransactionalMemory {
     // TODO: maybe we can remove this flag now that CheckedBackend exists?
     needs_recovery: AtomicBool,
     storage: PagedCachedFile,
+    write_ahead_log: Option<Mutex<WriteAheadLog>>,
     state: Mutex<InMemoryState>,
     // The number of PageMut which are outstanding
     #[cfg(debug_assertions)]
@@ -100,7 +107,14 @@ pub(crate) struct TransactionalMemory {
     read_page_ref_counts: Arc<Mutex<HashMap<PageNumber, u64>>>,
     // Indicates that a non-durable commit has been made, so reads should be served from the secondary meta page
     read_from_secondary: AtomicBool,
+    // Held while the header is written and synced, so that commit() and group syncs do not race
+    commit_lock: Mutex<()>,
+    // True while a thread is syncing non-durable commits on behalf of a group commit
+    group_syncing: Mutex<bool>,
+    group_sync_complete: Condvar,
     page_size: u32,
+    // True if branch pages store subtree counts. Fixed by the file format version
+    subtree_counts: bool,
     // We store these separately from the layout because they're static, and accessed on the get_page()
     // code path where there is no locking
     region_size: u64,
@@ -117,6 +131,9 @@ impl TransactionalMemory {
         requested_region_size: Option<u64>,
         read_cache_size_bytes: usize,
         write_cache_size_bytes: usize,
+        write_ahead_log: Option<Box<dyn StorageBackend>>,
+        encryption: Option<Arc<dyn PageEncryption>>,
+        memory_map: Option<File>,
     ) -> Result<Self, DatabaseError> {
         assert!(page_size.is_power_of_two() && page_size >= DB_HEADER_SIZE);
 
@@ -132,6 +149,8 @@ impl TransactionalMemory {
             page_size as u64,
             read_cache_size_bytes,
             write_cache_size_bytes,
+            encryption,
+            memory_map,
         )?;
 
         let initial_storage_len = storage.raw_file_len()?;
@@ -191,6 +210,7 @@ impl TransactionalMemory {
 
             let mut header = DatabaseHeader::new(layout, TransactionId::new(0));
 
+            header.key_check = storage.encryption_key_check()?;
             header.recovery_required = false;
             header.two_phase_commit = true;
             storage
@@ -207,6 +227,21 @@ impl TransactionalMemory {
                 .copy_from_slice(&header.to_bytes(true));
             storage.flush(false)?;
         }
+
+        // Check the key before anything is decrypted, including the write-ahead log
+        let (header, _) = DatabaseHeader::from_bytes(&storage.read_direct(0, DB_HEADER_SIZE)?)?;
+        if header.key_check != storage.encryption_key_check()? {
+            return Err(DatabaseError::EncryptionKeyMismatch);
+        }
+
+        let write_ahead_log = if let Some(file) = write_ahead_log {
+            let mut log = WriteAheadLog::new(file)?;
+            log.replay(&storage)?;
+            Some(Mutex::new(log))
+        } else {
+            None
+        };
+
         let header_bytes = storage.read_direct(0, DB_HEADER_SIZE)?;
         let (mut header, repair_info) = DatabaseHeader::from_bytes(&header_bytes)?;
 
@@ -237,6 +272,7 @@ impl TransactionalMemory {
         assert_eq!(layout.len(), storage.raw_file_len()?);
         let region_size = layout.full_region_layout().len();
         let region_header_size = layout.full_region_layout().data_section().start;
+        let subtree_counts = header.primary_slot().version >= FILE_FORMAT_VERSION4;
         let state = InMemoryState::new(header);
 
         assert!(page_size >= DB_HEADER_SIZE);
@@ -245,13 +281,18 @@ impl TransactionalMemory {
             allocated_since_commit: Mutex::new(HashSet::new()),
             needs_recovery: AtomicBool::new(needs_recovery),
             storage,
+            write_ahead_log,
             state: Mutex::new(state),
             #[cfg(debug_assertions)]
             open_dirty_pages: Arc::new(Mutex::new(HashSet::new())),
             #[cfg(debug_assertions)]
             read_page_ref_counts: Arc::new(Mutex::new(HashMap::new())),
             read_from_secondary: AtomicBool::new(false),
+            commit_lock: Mutex::new(()),
+            group_syncing: Mutex::new(false),
+            group_sync_complete: Condvar::new(),
             page_size: page_size.try_into().unwrap(),
+            subtree_counts,
             region_size,
             region_header_with_padding_size: region_header_size,
         })
@@ -262,7 +303,19 @@ impl TransactionalMemory {
     }
 
     pub(crate) fn check_io_errors(&self) -> Result {
-        self.storage.check_io_errors()
+        self.storage.check_io_errors()?;
+        if let Some(log) = &self.write_ahead_log {
+            log.lock().unwrap().check_io_errors()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn has_write_ahead_log(&self) -> bool {
+        self.write_ahead_log.is_some()
+    }
+
+    pub(crate) fn encryption(&self) -> Option<Arc<dyn PageEncryption>> {
+        self.storage.encryption()
     }
 
     #[cfg(any(test, fuzzing))]
@@ -523,6 +576,7 @@ impl TransactionalMemory {
         debug_assert!(self.open_dirty_pages.lock().unwrap().is_empty());
         assert!(!self.needs_recovery.load(Ordering::Acquire));
 
+        let _commit_guard = self.commit_lock.lock().unwrap();
         let mut state = self.state.lock().unwrap();
         // Trim surplus file space, before finalizing the commit
         let shrunk = Self::try_shrink(&mut state)?;
@@ -552,6 +606,12 @@ impl TransactionalMemory {
         self.write_header(&header)?;
         self.storage.flush(eventual)?;
 
+        // This commit includes every logged commit, so the log is no longer needed
+        if let Some(log) = &self.write_ahead_log {
+            assert!(!eventual);
+            log.lock().unwrap().truncate()?;
+        }
+
         if shrunk {
             let result = self.storage.resize(header.layout().len());
             if result.is_err() {
@@ -606,6 +666,223 @@ impl TransactionalMemory {
         Ok(())
     }
 
+    // Wait until the given transaction, which must already have been committed with
+    // non_durable_commit(), is durable. If no other thread is syncing, this thread becomes the
+    // leader and makes all the pending non-durable commits durable with a single sync.
+    // Returns the id of the last transaction made durable, if this thread performed the sync
+    pub(crate) fn group_sync(
+        &self,
+        transaction_id: TransactionId,
+    ) -> Result<Option<TransactionId>> {
+        let mut syncing = self.group_syncing.lock().unwrap();
+        loop {
+            if self.get_durable_transaction_id() >= transaction_id {
+                return Ok(None);
+            }
+            if !*syncing {
+                break;
+            }
+            syncing = self.group_sync_complete.wait(syncing).unwrap();
+        }
+        *syncing = true;
+        drop(syncing);
+
+        let result = self.sync_non_durable_commits();
+        if result.is_err() {
+            self.needs_recovery.store(true, Ordering::Release);
+        }
+
+        *self.group_syncing.lock().unwrap() = false;
+        self.group_sync_complete.notify_all();
+
+        result
+    }
+
+    fn get_durable_transaction_id(&self) -> TransactionId {
+        self.state
+            .lock()
+            .unwrap()
+            .header
+            .primary_slot()
+            .transaction_id
+    }
+
+    // Make the latest non-durable commit durable, by promoting it to the primary slot. This may
+    // run concurrently with a write transaction, so unlike commit() it must not flush the write
+    // buffer, which may contain pages that are still being written. Those pages are not part of
+    // any committed state, because non_durable_commit() issues a write barrier
+    fn sync_non_durable_commits(&self) -> Result<Option<TransactionId>> {
+        let _commit_guard = self.commit_lock.lock().unwrap();
+        let state = self.state.lock().unwrap();
+        if !self.read_from_secondary.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        let mut header = state.header.clone();
+        drop(state);
+
+        header.swap_primary_slot();
+        header.two_phase_commit = false;
+        let synced = header.primary_slot().clone();
+        self.storage.write_direct(0, &header.to_bytes(true))?;
+        self.storage.sync_written()?;
+
+        let mut state = self.state.lock().unwrap();
+        // Another non-durable commit may have been made while syncing. If so, it remains pending
+        // in the new secondary slot
+        let latest = state.header.secondary_slot().clone();
+        *state.header.secondary_slot_mut() = synced.clone();
+        state.header.swap_primary_slot();
+        state.header.two_phase_commit = false;
+        if latest.transaction_id == synced.transaction_id {
+            self.read_from_secondary.store(false, Ordering::Release);
+        } else {
+            *state.header.secondary_slot_mut() = latest;
+        }
+        drop(state);
+
+        Ok(Some(synced.transaction_id))
+    }
+
+    // Make changes visible, and append them to the write-ahead log. The changes are durable once
+    // the log is synced, even though the database file is not synced until the next checkpoint.
+    // Returns true if the log has grown large enough that it should be checkpointed, by calling
+    // checkpoint_logged_commits() once the write lock is released
+    pub(crate) fn logged_commit(
+        &self,
+        data_root: Option<BtreeHeader>,
+        system_root: Option<BtreeHeader>,
+        transaction_id: TransactionId,
+        sync: bool,
+        eventual: bool,
+    ) -> Result<bool> {
+        let result =
+            self.logged_commit_inner(data_root, system_root, transaction_id, sync, eventual);
+        if result.is_err() {
+            self.needs_recovery.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    fn logged_commit_inner(
+        &self,
+        data_root: Option<BtreeHeader>,
+        system_root: Option<BtreeHeader>,
+        transaction_id: TransactionId,
+        sync: bool,
+        eventual: bool,
+    ) -> Result<bool> {
+        #[cfg(debug_assertions)]
+        debug_assert!(self.open_dirty_pages.lock().unwrap().is_empty());
+        assert!(!self.needs_recovery.load(Ordering::Acquire));
+
+        self.storage.write_barrier()?;
+
+        // The header that makes this transaction the primary, for use by recovery
+        let mut header = self.state.lock().unwrap().header.clone();
+        let secondary = header.secondary_slot_mut();
+        secondary.transaction_id = transaction_id;
+        secondary.user_root = data_root;
+        secondary.system_root = system_root;
+        header.swap_primary_slot();
+        header.two_phase_commit = false;
+
+        // Every page written by this transaction was allocated by it, since pages are copied on write
+        let written: Vec<PageNumber> = self
+            .allocated_since_commit
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect();
+        let mut pages = vec![];
+        for page in written {
+            let range = page.address_range(
+                self.page_size.into(),
+                self.region_size,
+                self.region_header_with_padding_size,
+                self.page_size,
+            );
+            let len: usize = (range.end - range.start).try_into().unwrap();
+            let mut data = self.storage.read(range.start, len, PageHint::None)?;
+            if self.storage.encryption().is_some() {
+                // The log stores pages as they are stored in the database file
+                let mut encrypted = data.to_vec();
+                self.storage.encrypt(range.start, &mut encrypted)?;
+                data = encrypted.into();
+            }
+            pages.push((range.start, data));
+        }
+
+        let mut log = self.write_ahead_log.as_ref().unwrap().lock().unwrap();
+        log.append(transaction_id, &pages, &header.to_bytes(true))?;
+        if sync {
+            log.sync(eventual)?;
+        }
+        let checkpoint = log.len() >= CHECKPOINT_BYTES;
+        drop(log);
+
+        self.non_durable_commit(data_root, system_root, transaction_id)?;
+
+        Ok(checkpoint)
+    }
+
+    // Make the logged commits durable in the database file, and empty the write-ahead log if no
+    // newer commit has been appended to it. Unlike checkpoint(), this may run concurrently with a
+    // write transaction, so it must not flush the write buffer. The pages of every logged commit
+    // already left it, because logged_commit() issues a write barrier.
+    // Returns the id of the last transaction made durable, if this thread performed the sync
+    pub(crate) fn checkpoint_logged_commits(&self) -> Result<Option<TransactionId>> {
+        let result = self.checkpoint_logged_commits_inner();
+        if result.is_err() {
+            self.needs_recovery.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    fn checkpoint_logged_commits_inner(&self) -> Result<Option<TransactionId>> {
+        let Some(log) = &self.write_ahead_log else {
+            return Ok(None);
+        };
+        // As in checkpoint_inner(), the pages must be synced before the header
+        self.storage.sync_written()?;
+        let synced = self.sync_non_durable_commits()?;
+        let durable = self.get_durable_transaction_id();
+        let mut log = log.lock().unwrap();
+        // A commit which was appended after the sync is only durable in the log
+        if log
+            .last_transaction()
+            .is_none_or(|transaction_id| transaction_id <= durable)
+        {
+            log.truncate()?;
+        }
+        Ok(synced)
+    }
+
+    // Make all logged commits durable in the database file, and empty the write-ahead log. The
+    // caller must hold the write lock
+    pub(crate) fn checkpoint(&self) -> Result {
+        let result = self.checkpoint_inner();
+        if result.is_err() {
+            self.needs_recovery.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    fn checkpoint_inner(&self) -> Result {
+        let Some(log) = &self.write_ahead_log else {
+            return Ok(());
+        };
+        let mut log = log.lock().unwrap();
+        if log.len() == 0 {
+            return Ok(());
+        }
+        // Sync the pages before the header, so that the primary never references pages which are
+        // not durable. Recovery relies on this to find the first record to replay
+        self.storage.flush(false)?;
+        self.sync_non_durable_commits()?;
+        log.truncate()
+    }
+
     pub(crate) fn rollback_uncommitted_writes(&self) -> Result {
         let result = self.rollback_uncommitted_writes_inner();
         if result.is_err() {
@@ -667,8 +944,35 @@ impl TransactionalMemory {
             self.page_size,
         );
         let len: usize = (range.end - range.start).try_into().unwrap();
-        let mem = self.storage.read(range.start, len, hint)?;
+        let mem = PageBuffer::Owned(self.storage.read(range.start, len, hint)?);
+
+        Ok(self.track_read_page(page_number, mem))
+    }
+
+    // Get a committed page for a read transaction. If reads are memory mapped, the page borrows
+    // from the mapping, and keeps `guard` alive so that the page is not freed while it is in use
+    pub(crate) fn get_page_pinned(
+        &self,
+        page_number: PageNumber,
+        guard: &Arc<TransactionGuard>,
+    ) -> Result<PageImpl> {
+        let range = page_number.address_range(
+            self.page_size.into(),
+            self.region_size,
+            self.region_header_with_padding_size,
+            self.page_size,
+        );
+        let len: usize = (range.end - range.start).try_into().unwrap();
+        let mem = if let Some(mem) = self.storage.read_mapped(range.start, len, guard)? {
+            mem
+        } else {
+            PageBuffer::Owned(self.storage.read(range.start, len, PageHint::Clean)?)
+        };
 
+        Ok(self.track_read_page(page_number, mem))
+    }
+
+    fn track_read_page(&self, page_number: PageNumber, mem: PageBuffer) -> PageImpl {
         // We must not retrieve an immutable reference to a page which already has a mutable ref to it
         #[cfg(debug_assertions)]
         {
@@ -683,12 +987,12 @@ impl TransactionalMemory {
             drop(dirty_pages);
         }
 
-        Ok(PageImpl {
+        PageImpl {
             mem,
             page_number,
             #[cfg(debug_assertions)]
             open_pages: self.read_page_ref_counts.clone(),
-        })
+        }
     }
 
     // NOTE: the caller must ensure that the read cache has been invalidated or stale reads my occur
@@ -729,6 +1033,10 @@ impl TransactionalMemory {
         })
     }
 
+    pub(crate) fn stores_subtree_counts(&self) -> bool {
+        self.subtree_counts
+    }
+
     pub(crate) fn get_version(&self) -> u8 {
         let state = self.state.lock().unwrap();
         if self.read_from_secondary.load(Ordering::Acquire) {
@@ -810,6 +1118,36 @@ impl TransactionalMemory {
         }
     }
 
+    // Treats the pages allocated since the last commit as committed, so that they are copied
+    // rather than modified or freed. The returned pages must be passed to either
+    // unfreeze_uncommitted() or rollback_to_frozen()
+    pub(crate) fn freeze_uncommitted(&self) -> HashSet<PageNumber> {
+        mem::take(&mut *self.allocated_since_commit.lock().unwrap())
+    }
+
+    // Whether any pages have been allocated since the last commit, and not frozen
+    pub(crate) fn any_uncommitted(&self) -> bool {
+        !self.allocated_since_commit.lock().unwrap().is_empty()
+    }
+
+    pub(crate) fn unfreeze_uncommitted(&self, frozen: HashSet<PageNumber>) {
+        self.allocated_since_commit.lock().unwrap().extend(frozen);
+    }
+
+    // Frees all the pages allocated since freeze_uncommitted() returned `frozen`, and unfreezes them
+    pub(crate) fn rollback_to_frozen(
+        &self,
+        frozen: HashSet<PageNumber>,
+        allocated: &mut PageTrackerPolicy,
+    ) {
+        let pages = mem::replace(&mut *self.allocated_since_commit.lock().unwrap(), frozen);
+        for page in pages {
+            // Pages allocated by the system tree are not tracked
+            allocated.remove_if_tracked(page);
+            self.free_helper(page, &mut PageTrackerPolicy::Ignore);
+        }
+    }
+
     // Page has not been committed
     pub(crate) fn uncommitted(&self, page: PageNumber) -> bool {
         self.allocated_since_commit.lock().unwrap().contains(&page)
@@ -1035,6 +1373,9 @@ impl TransactionalMemory {
         }
 
         self.storage.close()?;
+        if let Some(log) = &self.write_ahead_log {
+            log.lock().unwrap().close()?;
+        }
 
         Ok(())
     }
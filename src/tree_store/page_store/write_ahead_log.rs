@@ -0,0 +1,301 @@
+use crate::transaction_tracker::TransactionId;
+use crate::tree_store::page_store::cached_file::{CheckedBackend, PagedCachedFile};
+use crate::tree_store::page_store::header::{DB_HEADER_SIZE, DatabaseHeader};
+use crate::tree_store::page_store::xxh3_checksum;
+use crate::{DatabaseError, Result, StorageBackend};
+use std::sync::Arc;
+
+// The log is checkpointed after the first commit which grows it past this size
+pub(super) const CHECKPOINT_BYTES: u64 = 64 * 1024 * 1024;
+// Records are written to the backend in chunks of at most this size
+const WRITE_CHUNK_BYTES: usize = 1024 * 1024;
+// Record length, transaction id, and page count
+const RECORD_FIELDS_SIZE: usize = 24;
+const PAGE_FIELDS_SIZE: usize = 16;
+const CHECKSUM_SIZE: usize = 16;
+
+// A log of the pages written by each commit. A commit is made durable by appending its pages to the
+// log and syncing the log, instead of syncing the database file. A checkpoint syncs the database
+// file, and then empties the log.
+//
+// Each record has the format:
+// * 8 bytes: length of the record, including this field and the checksum
+// * 8 bytes: transaction id
+// * 8 bytes: number of pages
+// * for each page: 8 bytes offset in the database file, 8 bytes length, and then the page contents
+// * DB_HEADER_SIZE bytes: the database header, with the transaction in the primary slot
+// * 16 bytes: checksum of the record. See RecordChecksum
+//
+// All integers are little-endian
+pub(super) struct WriteAheadLog {
+    file: CheckedBackend,
+    len: u64,
+    // The transaction of the last record appended since the log was opened or emptied
+    last_transaction: Option<TransactionId>,
+}
+
+impl WriteAheadLog {
+    pub(super) fn new(file: Box<dyn StorageBackend>) -> Result<Self> {
+        let file = CheckedBackend::new(file);
+        let len = file.len()?;
+        Ok(Self {
+            file,
+            len,
+            last_transaction: None,
+        })
+    }
+
+    pub(super) fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub(super) fn last_transaction(&self) -> Option<TransactionId> {
+        self.last_transaction
+    }
+
+    pub(super) fn check_io_errors(&self) -> Result {
+        self.file.check_failure()
+    }
+
+    pub(super) fn close(&self) -> Result {
+        self.file.close()
+    }
+
+    // Append a record of the given transaction. `pages` contains the offset and contents of every
+    // page written by the transaction, and `header` is the database header which makes the
+    // transaction the primary
+    pub(super) fn append(
+        &mut self,
+        transaction_id: TransactionId,
+        pages: &[(u64, Arc<[u8]>)],
+        header: &[u8],
+    ) -> Result {
+        assert_eq!(header.len(), DB_HEADER_SIZE);
+        let record_len = RECORD_FIELDS_SIZE
+            + pages
+                .iter()
+                .map(|(_, data)| PAGE_FIELDS_SIZE + data.len())
+                .sum::<usize>()
+            + DB_HEADER_SIZE
+            + CHECKSUM_SIZE;
+        let record_len: u64 = record_len.try_into().unwrap();
+        self.file.set_len(self.len + record_len)?;
+
+        let mut fields = vec![];
+        fields.extend_from_slice(&record_len.to_le_bytes());
+        fields.extend_from_slice(&transaction_id.raw_id().to_le_bytes());
+        fields.extend_from_slice(&u64::try_from(pages.len()).unwrap().to_le_bytes());
+        let mut checksum = RecordChecksum::new(&fields);
+        let mut writer = RecordWriter::new(&self.file, self.len);
+        writer.write(&fields)?;
+        for (offset, data) in pages {
+            checksum.push_page(*offset, data);
+            writer.write(&offset.to_le_bytes())?;
+            writer.write(&u64::try_from(data.len()).unwrap().to_le_bytes())?;
+            writer.write(data)?;
+        }
+        writer.write(header)?;
+        writer.write(&checksum.finish(header).to_le_bytes())?;
+        writer.flush()?;
+
+        self.len += record_len;
+        self.last_transaction = Some(transaction_id);
+        Ok(())
+    }
+
+    pub(super) fn sync(&self, eventual: bool) -> Result {
+        self.file.sync_data(eventual)
+    }
+
+    pub(super) fn truncate(&mut self) -> Result {
+        self.file.set_len(0)?;
+        self.file.sync_data(false)?;
+        self.len = 0;
+        self.last_transaction = None;
+        Ok(())
+    }
+
+    // Apply the records of transactions which are newer than the primary of the database file,
+    // and then empty the log. Records after the first incomplete or corrupted record, which is left
+    // by a crash while appending, are ignored
+    pub(super) fn replay(&mut self, storage: &PagedCachedFile) -> Result<(), DatabaseError> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        if storage.raw_file_len()? >= DB_HEADER_SIZE as u64 {
+            let (mut header, repair_info) =
+                DatabaseHeader::from_bytes(&storage.read_direct(0, DB_HEADER_SIZE)?)?;
+            if !repair_info.invalid_magic_number {
+                header.pick_primary_for_repair(repair_info)?;
+                self.apply_records(storage, header.primary_slot().transaction_id)?;
+            }
+        }
+
+        self.truncate()?;
+        Ok(())
+    }
+
+    fn apply_records(
+        &self,
+        storage: &PagedCachedFile,
+        durable_transaction: TransactionId,
+    ) -> Result<(), DatabaseError> {
+        let mut offset = 0;
+        let mut last_header = None;
+        while let Some(record) = self.read_record(offset)? {
+            offset += record.len;
+            if record.transaction_id <= durable_transaction {
+                continue;
+            }
+            let (header, _) = DatabaseHeader::from_bytes(&record.header)?;
+            let file_len = header.layout().len();
+            if storage.raw_file_len()? < file_len {
+                storage.resize(file_len)?;
+            }
+            for (page_offset, mut data) in record.pages {
+                // Pages are logged encrypted, if the database is encrypted
+                storage.decrypt(page_offset, &mut data)?;
+                storage.write_direct(page_offset, &data)?;
+            }
+            last_header = Some(record.header);
+        }
+
+        if let Some(header) = last_header {
+            // Sync the pages before the header, so that the primary never references pages which
+            // are not durable
+            storage.sync_written()?;
+            storage.write_direct(0, &header)?;
+            storage.sync_written()?;
+        }
+
+        Ok(())
+    }
+
+    // Returns None if there is no complete and valid record at the given offset
+    fn read_record(&self, offset: u64) -> Result<Option<Record>> {
+        if offset + RECORD_FIELDS_SIZE as u64 > self.len {
+            return Ok(None);
+        }
+        let fields = self.file.read(offset, RECORD_FIELDS_SIZE)?;
+        let len = get_u64(&fields[..8]);
+        let minimum_len = (RECORD_FIELDS_SIZE + DB_HEADER_SIZE + CHECKSUM_SIZE) as u64;
+        if len < minimum_len || len > self.len - offset {
+            return Ok(None);
+        }
+        let transaction_id = TransactionId::new(get_u64(&fields[8..16]));
+        let page_count = get_u64(&fields[16..24]);
+
+        let data = self.file.read(offset, len.try_into().unwrap())?;
+        let mut checksum = RecordChecksum::new(&fields);
+        let mut pages = vec![];
+        let mut position = RECORD_FIELDS_SIZE;
+        let pages_end = data.len() - DB_HEADER_SIZE - CHECKSUM_SIZE;
+        for _ in 0..page_count {
+            if position + PAGE_FIELDS_SIZE > pages_end {
+                return Ok(None);
+            }
+            let page_offset = get_u64(&data[position..]);
+            let page_len = get_u64(&data[(position + 8)..]);
+            position += PAGE_FIELDS_SIZE;
+            if page_len > (pages_end - position) as u64 {
+                return Ok(None);
+            }
+            let page = data[position..(position + usize::try_from(page_len).unwrap())].to_vec();
+            position += page.len();
+            checksum.push_page(page_offset, &page);
+            pages.push((page_offset, page));
+        }
+        if position != pages_end {
+            return Ok(None);
+        }
+        let header = data[pages_end..(pages_end + DB_HEADER_SIZE)].to_vec();
+        let expected =
+            u128::from_le_bytes(data[(pages_end + DB_HEADER_SIZE)..].try_into().unwrap());
+        if checksum.finish(&header) != expected {
+            return Ok(None);
+        }
+
+        Ok(Some(Record {
+            len,
+            transaction_id,
+            pages,
+            header,
+        }))
+    }
+}
+
+struct Record {
+    len: u64,
+    transaction_id: TransactionId,
+    pages: Vec<(u64, Vec<u8>)>,
+    header: Vec<u8>,
+}
+
+// Checksums the fixed fields, the offset, length and checksum of each page, and the header. This
+// avoids holding the whole record in memory while it is written
+struct RecordChecksum {
+    summary: Vec<u8>,
+}
+
+impl RecordChecksum {
+    fn new(fields: &[u8]) -> Self {
+        Self {
+            summary: fields.to_vec(),
+        }
+    }
+
+    fn push_page(&mut self, offset: u64, data: &[u8]) {
+        self.summary.extend_from_slice(&offset.to_le_bytes());
+        self.summary
+            .extend_from_slice(&u64::try_from(data.len()).unwrap().to_le_bytes());
+        self.summary
+            .extend_from_slice(&xxh3_checksum(data).to_le_bytes());
+    }
+
+    fn finish(mut self, header: &[u8]) -> u128 {
+        self.summary.extend_from_slice(header);
+        xxh3_checksum(&self.summary)
+    }
+}
+
+struct RecordWriter<'a> {
+    file: &'a CheckedBackend,
+    offset: u64,
+    buffer: Vec<u8>,
+}
+
+impl<'a> RecordWriter<'a> {
+    fn new(file: &'a CheckedBackend, offset: u64) -> Self {
+        Self {
+            file,
+            offset,
+            buffer: vec![],
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result {
+        if self.buffer.len() + data.len() > WRITE_CHUNK_BYTES {
+            self.flush()?;
+        }
+        if data.len() > WRITE_CHUNK_BYTES {
+            self.file.write(self.offset, data)?;
+            self.offset += data.len() as u64;
+        } else {
+            self.buffer.extend_from_slice(data);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result {
+        if !self.buffer.is_empty() {
+            self.file.write(self.offset, &self.buffer)?;
+            self.offset += self.buffer.len() as u64;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+}
+
+fn get_u64(data: &[u8]) -> u64 {
+    u64::from_le_bytes(data[..8].try_into().unwrap())
+}
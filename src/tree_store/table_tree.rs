@@ -149,6 +149,16 @@ impl TableTree {
         Ok(result)
     }
 
+    // Definitions of all tables, of both types, sorted by name
+    pub(crate) fn list_definitions(&self) -> Result<Vec<(String, InternalTableDefinition)>> {
+        let mut result = vec![];
+        for entry in self.tree.range::<RangeFull, &str>(&(..))? {
+            let entry = entry?;
+            result.push((entry.key().to_string(), entry.value()));
+        }
+        Ok(result)
+    }
+
     pub(crate) fn get_table_untyped(
         &self,
         name: &str,
@@ -285,6 +295,32 @@ impl TableTreeMut<'_> {
             .insert(name.to_string(), (table_root, length));
     }
 
+    // Flushes pending table updates, and returns the root of the tree along with the number of
+    // freed pages. These can be passed to rollback_to() to discard any later changes
+    pub(crate) fn checkpoint(&mut self) -> Result<(Option<BtreeHeader>, usize)> {
+        self.flush_table_root_updates()?;
+        Ok((self.tree.get_root(), self.freed_pages.lock()?.len()))
+    }
+
+    // Pages allocated since the checkpoint must be freed by the caller
+    pub(crate) fn rollback_to(&mut self, root: Option<BtreeHeader>, freed_pages: usize) {
+        self.pending_table_updates.clear();
+        self.tree.set_root(root);
+        self.freed_pages.lock().unwrap().truncate(freed_pages);
+    }
+
+    // Immediately frees the pages which were freed since the checkpoint, and have not been
+    // committed
+    pub(crate) fn free_uncommitted_since(&mut self, freed_pages: usize) {
+        let mut allocated = self.allocated_pages.lock().unwrap();
+        let mut all_freed = self.freed_pages.lock().unwrap();
+        for page in all_freed.split_off(freed_pages) {
+            if !self.mem.free_if_uncommitted(page, &mut allocated) {
+                all_freed.push(page);
+            }
+        }
+    }
+
     pub(crate) fn clear_root_updates_and_close(&mut self) {
         self.pending_table_updates.clear();
         self.allocated_pages.lock().unwrap().close();
@@ -565,6 +601,25 @@ impl TableTreeMut<'_> {
         Ok(false)
     }
 
+    // Inserts a table whose pages were copied from another database. The table's checksums are
+    // finalized when the pending table updates are flushed
+    pub(crate) fn insert_copied_table(
+        &mut self,
+        name: &str,
+        mut definition: InternalTableDefinition,
+        root: Option<BtreeHeader>,
+    ) -> Result<(), TableError> {
+        if self.tree.get(&name)?.is_some() {
+            return Err(TableError::TableExists(name.to_string()));
+        }
+        let length = definition.get_length();
+        definition.set_header(None, 0);
+        self.tree.insert(&name, &definition)?;
+        self.stage_update_table_root(name, root, length);
+
+        Ok(())
+    }
+
     pub(crate) fn get_or_create_table<K: Key, V: Value>(
         &mut self,
         name: &str,
@@ -1,6 +1,9 @@
-use crate::multimap_table::{UntypedMultiBtree, relocate_subtrees};
+use crate::multimap_table::{
+    UntypedMultiBtree, copy_subtrees, relocate_subtrees, visit_subtree_pages_with_checksums,
+};
 use crate::tree_store::{
-    BtreeHeader, PageNumber, PagePath, TransactionalMemory, UntypedBtree, UntypedBtreeMut,
+    BtreeHeader, Checksum, DEFERRED, PageImpl, PageNumber, PagePath, PageSource, PageTrackerPolicy,
+    TransactionalMemory, UntypedBtree, UntypedBtreeMut, copy_btree, visit_pages_with_checksums,
 };
 use crate::{Key, Result, TableError, TypeName, Value};
 use std::collections::HashMap;
@@ -256,6 +259,79 @@ impl InternalTableDefinition {
         }
     }
 
+    // Copies this table from `source` to newly allocated pages in `destination`, and returns the
+    // root of the copy. The checksums of the copy are deferred, and must be finalized by the caller
+    pub(crate) fn copy_tree(
+        &self,
+        source: &impl PageSource,
+        destination: &TransactionalMemory,
+        allocated_pages: &mut PageTrackerPolicy,
+    ) -> Result<Option<BtreeHeader>> {
+        let Some(header) = self.private_get_root() else {
+            return Ok(None);
+        };
+        let (root, _) = match self {
+            InternalTableDefinition::Normal { fixed_key_size, .. } => copy_btree(
+                header.root,
+                header.checksum,
+                *fixed_key_size,
+                source,
+                destination,
+                allocated_pages,
+            )?,
+            InternalTableDefinition::Multimap {
+                fixed_key_size,
+                fixed_value_size,
+                ..
+            } => copy_subtrees(
+                header.root,
+                header.checksum,
+                *fixed_key_size,
+                *fixed_value_size,
+                source,
+                destination,
+                allocated_pages,
+            )?,
+        };
+        Ok(Some(BtreeHeader::new(root, DEFERRED, header.length)))
+    }
+
+    // Visits all the pages in this table, along with the checksum of each page.
+    // The children of a page are skipped if the visitor returns false for it
+    pub(crate) fn visit_pages_with_checksums<F>(
+        &self,
+        mem: &TransactionalMemory,
+        mut visitor: F,
+    ) -> Result
+    where
+        F: FnMut(&PageImpl, Checksum) -> Result<bool>,
+    {
+        let Some(header) = self.private_get_root() else {
+            return Ok(());
+        };
+        match self {
+            InternalTableDefinition::Normal { fixed_key_size, .. } => visit_pages_with_checksums(
+                header.root,
+                header.checksum,
+                *fixed_key_size,
+                mem,
+                &mut visitor,
+            ),
+            InternalTableDefinition::Multimap {
+                fixed_key_size,
+                fixed_value_size,
+                ..
+            } => visit_subtree_pages_with_checksums(
+                header.root,
+                header.checksum,
+                *fixed_key_size,
+                *fixed_value_size,
+                mem,
+                &mut visitor,
+            ),
+        }
+    }
+
     fn private_get_root(&self) -> Option<BtreeHeader> {
         match self {
             InternalTableDefinition::Normal { table_root, .. }
@@ -4,6 +4,10 @@ use std::fmt::Debug;
 use std::mem::size_of;
 #[cfg(feature = "chrono_v0_4")]
 mod chrono_v0_4;
+#[cfg(feature = "compression")]
+mod compressed;
+#[cfg(feature = "compression")]
+pub use compressed::Compressed;
 #[derive(Eq, PartialEq, Clone, Debug)]
 enum TypeClassification {
     Internal,
@@ -0,0 +1,194 @@
+use crate::tree_store::MAX_VALUE_LENGTH;
+use crate::{StorageError, TypeName, Value};
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+
+const UNCOMPRESSED: u8 = 0;
+const LZ4: u8 = 1;
+
+/// A value which is compressed with LZ4 when it is stored
+///
+/// Wrap the value type of a table to compress each of its values, for example
+/// `TableDefinition<&str, Compressed<&[u8]>>`. Values which do not shrink when compressed are
+/// stored uncompressed, with one byte of overhead.
+///
+/// Requires the `compression` feature
+pub struct Compressed<V: Value> {
+    contents: Contents,
+    _value_type: PhantomData<V>,
+}
+
+#[derive(Clone)]
+enum Contents {
+    // The serialized value, before compression
+    Decompressed(Vec<u8>),
+    // Stored bytes which could not be decompressed. They are kept so that the value can be
+    // written back unchanged
+    Corrupted(Vec<u8>),
+}
+
+impl<V: Value> Compressed<V> {
+    /// Wraps `value`, which will be compressed when it is stored
+    pub fn new<'a>(value: &V::SelfType<'a>) -> Self
+    where
+        V: 'a,
+    {
+        Self {
+            contents: Contents::Decompressed(V::as_bytes(value).as_ref().to_vec()),
+            _value_type: Default::default(),
+        }
+    }
+
+    /// The decompressed value
+    ///
+    /// Returns [`StorageError::Corrupted`] if the stored value could not be decompressed
+    pub fn value(&self) -> Result<V::SelfType<'_>, StorageError> {
+        match &self.contents {
+            Contents::Decompressed(data) => Ok(V::from_bytes(data)),
+            Contents::Corrupted(_) => Err(StorageError::Corrupted(
+                "Compressed value could not be decompressed".to_string(),
+            )),
+        }
+    }
+}
+
+// Returns `None` if `data` is not a valid compressed value
+fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    let (&tag, data) = data.split_first()?;
+    match tag {
+        UNCOMPRESSED => Some(data.to_vec()),
+        LZ4 => {
+            let (len, data) = lz4_flex::block::uncompressed_size(data).ok()?;
+            // Check the length before allocating for it, since it may be corrupted
+            if len > MAX_VALUE_LENGTH {
+                return None;
+            }
+            let decompressed = lz4_flex::block::decompress(data, len).ok()?;
+            (decompressed.len() == len).then_some(decompressed)
+        }
+        _ => None,
+    }
+}
+
+impl<V: Value> Clone for Compressed<V> {
+    fn clone(&self) -> Self {
+        Self {
+            contents: self.contents.clone(),
+            _value_type: Default::default(),
+        }
+    }
+}
+
+impl<V: Value> Debug for Compressed<V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.value() {
+            Ok(value) => f.debug_tuple("Compressed").field(&value).finish(),
+            Err(_) => f.write_str("Compressed(<corrupted>)"),
+        }
+    }
+}
+
+impl<V: Value> Value for Compressed<V> {
+    type SelfType<'a>
+        = Compressed<V>
+    where
+        Self: 'a;
+    type AsBytes<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Compressed<V>
+    where
+        Self: 'a,
+    {
+        let contents = match decompress(data) {
+            Some(decompressed) => Contents::Decompressed(decompressed),
+            None => Contents::Corrupted(data.to_vec()),
+        };
+        Compressed {
+            contents,
+            _value_type: Default::default(),
+        }
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Compressed<V>) -> Vec<u8>
+    where
+        Self: 'b,
+    {
+        let data = match &value.contents {
+            Contents::Decompressed(data) => data,
+            Contents::Corrupted(stored) => return stored.clone(),
+        };
+        let compressed = lz4_flex::compress_prepend_size(data);
+        let mut result = vec![];
+        if compressed.len() < data.len() {
+            result.push(LZ4);
+            result.extend_from_slice(&compressed);
+        } else {
+            result.push(UNCOMPRESSED);
+            result.extend_from_slice(data);
+        }
+        result
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::internal(&format!("Compressed<{}>", V::type_name().name()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::Compressed;
+    use crate::{Database, TableDefinition, Value};
+
+    #[test]
+    fn round_trip() {
+        let tmpfile = crate::create_tempfile();
+        let db = Database::create(tmpfile.path()).unwrap();
+        let definition: TableDefinition<u64, Compressed<&str>> = TableDefinition::new("x");
+
+        let compressible = "redb ".repeat(1000);
+        let txn = db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(definition).unwrap();
+            table
+                .insert(0, Compressed::new(&compressible.as_str()))
+                .unwrap();
+            table.insert(1, Compressed::new(&"x")).unwrap();
+        }
+        txn.commit().unwrap();
+
+        let txn = db.begin_read().unwrap();
+        let table = txn.open_table(definition).unwrap();
+        assert_eq!(
+            table.get(0).unwrap().unwrap().value().value().unwrap(),
+            compressible
+        );
+        assert_eq!(table.get(1).unwrap().unwrap().value().value().unwrap(), "x");
+
+        let stored = Compressed::<&str>::as_bytes(&Compressed::new(&compressible.as_str()));
+        assert!(stored.len() < compressible.len() / 10);
+        let stored = Compressed::<&str>::as_bytes(&Compressed::new(&"x"));
+        assert_eq!(stored.len(), 2);
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut oversized = vec![1];
+        oversized.extend_from_slice(&u32::MAX.to_le_bytes());
+        oversized.extend_from_slice(&[0xFF; 8]);
+        let mut truncated =
+            Compressed::<&str>::as_bytes(&Compressed::new(&"redb ".repeat(100).as_str()));
+        truncated.truncate(truncated.len() / 2);
+        for stored in [vec![], vec![2, b'x'], oversized, truncated] {
+            let value = Compressed::<&str>::from_bytes(&stored);
+            assert!(value.value().is_err());
+            assert_eq!(Compressed::<&str>::as_bytes(&value), stored);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use redb::asynchronous::AsyncDatabase;
+use redb::{Database, ReadableTableMetadata, TableDefinition};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+const U64_TABLE: TableDefinition<u64, u64> = TableDefinition::new("u64");
+
+fn create_tempfile() -> tempfile::NamedTempFile {
+    if cfg!(target_os = "wasi") {
+        tempfile::NamedTempFile::new_in("/tmp").unwrap()
+    } else {
+        tempfile::NamedTempFile::new().unwrap()
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Minimal executor, so that the tests don't depend on a particular async runtime
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+#[cfg(not(target_os = "wasi"))]
+fn async_write_and_range() {
+    let tmpfile = create_tempfile();
+    let db = AsyncDatabase::new(Database::create(tmpfile.path()).unwrap());
+
+    block_on(async {
+        let write_txn = db.begin_write().await.unwrap();
+        {
+            let mut table = write_txn.open_table(U64_TABLE).unwrap();
+            for i in 0..1000 {
+                table.insert(i, i * 2).unwrap();
+            }
+        }
+        write_txn.commit().await.unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 1000);
+        let mut range = db.range(table.range(10..900).unwrap());
+        let mut expected = 10;
+        while let Some(entry) = range.next().await {
+            let (key, value) = entry.unwrap();
+            assert_eq!(key.value(), expected);
+            assert_eq!(value.value(), expected * 2);
+            expected += 1;
+        }
+        assert_eq!(expected, 900);
+    });
+}
+
+#[test]
+#[cfg(not(target_os = "wasi"))]
+fn async_begin_write_waits() {
+    let tmpfile = create_tempfile();
+    // Waiting for the write transaction must not occupy the only worker thread, which is needed
+    // to commit the transaction being waited for
+    let db = AsyncDatabase::with_worker_threads(Database::create(tmpfile.path()).unwrap(), 1);
+
+    let write_txn = block_on(db.begin_write()).unwrap();
+    thread::scope(|s| {
+        let t = s.spawn(|| {
+            block_on(async {
+                let write_txn = db.begin_write().await.unwrap();
+                {
+                    let mut table = write_txn.open_table(U64_TABLE).unwrap();
+                    table.insert(1, 1).unwrap();
+                }
+                write_txn.commit().await.unwrap();
+            });
+        });
+        thread::sleep(Duration::from_millis(10));
+        {
+            let mut table = write_txn.open_table(U64_TABLE).unwrap();
+            table.insert(0, 0).unwrap();
+        }
+        block_on(write_txn.commit()).unwrap();
+        t.join().unwrap();
+    });
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 2);
+}
@@ -1,4 +1,4 @@
-use redb::ReadableTableMetadata;
+use redb::{ReadableTable, ReadableTableMetadata};
 
 const ELEMENTS: usize = 3;
 
@@ -327,3 +327,64 @@ fn mixed_width() {
     test_helper::<u8, &[u8]>();
     test_helper::<&[u8; 5], &str>();
 }
+
+// Databases created by 2.6 do not store subtree counts in their branch pages
+#[test]
+fn order_statistics_without_counts() {
+    let tmpfile = create_tempfile();
+    let db = redb2_6::Database::builder()
+        .create_with_file_format_v3(true)
+        .create(tmpfile.path())
+        .unwrap();
+    let table_def: redb2_6::TableDefinition<u64, u64> = redb2_6::TableDefinition::new("table");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        for i in 0..10_000 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+    drop(db);
+
+    let mut db = redb::Database::open(tmpfile.path()).unwrap();
+    let table_def: redb::TableDefinition<u64, u64> = redb::TableDefinition::new("table");
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(table_def).unwrap();
+        assert_eq!(table.range_len(100..5000).unwrap(), 4900);
+        assert_eq!(table.nth(7000).unwrap().unwrap().0.value(), 7000);
+        assert_eq!(table.rank(1234).unwrap(), 1234);
+        for i in 10_000..20_000 {
+            table.insert(&i, &i).unwrap();
+        }
+        table.retain_in(2000..4000, |_, _| false).unwrap();
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap());
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(table.len().unwrap(), 18_000);
+    assert_eq!(table.range_len(1000..15_000).unwrap(), 12_000);
+    assert_eq!(table.nth(5000).unwrap().unwrap().0.value(), 7000);
+    assert_eq!(table.rank(7000).unwrap(), 5000);
+    drop(table);
+    drop(read_txn);
+
+    // Restoring a backup converts the branch pages to the current format
+    let dir = tempfile::tempdir().unwrap();
+    let full = dir.path().join("full");
+    let restored = dir.path().join("restored");
+    db.incremental_backup_to(&full, &[] as &[&std::path::Path])
+        .unwrap();
+    redb::Builder::new()
+        .restore_backup_chain(&[&full], &restored)
+        .unwrap();
+    let mut restored = redb::Database::open(&restored).unwrap();
+    assert!(restored.check_integrity().unwrap());
+    let read_txn = restored.begin_read().unwrap();
+    let table = read_txn.open_table(table_def).unwrap();
+    assert_eq!(table.range_len(1000..15_000).unwrap(), 12_000);
+    assert_eq!(table.nth(5000).unwrap().unwrap().0.value(), 7000);
+}
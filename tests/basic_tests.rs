@@ -3,6 +3,7 @@ use redb::backends::InMemoryBackend;
 use redb::{
     Database, Key, MultimapTableDefinition, MultimapTableHandle, Range, ReadableTable,
     ReadableTableMetadata, TableDefinition, TableError, TableHandle, TypeName, Value,
+    WriteTransaction,
 };
 use std::cmp::Ordering;
 #[cfg(not(target_os = "wasi"))]
@@ -79,6 +80,44 @@ fn in_memory() {
     assert_eq!(table.len().unwrap(), 3);
 }
 
+#[test]
+fn in_memory_image() {
+    let backend = InMemoryBackend::new();
+    let handle = backend.image();
+    let db = Database::builder().create_with_backend(backend).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "world").unwrap();
+    }
+    write_txn.commit().unwrap();
+    // The image is not available while the database is open
+    assert!(handle.to_bytes().is_none());
+    let handle = handle.into_bytes().unwrap_err();
+    drop(db);
+    assert!(handle.is_closed());
+    let image = handle.into_bytes().unwrap();
+
+    let db = Database::builder()
+        .set_repair_callback(|_| panic!("repair should not be needed"))
+        .create_with_backend(InMemoryBackend::from_bytes(image.clone()))
+        .unwrap();
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    assert_eq!(table.get("hello").unwrap().unwrap().value(), "world");
+    drop(table);
+    drop(read_txn);
+    drop(db);
+
+    // The image has the same format as a database file
+    let tmpfile = create_tempfile();
+    std::fs::write(tmpfile.path(), image).unwrap();
+    let db = Database::open(tmpfile.path()).unwrap();
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(STR_TABLE).unwrap();
+    assert_eq!(table.get("hello").unwrap().unwrap().value(), "world");
+}
+
 #[test]
 fn first_last() {
     let tmpfile = create_tempfile();
@@ -276,6 +315,508 @@ fn retain() {
     }
 }
 
+#[test]
+fn remove_range() {
+    let tmpfile = create_tempfile();
+    let mut db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..100_000 {
+            table.insert(&i, &i).unwrap();
+        }
+        // Test removing uncommitted data
+        assert_eq!(table.remove_range(90_000..).unwrap(), 10_000);
+        assert_eq!(table.len().unwrap(), 90_000);
+        assert_eq!(table.last().unwrap().unwrap().0.value(), 89_999);
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(table.remove_range(10..=80_000).unwrap(), 79_991);
+        assert_eq!(table.remove_range(5..5).unwrap(), 0);
+        assert_eq!(table.remove_range(..3).unwrap(), 3);
+        assert_eq!(table.remove_range(..3).unwrap(), 0);
+        assert_eq!(table.len().unwrap(), 10_006);
+
+        let expected: Vec<u64> = (3..10).chain(80_001..90_000).collect();
+        let actual: Vec<u64> = table
+            .iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().0.value())
+            .collect();
+        assert_eq!(actual, expected);
+
+        // The table must still be usable after its subtrees are freed
+        for i in 0..100 {
+            table.insert(&(i * 1000), &i).unwrap();
+        }
+        assert_eq!(table.get(&50_000).unwrap().unwrap().value(), 50);
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap());
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        let len = table.len().unwrap();
+        assert_eq!(table.remove_range::<u64>(..).unwrap(), len);
+        assert!(table.is_empty().unwrap());
+    }
+    write_txn.abort().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        let len = table.len().unwrap();
+        assert_eq!(table.remove_range::<u64>(..).unwrap(), len);
+        assert_eq!(table.remove_range::<u64>(..).unwrap(), 0);
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap());
+}
+
+#[test]
+fn clear() {
+    let tmpfile = create_tempfile();
+    let mut db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000 {
+            table.insert(&i, &i).unwrap();
+        }
+        assert!(matches!(
+            write_txn.truncate_table(U64_TABLE).unwrap_err(),
+            TableError::TableAlreadyOpen(_, _)
+        ));
+    }
+    write_txn.commit().unwrap();
+    let allocated = db.begin_write().unwrap().stats().unwrap().allocated_pages();
+
+    let write_txn = db.begin_write().unwrap();
+    write_txn.truncate_table(U64_TABLE).unwrap();
+    {
+        let table = write_txn.open_table(U64_TABLE).unwrap();
+        assert!(table.is_empty().unwrap());
+    }
+    write_txn.commit().unwrap();
+    // Commit again, so that the freed pages are released
+    db.begin_write().unwrap().commit().unwrap();
+    assert!(db.begin_write().unwrap().stats().unwrap().allocated_pages() < allocated);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        table.insert(&1, &1).unwrap();
+        table.clear().unwrap();
+        assert!(table.is_empty().unwrap());
+        table.insert(&2, &2).unwrap();
+    }
+    assert!(
+        write_txn
+            .list_tables()
+            .unwrap()
+            .any(|handle| handle.name() == U64_TABLE.name())
+    );
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 1);
+    assert_eq!(table.get(&2).unwrap().unwrap().value(), 2);
+    drop(table);
+    drop(read_txn);
+    assert!(db.check_integrity().unwrap());
+}
+
+#[test]
+fn bulk_load() {
+    let tmpfile = create_tempfile();
+    let mut db = Database::create(tmpfile.path()).unwrap();
+    for n in [0u64, 1, 2, 100, 1000, 5000, 100_000] {
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(U64_TABLE).unwrap();
+            table.remove_range::<u64>(..).unwrap();
+        }
+        assert_eq!(
+            write_txn
+                .bulk_load(U64_TABLE, (0..n).map(|i| (i, i * 2)))
+                .unwrap(),
+            n
+        );
+        {
+            let table = write_txn.open_table(U64_TABLE).unwrap();
+            assert_eq!(table.len().unwrap(), n);
+            for (i, entry) in table.iter().unwrap().enumerate() {
+                let (k, v) = entry.unwrap();
+                assert_eq!(k.value(), i as u64);
+                assert_eq!(v.value(), i as u64 * 2);
+            }
+            assert_eq!(
+                table.range(10..20).unwrap().count(),
+                n.clamp(10, 20) as usize - 10
+            );
+        }
+        write_txn.commit().unwrap();
+        assert!(db.check_integrity().unwrap());
+    }
+
+    // The loaded table is fully usable
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in (0..20_000).step_by(3) {
+            table.insert(&i, &0).unwrap();
+        }
+        table.retain_in(0..1000, |k, _| k % 2 == 0).unwrap();
+        assert!(table.get(&999).unwrap().is_none());
+        assert_eq!(table.get(&9_996).unwrap().unwrap().value(), 0);
+        assert_eq!(table.get(&9_998).unwrap().unwrap().value(), 9_998 * 2);
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap());
+
+    // Entries after the first out of order key are inserted individually
+    let write_txn = db.begin_write().unwrap();
+    {
+        let entries = ["b", "d", "f", "a", "d", "g"];
+        assert_eq!(
+            write_txn
+                .bulk_load(STR_TABLE, entries.iter().map(|x| (*x, *x)))
+                .unwrap(),
+            6
+        );
+        // The table isn't empty, so all of these are inserted individually
+        assert_eq!(
+            write_txn
+                .bulk_load(STR_TABLE, [("c", "c"), ("e", "e")])
+                .unwrap(),
+            2
+        );
+        let table = write_txn.open_table(STR_TABLE).unwrap();
+        let keys: Vec<String> = table
+            .iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().0.value().to_string())
+            .collect();
+        assert_eq!(keys, ["a", "b", "c", "d", "e", "f", "g"]);
+    }
+    write_txn.commit().unwrap();
+
+    // Large values are stored in their own pages
+    let write_txn = db.begin_write().unwrap();
+    {
+        let value = vec![0xAB; 10_000];
+        let keys: Vec<String> = (0..100).map(|i| format!("key{i:03}")).collect();
+        write_txn
+            .bulk_load(
+                SLICE_TABLE,
+                keys.iter().map(|k| (k.as_bytes(), value.as_slice())),
+            )
+            .unwrap();
+        let table = write_txn.open_table(SLICE_TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 100);
+        assert_eq!(
+            table.get(b"key042".as_slice()).unwrap().unwrap().value(),
+            value
+        );
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap());
+}
+
+#[test]
+fn bulk_load_partial_last_branch() {
+    // Check that no entries are lost when the last branch of a level would have only one child
+    fn check<K: Key + 'static, V: Value + 'static>(
+        db: &mut Database,
+        definition: TableDefinition<K, V>,
+        load: impl FnOnce(&WriteTransaction) -> u64,
+        n: u64,
+    ) {
+        let write_txn = db.begin_write().unwrap();
+        write_txn.delete_table(definition).unwrap();
+        assert_eq!(load(&write_txn), n);
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(definition).unwrap();
+        assert_eq!(table.len().unwrap(), n);
+        assert_eq!(table.iter().unwrap().count() as u64, n);
+        drop(table);
+        drop(read_txn);
+        assert!(db.check_integrity().unwrap());
+    }
+
+    let mut db = Database::builder()
+        .create_with_backend(InMemoryBackend::new())
+        .unwrap();
+
+    // A leaf holds 255 of these entries, and a branch 128 children, so the root of the first
+    // 32640 entries is full, and entries 32641 to 32895 are in a single trailing leaf
+    for n in [
+        32_640, 32_641, 32_654, 32_876, 32_895, 32_896, 65_280, 65_288, 65_510,
+    ] {
+        let load = |txn: &WriteTransaction| txn.bulk_load(U64_TABLE, (0..n).map(|i| (i, i)));
+        check(&mut db, U64_TABLE, |txn| load(txn).unwrap(), n);
+    }
+
+    // With 500 byte keys, a leaf holds 8 entries and a branch 8 children, so a full branch of leaves
+    // holds 64 entries and a full branch of those holds 512. This covers one full branch followed
+    // by zero or one trailing children, at heights one and two
+    const LARGE_KEY_TABLE: TableDefinition<&[u8], ()> = TableDefinition::new("large_keys");
+    let keys: Vec<[u8; 500]> = (0..600u16)
+        .map(|i| {
+            let mut key = [0; 500];
+            key[..2].copy_from_slice(&i.to_be_bytes());
+            key
+        })
+        .collect();
+    for n in [8, 9, 64, 65, 72, 73, 512, 513, 520, 521, 576, 577] {
+        let entries = keys[..n].iter().map(|key| (key.as_slice(), ()));
+        let load = |txn: &WriteTransaction| txn.bulk_load(LARGE_KEY_TABLE, entries).unwrap();
+        check(&mut db, LARGE_KEY_TABLE, load, n as u64);
+    }
+}
+
+#[test]
+fn cursor() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000 {
+            table.insert(&(i * 2), &i).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    let mut cursor = table.cursor();
+    assert!(cursor.current().is_none());
+    assert_eq!(cursor.next().unwrap().unwrap().0.value(), 0);
+    assert!(cursor.prev().unwrap().is_none());
+    assert_eq!(cursor.current().unwrap().0.value(), 0);
+
+    let (key, value) = cursor.seek(&501).unwrap().unwrap();
+    assert_eq!(key.value(), 502);
+    assert_eq!(value.value(), 251);
+    assert_eq!(cursor.next().unwrap().unwrap().0.value(), 504);
+    assert_eq!(cursor.prev().unwrap().unwrap().0.value(), 502);
+    assert_eq!(cursor.prev().unwrap().unwrap().0.value(), 500);
+    assert_eq!(cursor.next().unwrap().unwrap().0.value(), 502);
+    for i in 252..1000 {
+        assert_eq!(cursor.next().unwrap().unwrap().1.value(), i);
+    }
+    assert!(cursor.next().unwrap().is_none());
+    assert_eq!(cursor.current().unwrap().0.value(), 1998);
+
+    assert!(cursor.seek(&1999).unwrap().is_none());
+    assert!(cursor.current().is_none());
+    assert!(cursor.next().unwrap().is_none());
+    assert_eq!(cursor.prev().unwrap().unwrap().0.value(), 1998);
+    assert_eq!(cursor.seek_first().unwrap().unwrap().0.value(), 0);
+    assert_eq!(cursor.seek_last().unwrap().unwrap().0.value(), 1998);
+    assert_eq!(cursor.prev().unwrap().unwrap().0.value(), 1996);
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        let mut cursor = table.cursor_mut();
+        assert!(!cursor.delete_current().unwrap());
+        let mut entry = cursor.seek_first().unwrap().map(|(k, _)| k.value());
+        while let Some(key) = entry {
+            if key % 4 == 0 {
+                assert!(cursor.delete_current().unwrap());
+                assert!(cursor.current().is_none());
+            } else {
+                assert!(cursor.replace_current(&(key + 1)).unwrap());
+                assert_eq!(cursor.current().unwrap().1.value(), key + 1);
+            }
+            entry = cursor.next().unwrap().map(|(k, _)| k.value());
+        }
+        assert_eq!(cursor.prev().unwrap().unwrap().0.value(), 1994);
+
+        assert_eq!(table.len().unwrap(), 500);
+        for entry in table.iter().unwrap() {
+            let (key, value) = entry.unwrap();
+            assert_eq!(key.value() % 4, 2);
+            assert_eq!(value.value(), key.value() + 1);
+        }
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn order_statistics() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..20_000 {
+            table.insert(&(i * 3), &i).unwrap();
+        }
+        assert_eq!(table.range_len(300..=600).unwrap(), 101);
+        assert_eq!(table.nth(7).unwrap().unwrap().0.value(), 21);
+        assert_eq!(table.rank(22).unwrap(), 8);
+    }
+    write_txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    let table = read_txn.open_table(U64_TABLE).unwrap();
+    for _ in 0..20 {
+        let x = random::<u64>() % 70_000;
+        let y = random::<u64>() % 70_000;
+        let (a, b) = (x.min(y), x.max(y));
+        assert_eq!(
+            table.range_len(a..b).unwrap(),
+            table.range(a..b).unwrap().count() as u64
+        );
+        assert_eq!(
+            table.range_len(a..=b).unwrap(),
+            table.range(a..=b).unwrap().count() as u64
+        );
+        assert_eq!(
+            table.range_len(a..).unwrap(),
+            table.range(a..).unwrap().count() as u64
+        );
+        assert_eq!(table.rank(a).unwrap(), a.div_ceil(3).min(20_000));
+    }
+    assert_eq!(table.range_len::<u64>(..).unwrap(), 20_000);
+    assert_eq!(table.range_len(70_000..).unwrap(), 0);
+
+    for index in [0, 1, 5000, 9999, 10_000, 19_999] {
+        let (key, value) = table.nth(index).unwrap().unwrap();
+        assert_eq!(key.value(), index * 3);
+        assert_eq!(value.value(), index);
+        assert_eq!(table.rank(key.value()).unwrap(), index);
+    }
+    assert!(table.nth(20_000).unwrap().is_none());
+    drop(table);
+    drop(read_txn);
+
+    // The counts are kept up to date as entries are removed
+    let mut db = db;
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in (0..20_000).step_by(7) {
+            table.remove(&(i * 3)).unwrap();
+        }
+        table.retain_in(9_000..30_000, |_, _| false).unwrap();
+        let expected: Vec<u64> = table
+            .iter()
+            .unwrap()
+            .map(|x| x.unwrap().0.value())
+            .collect();
+        assert_eq!(table.range_len::<u64>(..).unwrap(), expected.len() as u64);
+        for index in [0, 100, 2000, expected.len() - 1] {
+            let key = table.nth(index as u64).unwrap().unwrap().0.value();
+            assert_eq!(key, expected[index]);
+            assert_eq!(table.rank(key).unwrap(), index as u64);
+        }
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap());
+}
+
+#[test]
+fn update() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for _ in 0..3 {
+            for i in 0..1000 {
+                table
+                    .update(i % 100, |old| Some(old.unwrap_or(0) + 1))
+                    .unwrap();
+            }
+        }
+        assert_eq!(table.len().unwrap(), 100);
+        assert_eq!(table.get(7).unwrap().unwrap().value(), 30);
+
+        let old = table.update(7, |old| old.filter(|_| false)).unwrap();
+        assert_eq!(old.unwrap().value(), 30);
+        assert!(table.get(7).unwrap().is_none());
+        assert!(table.update(7, |old| old).unwrap().is_none());
+        assert!(table.get(7).unwrap().is_none());
+        assert_eq!(table.len().unwrap(), 99);
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let definition: TableDefinition<&str, String> = TableDefinition::new("strings");
+        let mut table = write_txn.open_table(definition).unwrap();
+        let keys: Vec<String> = (0..500).map(|i| format!("key{i}")).collect();
+        for _ in 0..10 {
+            for key in &keys {
+                table
+                    .update(key.as_str(), |old| {
+                        Some(format!("{}{key}", old.unwrap_or_default()))
+                    })
+                    .unwrap();
+            }
+        }
+        for key in &keys {
+            assert_eq!(
+                table.get(key.as_str()).unwrap().unwrap().value(),
+                key.repeat(10)
+            );
+        }
+    }
+    write_txn.commit().unwrap();
+}
+
+#[test]
+fn conditional_insert() {
+    let tmpfile = create_tempfile();
+    let db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(STR_TABLE).unwrap();
+        assert!(table.insert_if_absent("hello", "world").unwrap());
+        assert!(!table.insert_if_absent("hello", "world2").unwrap());
+        assert_eq!(table.get("hello").unwrap().unwrap().value(), "world");
+
+        assert!(!table.compare_and_swap("hello", "world2", "x").unwrap());
+        assert!(!table.compare_and_swap("missing", "world", "x").unwrap());
+        assert!(table.get("missing").unwrap().is_none());
+        assert!(table.compare_and_swap("hello", "world", "world2").unwrap());
+        assert_eq!(table.get("hello").unwrap().unwrap().value(), "world2");
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000 {
+            assert!(table.insert_if_absent(i, i).unwrap());
+        }
+        for i in 0..10_000 {
+            assert!(!table.insert_if_absent(i, 0).unwrap());
+            assert!(table.compare_and_swap(i, i, i + 1).unwrap());
+            assert!(!table.compare_and_swap(i, i, i + 2).unwrap());
+        }
+        assert_eq!(table.len().unwrap(), 10_000);
+        for (i, entry) in table.iter().unwrap().enumerate() {
+            let (key, value) = entry.unwrap();
+            assert_eq!(key.value(), i as u64);
+            assert_eq!(value.value(), i as u64 + 1);
+        }
+    }
+    write_txn.commit().unwrap();
+}
+
 #[test]
 fn stored_size() {
     let tmpfile = create_tempfile();
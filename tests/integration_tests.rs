@@ -2,9 +2,10 @@ use rand::Rng;
 use rand::prelude::SliceRandom;
 use redb::backends::FileBackend;
 use redb::{
-    AccessGuard, Builder, CompactionError, Database, Durability, Key, MultimapRange,
-    MultimapTableDefinition, MultimapValue, Range, ReadableTable, ReadableTableMetadata,
-    SetDurabilityError, StorageBackend, TableDefinition, TableStats, TransactionError, Value,
+    AccessGuard, Builder, ChangeKind, CommitEvent, CompactionError, Database, Durability, Key,
+    MultimapRange, MultimapTableDefinition, MultimapValue, PageEncryption, Range, ReadableTable,
+    ReadableTableMetadata, SetDurabilityError, StorageBackend, TableDefinition, TableStats,
+    TransactionError, Value,
 };
 use redb::{DatabaseError, ReadableMultimapTable, SavepointError, StorageError, TableError};
 use std::borrow::Borrow;
@@ -12,8 +13,8 @@ use std::fs;
 use std::io::{ErrorKind, Write};
 use std::marker::PhantomData;
 use std::ops::RangeBounds;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 const ELEMENTS: usize = 100;
 
@@ -1709,6 +1710,126 @@ fn persistent_savepoint() {
     assert_eq!(table.get(&0).unwrap().unwrap().value(), "hello");
 }
 
+#[test]
+fn nested_transaction() {
+    let tmpfile = create_tempfile();
+    let mut db = Database::create(tmpfile.path()).unwrap();
+
+    let mut txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000 {
+            table.insert(i, i).unwrap();
+        }
+        let mut table = txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "world").unwrap();
+    }
+
+    // Roll back changes to pages which were written earlier in the same transaction
+    let nested = txn.begin_nested().unwrap();
+    assert!(matches!(
+        nested.ephemeral_savepoint().err().unwrap(),
+        SavepointError::InvalidSavepoint
+    ));
+    {
+        let mut table = nested.open_table(U64_TABLE).unwrap();
+        table.remove_range(..500).unwrap();
+        for i in 1000..2000 {
+            table.insert(i, 0).unwrap();
+        }
+        table.insert(999, 0).unwrap();
+    }
+    assert!(nested.delete_table(STR_TABLE).unwrap());
+    nested.abort();
+    {
+        let table = txn.open_table(U64_TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 1000);
+        assert_eq!(table.get(0).unwrap().unwrap().value(), 0);
+        assert_eq!(table.get(999).unwrap().unwrap().value(), 999);
+        assert!(table.get(1000).unwrap().is_none());
+        let table = txn.open_table(STR_TABLE).unwrap();
+        assert_eq!(table.get("hello").unwrap().unwrap().value(), "world");
+    }
+
+    let mut nested = txn.begin_nested().unwrap();
+    {
+        let mut table = nested.open_table(U64_TABLE).unwrap();
+        table.remove_range(..500).unwrap();
+    }
+    {
+        let inner = nested.begin_nested().unwrap();
+        let mut table = inner.open_table(U64_TABLE).unwrap();
+        table.clear().unwrap();
+        // Dropping a nested transaction rolls it back
+    }
+    {
+        let mut inner = nested.begin_nested().unwrap();
+        {
+            let mut table = inner.open_table(U64_TABLE).unwrap();
+            table.insert(2000, 2000).unwrap();
+        }
+        {
+            let innermost = inner.begin_nested().unwrap();
+            let mut table = innermost.open_table(U64_TABLE).unwrap();
+            table.insert(3000, 3000).unwrap();
+        }
+        inner.commit();
+    }
+    nested.commit();
+    txn.commit().unwrap();
+
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 501);
+    assert_eq!(table.first().unwrap().unwrap().0.value(), 500);
+    assert_eq!(table.last().unwrap().unwrap().0.value(), 2000);
+    drop(table);
+    drop(txn);
+
+    // Allocations are tracked while a savepoint exists
+    let mut txn = db.begin_write().unwrap();
+    let savepoint = txn.ephemeral_savepoint().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        table.insert(0, 0).unwrap();
+    }
+    {
+        let nested = txn.begin_nested().unwrap();
+        nested.open_table(U64_TABLE).unwrap().clear().unwrap();
+        assert_eq!(nested.list_persistent_savepoints().unwrap().count(), 0);
+    }
+    txn.commit().unwrap();
+    let mut txn = db.begin_write().unwrap();
+    txn.restore_savepoint(&savepoint).unwrap();
+    txn.commit().unwrap();
+    drop(savepoint);
+
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 501);
+    drop(table);
+    drop(txn);
+
+    // Savepoints can be created once a nested transaction has ended, if it made no changes
+    let mut txn = db.begin_write().unwrap();
+    {
+        let nested = txn.begin_nested().unwrap();
+        nested.open_table(U64_TABLE).unwrap().insert(0, 1).unwrap();
+    }
+    txn.begin_nested().unwrap().commit();
+    txn.ephemeral_savepoint().unwrap();
+    let nested = txn.begin_nested().unwrap();
+    nested.open_table(U64_TABLE).unwrap().insert(0, 1).unwrap();
+    nested.commit();
+    assert!(matches!(
+        txn.ephemeral_savepoint().err().unwrap(),
+        SavepointError::InvalidSavepoint
+    ));
+    txn.abort().unwrap();
+
+    assert!(db.check_integrity().unwrap());
+}
+
 #[test]
 fn savepoint() {
     let tmpfile = create_tempfile();
@@ -1942,3 +2063,814 @@ fn custom_table_type() {
         table.get(1).unwrap().next().unwrap().unwrap().value()
     );
 }
+
+#[test]
+fn backup() {
+    let tmpfile = create_tempfile();
+    let backup_dir = tempfile::tempdir().unwrap();
+    let backup_path = backup_dir.path().join("backup.redb");
+    let multimap_def: MultimapTableDefinition<u64, u64> = MultimapTableDefinition::new("multimap");
+
+    let mut db = Database::create(tmpfile.path()).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000 {
+            table.insert(i, i + 1).unwrap();
+        }
+        let mut table = txn.open_multimap_table(multimap_def).unwrap();
+        // Enough values for the first key to require a subtree
+        for i in 0..1_000 {
+            table.insert(0, i).unwrap();
+        }
+        table.insert(1, 1).unwrap();
+        let mut table = txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "world").unwrap();
+    }
+    txn.commit().unwrap();
+
+    let read_txn = db.begin_read().unwrap();
+    // Writes after the snapshot was taken must not appear in the copy
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        table.insert(0, 0).unwrap();
+        table.insert(20_000, 0).unwrap();
+    }
+    txn.delete_table(STR_TABLE).unwrap();
+    txn.commit().unwrap();
+    read_txn.copy_to(&backup_path).unwrap();
+    // The destination must not already exist
+    assert!(read_txn.copy_to(&backup_path).is_err());
+    drop(read_txn);
+
+    let mut backup = Database::create(&backup_path).unwrap();
+    assert!(backup.check_integrity().unwrap());
+    let txn = backup.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 10_000);
+    for (i, entry) in table.iter().unwrap().enumerate() {
+        let (key, value) = entry.unwrap();
+        assert_eq!(key.value(), i as u64);
+        assert_eq!(value.value(), i as u64 + 1);
+    }
+    let table = txn.open_multimap_table(multimap_def).unwrap();
+    assert_eq!(table.len().unwrap(), 1_001);
+    for (i, value) in table.get(0).unwrap().enumerate() {
+        assert_eq!(value.unwrap().value(), i as u64);
+    }
+    assert_eq!(table.get(1).unwrap().next().unwrap().unwrap().value(), 1);
+    let table = txn.open_table(STR_TABLE).unwrap();
+    assert_eq!(table.get("hello").unwrap().unwrap().value(), "world");
+    drop(table);
+    drop(txn);
+
+    // Backing up the live database includes the later writes
+    let backup_path2 = backup_dir.path().join("backup2.redb");
+    db.backup_to(&backup_path2).unwrap();
+    let backup2 = Database::create(&backup_path2).unwrap();
+    let txn = backup2.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 10_001);
+    assert_eq!(table.get(0).unwrap().unwrap().value(), 0);
+    assert!(txn.open_table(STR_TABLE).is_err());
+    assert!(db.check_integrity().unwrap());
+}
+
+#[test]
+fn incremental_backup() {
+    let tmpfile = create_tempfile();
+    let backup_dir = tempfile::tempdir().unwrap();
+    let full = backup_dir.path().join("full");
+    let incremental1 = backup_dir.path().join("incremental1");
+    let incremental2 = backup_dir.path().join("incremental2");
+    let multimap_def: MultimapTableDefinition<u64, u64> = MultimapTableDefinition::new("multimap");
+
+    let db = Database::create(tmpfile.path()).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10_000 {
+            table.insert(i, i).unwrap();
+        }
+        let mut table = txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "world").unwrap();
+    }
+    txn.commit().unwrap();
+    db.incremental_backup_to(&full, &[] as &[&std::path::Path])
+        .unwrap();
+
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        table.insert(0, 1).unwrap();
+        let mut table = txn.open_multimap_table(multimap_def).unwrap();
+        for i in 0..1_000 {
+            table.insert(0, i).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+    db.incremental_backup_to(&incremental1, &[&full]).unwrap();
+    // Only the modified pages are included
+    assert!(fs::metadata(&incremental1).unwrap().len() < fs::metadata(&full).unwrap().len());
+
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        table.insert(20_000, 0).unwrap();
+        let mut table = txn.open_multimap_table(multimap_def).unwrap();
+        table.insert(1, 1).unwrap();
+    }
+    txn.delete_table(STR_TABLE).unwrap();
+    txn.commit().unwrap();
+    db.incremental_backup_to(&incremental2, &[&full, &incremental1])
+        .unwrap();
+
+    // The chain must be complete and in order
+    let broken = backup_dir.path().join("broken");
+    assert!(Database::restore_backup_chain(&[&full, &incremental2], &broken).is_err());
+    assert!(Database::restore_backup_chain(&[&incremental1], &broken).is_err());
+    assert!(!broken.exists());
+
+    let restored = backup_dir.path().join("restored1");
+    Database::restore_backup_chain(&[&full, &incremental1], &restored).unwrap();
+    let mut restored = Database::create(&restored).unwrap();
+    assert!(restored.check_integrity().unwrap());
+    let txn = restored.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 10_000);
+    assert_eq!(table.get(0).unwrap().unwrap().value(), 1);
+    assert_eq!(table.get(9_999).unwrap().unwrap().value(), 9_999);
+    let table = txn.open_table(STR_TABLE).unwrap();
+    assert_eq!(table.get("hello").unwrap().unwrap().value(), "world");
+    let table = txn.open_multimap_table(multimap_def).unwrap();
+    assert_eq!(table.len().unwrap(), 1_000);
+    drop(table);
+    drop(txn);
+
+    let restored = backup_dir.path().join("restored2");
+    Database::restore_backup_chain(&[&full, &incremental1, &incremental2], &restored).unwrap();
+    let mut restored = Database::create(&restored).unwrap();
+    assert!(restored.check_integrity().unwrap());
+    let txn = restored.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 10_001);
+    assert_eq!(table.get(20_000).unwrap().unwrap().value(), 0);
+    assert!(txn.open_table(STR_TABLE).is_err());
+    let table = txn.open_multimap_table(multimap_def).unwrap();
+    assert_eq!(table.len().unwrap(), 1_001);
+    for (i, value) in table.get(0).unwrap().enumerate() {
+        assert_eq!(value.unwrap().value(), i as u64);
+    }
+}
+
+#[test]
+fn commit_hook() {
+    let tmpfile = create_tempfile();
+    let events = Arc::new(Mutex::new(vec![]));
+    let events2 = events.clone();
+    let db = Builder::new()
+        .set_commit_hook(move |event: &CommitEvent| events2.lock().unwrap().push(event.clone()))
+        .set_commit_hook_key_changes(true)
+        .create(tmpfile.path())
+        .unwrap();
+
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        for i in 0..10 {
+            table.insert(i, i).unwrap();
+        }
+        let mut table = txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", "world").unwrap();
+        table.insert("temporary", "value").unwrap();
+        table.remove("temporary").unwrap();
+    }
+    txn.commit().unwrap();
+
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        table.insert(0, 1).unwrap();
+        table.insert(10, 10).unwrap();
+        table.remove(1).unwrap();
+        table.retain(|key, _| key != 2).unwrap();
+        for entry in table.extract_if(|key, _| key == 3).unwrap() {
+            entry.unwrap();
+        }
+        // Opening a table without modifying it doesn't touch it
+        txn.open_table(STR_TABLE).unwrap();
+        txn.open_multimap_table::<u64, u64>(MultimapTableDefinition::new("multimap"))
+            .unwrap()
+            .insert(0, 0)
+            .unwrap();
+    }
+    txn.commit().unwrap();
+
+    // Aborted transactions aren't reported
+    let txn = db.begin_write().unwrap();
+    txn.open_table(U64_TABLE).unwrap().insert(100, 100).unwrap();
+    txn.abort().unwrap();
+
+    let txn = db.begin_write().unwrap();
+    txn.rename_table(STR_TABLE, TableDefinition::<&str, &str>::new("renamed"))
+        .unwrap();
+    txn.commit().unwrap();
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 3);
+    assert!(events[0].transaction_id() < events[1].transaction_id());
+    assert!(events[1].transaction_id() < events[2].transaction_id());
+
+    assert_eq!(events[0].tables(), ["u64", "x"]);
+    let changes = events[0].key_changes().unwrap();
+    assert_eq!(changes.len(), 11);
+    assert!(changes.iter().all(|x| x.kind() == ChangeKind::Inserted));
+    assert_eq!(changes[10].table(), "x");
+    assert_eq!(<&str>::from_bytes(changes[10].key()), "hello");
+
+    assert_eq!(events[1].tables(), ["multimap", "u64"]);
+    let changes: Vec<(u64, ChangeKind)> = events[1]
+        .key_changes()
+        .unwrap()
+        .iter()
+        .map(|x| {
+            assert_eq!(x.table(), "u64");
+            (u64::from_bytes(x.key()), x.kind())
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            (0, ChangeKind::Updated),
+            (1, ChangeKind::Removed),
+            (2, ChangeKind::Removed),
+            (3, ChangeKind::Removed),
+            (10, ChangeKind::Inserted),
+        ]
+    );
+
+    assert_eq!(events[2].tables(), ["renamed", "x"]);
+    assert!(events[2].key_changes().unwrap().is_empty());
+}
+
+#[test]
+fn write_ahead_log() {
+    let tmpfile = create_tempfile();
+    let mut log_path = tmpfile.path().as_os_str().to_owned();
+    log_path.push("-wal");
+
+    let db = Builder::new()
+        .set_write_ahead_log(true)
+        .create(tmpfile.path())
+        .unwrap();
+    for i in 0..10 {
+        let txn = db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(U64_TABLE).unwrap();
+            table.insert(&i, &i).unwrap();
+        }
+        txn.commit().unwrap();
+    }
+    assert!(fs::metadata(&log_path).unwrap().len() > 0);
+
+    db.checkpoint().unwrap();
+    assert_eq!(fs::metadata(&log_path).unwrap().len(), 0);
+
+    let mut txn = db.begin_write().unwrap();
+    txn.set_durability(Durability::None).unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        table.remove(&0).unwrap();
+    }
+    txn.commit().unwrap();
+    drop(db);
+    assert_eq!(fs::metadata(&log_path).unwrap().len(), 0);
+
+    let mut db = Builder::new()
+        .set_write_ahead_log(true)
+        .open(tmpfile.path())
+        .unwrap();
+    assert!(db.check_integrity().unwrap());
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 9);
+    assert_eq!(table.get(&9).unwrap().unwrap().value(), 9);
+    drop(table);
+    drop(txn);
+    drop(db);
+    fs::remove_file(log_path).unwrap();
+}
+
+#[test]
+fn write_ahead_log_size_checkpoint() {
+    let tmpfile = create_tempfile();
+    let mut log_path = tmpfile.path().as_os_str().to_owned();
+    log_path.push("-wal");
+
+    let db = Builder::new()
+        .set_write_ahead_log(true)
+        .create(tmpfile.path())
+        .unwrap();
+    let value = vec![7u8; 65 * 1024 * 1024];
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(SLICE_TABLE).unwrap();
+        table.insert(b"big".as_slice(), value.as_slice()).unwrap();
+    }
+    txn.commit().unwrap();
+    // The checkpoint runs after the write lock is released, but before commit() returns
+    assert_eq!(fs::metadata(&log_path).unwrap().len(), 0);
+
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(SLICE_TABLE).unwrap();
+        table.insert(b"small".as_slice(), b"1".as_slice()).unwrap();
+    }
+    txn.commit().unwrap();
+    assert!(fs::metadata(&log_path).unwrap().len() > 0);
+    drop(db);
+
+    let db = Builder::new()
+        .set_write_ahead_log(true)
+        .open(tmpfile.path())
+        .unwrap();
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(SLICE_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 2);
+    assert_eq!(
+        table.get(b"big".as_slice()).unwrap().unwrap().value(),
+        value
+    );
+    drop(table);
+    drop(txn);
+    drop(db);
+    fs::remove_file(log_path).unwrap();
+}
+
+#[test]
+fn write_ahead_log_recovery() {
+    // Shares its storage between databases, and simulates a crash by discarding all writes
+    #[derive(Debug)]
+    struct SharedBackend {
+        inner: Arc<redb::backends::InMemoryBackend>,
+        crashed: Arc<AtomicBool>,
+    }
+
+    impl SharedBackend {
+        fn check_crashed(&self) -> Result<(), std::io::Error> {
+            if self.crashed.load(Ordering::SeqCst) {
+                Err(std::io::Error::from(ErrorKind::Other))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl StorageBackend for SharedBackend {
+        fn len(&self) -> Result<u64, std::io::Error> {
+            self.inner.len()
+        }
+
+        fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, std::io::Error> {
+            self.inner.read(offset, len)
+        }
+
+        fn set_len(&self, len: u64) -> Result<(), std::io::Error> {
+            self.check_crashed()?;
+            self.inner.set_len(len)
+        }
+
+        fn sync_data(&self, eventual: bool) -> Result<(), std::io::Error> {
+            self.check_crashed()?;
+            self.inner.sync_data(eventual)
+        }
+
+        fn write(&self, offset: u64, data: &[u8]) -> Result<(), std::io::Error> {
+            self.check_crashed()?;
+            self.inner.write(offset, data)
+        }
+    }
+
+    let file = Arc::new(redb::backends::InMemoryBackend::new());
+    let log = Arc::new(redb::backends::InMemoryBackend::new());
+    let open = |crashed: &Arc<AtomicBool>| {
+        Builder::new()
+            .create_with_write_ahead_log(
+                SharedBackend {
+                    inner: file.clone(),
+                    crashed: crashed.clone(),
+                },
+                SharedBackend {
+                    inner: log.clone(),
+                    crashed: crashed.clone(),
+                },
+            )
+            .unwrap()
+    };
+
+    let crashed = Arc::new(AtomicBool::new(false));
+    let db = open(&crashed);
+    for i in 0..11 {
+        let txn = db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(U64_TABLE).unwrap();
+            table.insert(&i, &i).unwrap();
+        }
+        txn.commit().unwrap();
+    }
+    crashed.store(true, Ordering::SeqCst);
+    drop(db);
+
+    let crashed = Arc::new(AtomicBool::new(false));
+    let mut db = open(&crashed);
+    assert!(db.check_integrity().unwrap());
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 11);
+    drop(table);
+    drop(txn);
+
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        table.remove(&0).unwrap();
+    }
+    txn.commit().unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        table.insert(&11, &11).unwrap();
+    }
+    txn.commit().unwrap();
+    crashed.store(true, Ordering::SeqCst);
+    drop(db);
+    // Tear the last record, so that only the last commit is lost
+    log.set_len(log.len().unwrap() - 1).unwrap();
+
+    let db = open(&Arc::new(AtomicBool::new(false)));
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 10);
+    assert!(table.get(&0).unwrap().is_none());
+    assert!(table.get(&11).unwrap().is_none());
+}
+
+// Not secure. XORs each page with the key and its offset
+#[derive(Debug)]
+struct XorEncryption {
+    key: u8,
+}
+
+impl PageEncryption for XorEncryption {
+    fn encrypt(&self, offset: u64, page: &mut [u8]) -> Result<(), std::io::Error> {
+        let tweak = (offset / page.len() as u64) as u8;
+        for byte in page {
+            *byte ^= self.key.wrapping_add(tweak);
+        }
+        Ok(())
+    }
+
+    fn decrypt(&self, offset: u64, page: &mut [u8]) -> Result<(), std::io::Error> {
+        self.encrypt(offset, page)
+    }
+}
+
+#[test]
+fn encryption() {
+    let secret = "secret value".repeat(10);
+    let tmpfile = create_tempfile();
+    let mut log_path = tmpfile.path().as_os_str().to_owned();
+    log_path.push("-wal");
+
+    let db = Builder::new()
+        .set_encryption(XorEncryption { key: 0x5a })
+        .set_write_ahead_log(true)
+        .create(tmpfile.path())
+        .unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(STR_TABLE).unwrap();
+        table.insert("hello", secret.as_str()).unwrap();
+    }
+    txn.commit().unwrap();
+    let contains_secret = |data: &[u8]| {
+        data.windows(secret.len())
+            .any(|window| window == secret.as_bytes())
+    };
+    assert!(!contains_secret(&fs::read(&log_path).unwrap()));
+    drop(db);
+    assert!(!contains_secret(&fs::read(tmpfile.path()).unwrap()));
+
+    // Encrypted databases use file format version 4, so that versions which do not support
+    // encryption refuse to open them, even though they ignore the encryption flag
+    let image = fs::read(tmpfile.path()).unwrap();
+    assert_eq!(image[64], 4);
+    assert_eq!(image[192], 4);
+    let modified = create_tempfile();
+    let mut unflagged = image.clone();
+    unflagged[9] &= !8;
+    fs::write(modified.path(), unflagged).unwrap();
+    assert!(matches!(
+        Database::open(modified.path()),
+        Err(DatabaseError::Storage(StorageError::Corrupted(_)))
+    ));
+    let mut unknown_flag = image;
+    unknown_flag[9] |= 0x80;
+    fs::write(modified.path(), unknown_flag).unwrap();
+    assert!(matches!(
+        Database::open(modified.path()),
+        Err(DatabaseError::Storage(StorageError::Corrupted(_)))
+    ));
+
+    assert!(matches!(
+        Database::open(tmpfile.path()),
+        Err(DatabaseError::EncryptionKeyMismatch)
+    ));
+    assert!(matches!(
+        Builder::new()
+            .set_encryption(XorEncryption { key: 0x33 })
+            .open(tmpfile.path()),
+        Err(DatabaseError::EncryptionKeyMismatch)
+    ));
+
+    let mut db = Builder::new()
+        .set_encryption(XorEncryption { key: 0x5a })
+        .open(tmpfile.path())
+        .unwrap();
+    assert!(db.check_integrity().unwrap());
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(STR_TABLE).unwrap();
+    assert_eq!(table.get("hello").unwrap().unwrap().value(), secret);
+    drop(table);
+    drop(txn);
+
+    let backup = create_tempfile();
+    fs::remove_file(backup.path()).unwrap();
+    db.backup_to(backup.path()).unwrap();
+    assert!(!contains_secret(&fs::read(backup.path()).unwrap()));
+    assert!(matches!(
+        Database::open(backup.path()),
+        Err(DatabaseError::EncryptionKeyMismatch)
+    ));
+    let copy = Builder::new()
+        .set_encryption(XorEncryption { key: 0x5a })
+        .open(backup.path())
+        .unwrap();
+    let txn = copy.begin_read().unwrap();
+    let table = txn.open_table(STR_TABLE).unwrap();
+    assert_eq!(table.get("hello").unwrap().unwrap().value(), secret);
+    drop(table);
+    drop(txn);
+
+    let dir = tempfile::tempdir().unwrap();
+    let full = dir.path().join("full");
+    let restored = dir.path().join("restored");
+    db.incremental_backup_to(&full, &[] as &[&std::path::Path])
+        .unwrap();
+    assert!(!contains_secret(&fs::read(&full).unwrap()));
+    Builder::new()
+        .set_encryption(XorEncryption { key: 0x5a })
+        .restore_backup_chain(&[&full], &restored)
+        .unwrap();
+    let restored = Builder::new()
+        .set_encryption(XorEncryption { key: 0x5a })
+        .open(&restored)
+        .unwrap();
+    let txn = restored.begin_read().unwrap();
+    let table = txn.open_table(STR_TABLE).unwrap();
+    assert_eq!(table.get("hello").unwrap().unwrap().value(), secret);
+
+    let plain = create_tempfile();
+    Database::create(plain.path()).unwrap();
+    assert!(matches!(
+        Builder::new()
+            .set_encryption(XorEncryption { key: 0x5a })
+            .open(plain.path()),
+        Err(DatabaseError::EncryptionKeyMismatch)
+    ));
+    fs::remove_file(log_path).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn memory_mapped_reads() {
+    let tmpfile = create_tempfile();
+    let table_def: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+
+    let db = Builder::new()
+        .set_memory_mapped_reads(true)
+        .create(tmpfile.path())
+        .unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(table_def).unwrap();
+        for i in 0..100u64 {
+            table.insert(&i, [1u8; 1000].as_slice()).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(table_def).unwrap();
+    let guard = table.get(&7).unwrap().unwrap();
+    drop(table);
+    drop(txn);
+
+    // Overwrite every value, and grow the file, while the guard is still alive
+    for value in [2u8, 3] {
+        let txn = db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(table_def).unwrap();
+            for i in 0..1000u64 {
+                table.insert(&i, [value; 1000].as_slice()).unwrap();
+            }
+        }
+        txn.commit().unwrap();
+    }
+    assert_eq!(guard.value(), [1u8; 1000].as_slice());
+
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(table_def).unwrap();
+    assert_eq!(
+        table.get(&7).unwrap().unwrap().value(),
+        [3u8; 1000].as_slice()
+    );
+    assert_eq!(
+        table.get(&999).unwrap().unwrap().value(),
+        [3u8; 1000].as_slice()
+    );
+    drop(table);
+    drop(txn);
+    drop(db);
+
+    // The database stays locked until the guard is dropped
+    assert!(matches!(
+        Database::open(tmpfile.path()),
+        Err(DatabaseError::DatabaseAlreadyOpen)
+    ));
+    assert_eq!(guard.value(), [1u8; 1000].as_slice());
+    drop(guard);
+
+    let mut db = Database::open(tmpfile.path()).unwrap();
+    assert!(db.check_integrity().unwrap());
+}
+
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+#[test]
+fn io_uring_backend() {
+    use redb::backends::IoUringBackend;
+    let table_def: TableDefinition<u64, &[u8]> = TableDefinition::new("x");
+
+    let tmpfile = create_tempfile();
+    let backend = IoUringBackend::new(tmpfile.reopen().unwrap()).unwrap();
+    // Later writes to the same offset must win, even if they are queued together
+    backend.set_len(4096).unwrap();
+    for i in 0..200u8 {
+        backend.write(0, &[i; 4096]).unwrap();
+    }
+    // Vectored writes are submitted without copying them into the queue
+    let data = [7u8; 100];
+    let writes: Vec<(u64, &[u8])> = (0..40).map(|i| (i * 100, data.as_slice())).collect();
+    backend.write_vectored(&writes).unwrap();
+    backend.sync_data(false).unwrap();
+    let read = backend.read(0, 4096).unwrap();
+    assert!(read[..4000].iter().all(|x| *x == 7));
+    assert!(read[4000..].iter().all(|x| *x == 199));
+    drop(backend);
+
+    let tmpfile = create_tempfile();
+    let backend = IoUringBackend::new(tmpfile.reopen().unwrap()).unwrap();
+    let db = Builder::new().create_with_backend(backend).unwrap();
+    for i in 0..10u64 {
+        let txn = db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(table_def).unwrap();
+            // Enough pages to fill the submission queue more than once
+            for j in 0..1000 {
+                table
+                    .insert(&(i * 1000 + j), [1u8; 1000].as_slice())
+                    .unwrap();
+            }
+        }
+        txn.commit().unwrap();
+    }
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(table_def).unwrap();
+    assert_eq!(table.len().unwrap(), 10_000);
+    drop(table);
+    drop(txn);
+    drop(db);
+
+    let mut db = Database::open(tmpfile.path()).unwrap();
+    assert!(db.check_integrity().unwrap());
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(table_def).unwrap();
+    assert_eq!(table.len().unwrap(), 10_000);
+    assert_eq!(
+        table.get(&9_999).unwrap().unwrap().value(),
+        [1u8; 1000].as_slice()
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn direct_io() {
+    let tmpfile = create_tempfile();
+    let backend = FileBackend::new_direct(tmpfile.reopen().unwrap()).unwrap();
+    // Writes which are not aligned to blocks must preserve the data around them
+    backend.write(0, &[1; 5000]).unwrap();
+    backend.write(100, &[2; 10]).unwrap();
+    assert_eq!(backend.len().unwrap(), 5000);
+    backend.write(5000, &[3; 10]).unwrap();
+    assert_eq!(backend.len().unwrap(), 5010);
+    assert_eq!(
+        backend.read(95, 20).unwrap(),
+        [&[1; 5][..], &[2; 10], &[1; 5]].concat()
+    );
+    assert_eq!(backend.read(4990, 20).unwrap(), [[1; 10], [3; 10]].concat());
+    assert!(backend.read(5000, 20).is_err());
+    drop(backend);
+
+    let backend = FileBackend::new_direct(tmpfile.reopen().unwrap()).unwrap();
+    backend.set_len(0).unwrap();
+    let db = Builder::new().create_with_backend(backend).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+    drop(db);
+
+    let mut db = Database::open(tmpfile.path()).unwrap();
+    assert!(db.check_integrity().unwrap());
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.len().unwrap(), 1000);
+    assert_eq!(table.get(&999).unwrap().unwrap().value(), 999);
+}
+
+#[test]
+fn backend_read_into_and_write_vectored() {
+    // Panics if redb uses the methods which allocate or write a single array
+    #[derive(Debug)]
+    struct VectoredBackend {
+        inner: redb::backends::InMemoryBackend,
+        largest_write: Arc<AtomicUsize>,
+    }
+
+    impl StorageBackend for VectoredBackend {
+        fn len(&self) -> Result<u64, std::io::Error> {
+            self.inner.len()
+        }
+
+        fn read(&self, _: u64, _: usize) -> Result<Vec<u8>, std::io::Error> {
+            unreachable!()
+        }
+
+        fn read_into(&self, offset: u64, buffer: &mut [u8]) -> Result<(), std::io::Error> {
+            self.inner.read_into(offset, buffer)
+        }
+
+        fn set_len(&self, len: u64) -> Result<(), std::io::Error> {
+            self.inner.set_len(len)
+        }
+
+        fn sync_data(&self, eventual: bool) -> Result<(), std::io::Error> {
+            self.inner.sync_data(eventual)
+        }
+
+        fn write(&self, _: u64, _: &[u8]) -> Result<(), std::io::Error> {
+            unreachable!()
+        }
+
+        fn write_vectored(&self, writes: &[(u64, &[u8])]) -> Result<(), std::io::Error> {
+            self.largest_write.fetch_max(writes.len(), Ordering::SeqCst);
+            self.inner.write_vectored(writes)
+        }
+    }
+
+    let largest_write = Arc::new(AtomicUsize::new(0));
+    let backend = VectoredBackend {
+        inner: redb::backends::InMemoryBackend::new(),
+        largest_write: largest_write.clone(),
+    };
+    let db = Builder::new().create_with_backend(backend).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut table = txn.open_table(U64_TABLE).unwrap();
+        for i in 0..1000 {
+            table.insert(&i, &i).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+    // The pages written by the transaction are written together
+    assert!(largest_write.load(Ordering::SeqCst) > 1);
+
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(U64_TABLE).unwrap();
+    assert_eq!(table.get(&999).unwrap().unwrap().value(), 999);
+}
@@ -292,6 +292,48 @@ fn delete() {
     assert_eq!(empty, get_vec(&table, "hello"));
 }
 
+#[test]
+fn remove_range() {
+    let tmpfile = create_tempfile();
+    let mut db = Database::create(tmpfile.path()).unwrap();
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_multimap_table(U64_TABLE).unwrap();
+        for i in 0..10_000 {
+            table.insert(&i, &i).unwrap();
+            table.insert(&i, &(i + 1)).unwrap();
+        }
+        // Enough values to be stored in a separate tree
+        for i in 0..1000 {
+            table.insert(&5000, &(i + 10_000)).unwrap();
+        }
+    }
+    write_txn.commit().unwrap();
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_multimap_table(U64_TABLE).unwrap();
+        assert_eq!(table.remove_range(1000..6000).unwrap(), 11_000);
+        assert_eq!(table.remove_range(1000..6000).unwrap(), 0);
+        assert_eq!(table.len().unwrap(), 10_000);
+        assert!(table.get(&5000).unwrap().next().is_none());
+        assert_eq!(table.get(&999).unwrap().len(), 2);
+        assert_eq!(table.get(&6000).unwrap().len(), 2);
+        assert_eq!(table.range(1000..6000).unwrap().count(), 0);
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap());
+
+    let write_txn = db.begin_write().unwrap();
+    {
+        let mut table = write_txn.open_multimap_table(U64_TABLE).unwrap();
+        assert_eq!(table.remove_range::<u64>(..).unwrap(), 10_000);
+        assert!(table.is_empty().unwrap());
+    }
+    write_txn.commit().unwrap();
+    assert!(db.check_integrity().unwrap());
+}
+
 #[test]
 fn wrong_types() {
     let tmpfile = create_tempfile();
@@ -1,8 +1,9 @@
 #[cfg(not(target_os = "wasi"))]
 mod multithreading_test {
-    use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+    use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition, TransactionError};
     use std::sync::Arc;
     use std::thread;
+    use std::time::Duration;
 
     fn create_tempfile() -> tempfile::NamedTempFile {
         if cfg!(target_os = "wasi") {
@@ -112,4 +113,130 @@ mod multithreading_test {
         let table = read_txn.open_table(DEF3).unwrap();
         assert_eq!(table.len().unwrap(), 1);
     }
+
+    #[test]
+    fn try_begin_write() {
+        let tmpfile = create_tempfile();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.try_begin_write().unwrap();
+        assert!(matches!(
+            db.try_begin_write(),
+            Err(TransactionError::WriteTransactionInProgress)
+        ));
+        assert!(matches!(
+            db.begin_write_timeout(Duration::from_millis(10)),
+            Err(TransactionError::WriteTransactionInProgress)
+        ));
+        write_txn.abort().unwrap();
+
+        let write_txn = db.try_begin_write().unwrap();
+        write_txn.commit().unwrap();
+    }
+
+    #[test]
+    fn begin_write_timeout() {
+        let tmpfile = create_tempfile();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        thread::scope(|s| {
+            let t = s.spawn(|| {
+                let write_txn = db.begin_write_timeout(Duration::from_secs(60)).unwrap();
+                {
+                    let mut table = write_txn.open_table(TABLE).unwrap();
+                    table.insert("hello", "world").unwrap();
+                }
+                write_txn.commit().unwrap();
+            });
+            thread::sleep(Duration::from_millis(10));
+            write_txn.commit().unwrap();
+            t.join().unwrap();
+        });
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 1);
+    }
+
+    #[test]
+    fn wait_for_commit_after() {
+        let tmpfile = create_tempfile();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let id = read_txn.transaction_id();
+        drop(read_txn);
+        assert!(
+            !db.wait_for_commit_after(id, Duration::from_millis(10))
+                .unwrap()
+        );
+
+        thread::scope(|s| {
+            let t = s.spawn(|| {
+                assert!(
+                    db.wait_for_commit_after(id, Duration::from_secs(60))
+                        .unwrap()
+                );
+                let read_txn = db.begin_read().unwrap();
+                assert!(read_txn.transaction_id() > id);
+                let table = read_txn.open_table(TABLE).unwrap();
+                assert_eq!(table.get("hello").unwrap().unwrap().value(), "world");
+            });
+            thread::sleep(Duration::from_millis(10));
+            let write_txn = db.begin_write().unwrap();
+            {
+                let mut table = write_txn.open_table(TABLE).unwrap();
+                table.insert("hello", "world").unwrap();
+            }
+            write_txn.commit().unwrap();
+            t.join().unwrap();
+        });
+
+        // Returns immediately if a newer transaction is already visible
+        assert!(db.wait_for_commit_after(id, Duration::ZERO).unwrap());
+    }
+
+    #[test]
+    fn group_commit() {
+        let tmpfile = create_tempfile();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        const COUNTS: TableDefinition<u64, u64> = TableDefinition::new("counts");
+        let threads = 8;
+        let commits = 50;
+        thread::scope(|s| {
+            for i in 0..threads {
+                let db = &db;
+                s.spawn(move || {
+                    for j in 0..commits {
+                        let mut write_txn = db.begin_write().unwrap();
+                        write_txn.set_group_commit(true);
+                        {
+                            let mut table = write_txn.open_table(COUNTS).unwrap();
+                            table.insert(i, j + 1).unwrap();
+                            table.insert(threads + i * commits + j, j).unwrap();
+                        }
+                        write_txn.commit().unwrap();
+                    }
+                });
+            }
+        });
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(COUNTS).unwrap();
+        assert_eq!(table.len().unwrap(), threads + threads * commits);
+        for i in 0..threads {
+            assert_eq!(table.get(i).unwrap().unwrap().value(), commits);
+        }
+        drop(table);
+        drop(read_txn);
+        drop(db);
+
+        let mut db = Database::open(tmpfile.path()).unwrap();
+        assert!(db.check_integrity().unwrap());
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(COUNTS).unwrap();
+        assert_eq!(table.len().unwrap(), threads + threads * commits);
+    }
 }